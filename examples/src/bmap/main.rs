@@ -0,0 +1,243 @@
+//! A read only filesystem which has one file `data`, it reports an identity block map, the block
+//! `n` of the file is the block `n` of the device.
+//!
+//! It shows how a block mapped filesystem implements `bmap`, which is used by the `FIBMAP` ioctl.
+//! Kernel only sends bmap for the fuseblk mounts, on this mount `FIBMAP` always gets block 0.
+
+use std::env;
+use std::ffi::OsStr;
+use std::num::NonZeroU32;
+use std::time::{Duration, SystemTime};
+use std::vec::IntoIter;
+
+use bytes::Bytes;
+use fuse3::raw::prelude::*;
+use fuse3::{Errno, Inode, MountOptions, Result};
+use futures_util::stream::{self, Empty, Iter};
+use tokio::signal;
+use tracing::{debug, Level};
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INODE: Inode = 1;
+const DATA_INODE: Inode = 2;
+const DATA_NAME: &str = "data";
+const DATA_SIZE: u64 = 64 * 1024;
+const BLOCK_SIZE: u32 = 4096;
+
+fn new_attr(ino: Inode) -> FileAttr {
+    let (kind, perm, size) = if ino == ROOT_INODE {
+        (FileType::Directory, 0o555, 0)
+    } else {
+        (FileType::RegularFile, 0o444, DATA_SIZE)
+    };
+
+    FileAttr {
+        ino,
+        size,
+        blocks: size.div_ceil(512),
+        atime: SystemTime::UNIX_EPOCH.into(),
+        mtime: SystemTime::UNIX_EPOCH.into(),
+        ctime: SystemTime::UNIX_EPOCH.into(),
+        kind,
+        perm,
+        nlink: 1,
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+        rdev: 0,
+        blksize: BLOCK_SIZE,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Fs;
+
+impl Filesystem for Fs {
+    type DirEntryStream<'a>
+        = Empty<Result<DirectoryEntry>>
+    where
+        Self: 'a;
+
+    type DirEntryPlusStream<'a>
+        = Iter<IntoIter<Result<DirectoryEntryPlus>>>
+    where
+        Self: 'a;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: NonZeroU32::new(16 * 1024).unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn lookup(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<ReplyEntry> {
+        if parent != ROOT_INODE || name != DATA_NAME {
+            return Err(Errno::new_not_exist());
+        }
+
+        Ok(ReplyEntry {
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+            attr: new_attr(DATA_INODE),
+            generation: 0,
+        })
+    }
+
+    async fn getattr(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: Option<u64>,
+        _flags: GetattrFlags,
+    ) -> Result<ReplyAttr> {
+        if inode != ROOT_INODE && inode != DATA_INODE {
+            return Err(Errno::new_not_exist());
+        }
+
+        Ok(ReplyAttr {
+            ttl: TTL,
+            attr: new_attr(inode),
+        })
+    }
+
+    async fn open(&self, _req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
+        if inode != DATA_INODE {
+            return Err(Errno::new_is_dir());
+        }
+
+        if flags & libc::O_ACCMODE as u32 != libc::O_RDONLY as u32 {
+            return Err(libc::EROFS.into());
+        }
+
+        Ok(ReplyOpen { fh: 0, flags: 0 })
+    }
+
+    async fn read(
+        &self,
+        _req: Request,
+        _inode: Inode,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let size = DATA_SIZE.saturating_sub(offset).min(size as _);
+
+        Ok(ReplyData {
+            data: Bytes::from(vec![0; size as _]),
+        })
+    }
+
+    async fn bmap(
+        &self,
+        _req: Request,
+        inode: Inode,
+        blocksize: u32,
+        idx: u64,
+    ) -> Result<ReplyBmap> {
+        debug!("bmap inode {} blocksize {} idx {}", inode, blocksize, idx);
+
+        if inode != DATA_INODE {
+            return Err(libc::EINVAL.into());
+        }
+
+        if blocksize == 0 || idx >= DATA_SIZE.div_ceil(blocksize as _) {
+            return Err(libc::EINVAL.into());
+        }
+
+        // the identity block map
+        Ok(ReplyBmap { block: idx })
+    }
+
+    async fn readdirplus(
+        &self,
+        _req: Request,
+        parent: Inode,
+        _fh: u64,
+        offset: u64,
+        _lock_owner: u64,
+    ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'_>>> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        let entries = [
+            (ROOT_INODE, "."),
+            (ROOT_INODE, ".."),
+            (DATA_INODE, DATA_NAME),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (inode, name))| {
+            let attr = new_attr(inode);
+
+            Ok(DirectoryEntryPlus {
+                inode,
+                generation: 0,
+                kind: attr.kind,
+                name: name.into(),
+                offset: i as i64 + 1,
+                attr,
+                entry_ttl: TTL,
+                attr_ttl: TTL,
+            })
+        })
+        .skip(fuse3::offset_to_usize(offset)?)
+        .collect::<Vec<_>>();
+
+        Ok(ReplyDirectoryPlus {
+            parent: None,
+            entries: stream::iter(entries),
+        })
+    }
+}
+
+fn log_init() {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    log_init();
+
+    let args = env::args_os().skip(1).take(1).collect::<Vec<_>>();
+
+    let mount_path = args.first();
+
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+
+    let mut mount_options = MountOptions::default();
+    mount_options
+        .fs_name("bmap")
+        .read_only(true)
+        .uid(uid)
+        .gid(gid);
+
+    let mount_path = mount_path.expect("no mount point specified");
+
+    let not_unprivileged = env::var("NOT_UNPRIVILEGED").ok().as_deref() == Some("1");
+
+    let mut mount_handle = if !not_unprivileged {
+        Session::new(mount_options)
+            .mount_with_unprivileged(Fs, mount_path)
+            .await
+            .unwrap()
+    } else {
+        Session::new(mount_options)
+            .mount(Fs, mount_path)
+            .await
+            .unwrap()
+    };
+
+    let handle = &mut mount_handle;
+
+    tokio::select! {
+        res = handle => res.unwrap(),
+        _ = signal::ctrl_c() => {
+            mount_handle.unmount().await.unwrap()
+        }
+    }
+}
@@ -7,7 +7,7 @@ use std::vec::IntoIter;
 
 use bytes::Bytes;
 use fuse3::raw::prelude::*;
-use fuse3::{MountOptions, Result};
+use fuse3::{Errno, MountOptions, Result};
 use futures_util::stream;
 use futures_util::stream::Iter;
 use tracing::Level;
@@ -52,7 +52,8 @@ impl Filesystem for HelloWorld {
         }
 
         Ok(ReplyEntry {
-            ttl: TTL,
+            entry_ttl: TTL,
+            attr_ttl: TTL,
             attr: FileAttr {
                 ino: FILE_INODE,
                 size: CONTENT.len() as u64,
@@ -63,8 +64,9 @@ impl Filesystem for HelloWorld {
                 kind: FileType::RegularFile,
                 perm: FILE_MODE,
                 nlink: 0,
-                uid: 0,
-                gid: 0,
+                // the owner in attr is what `stat` shows, report the mount uid and gid
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
                 rdev: 0,
                 blksize: 0,
             },
@@ -77,7 +79,7 @@ impl Filesystem for HelloWorld {
         _req: Request,
         inode: u64,
         _fh: Option<u64>,
-        _flags: u32,
+        _flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
         if inode == PARENT_INODE {
             Ok(ReplyAttr {
@@ -92,8 +94,8 @@ impl Filesystem for HelloWorld {
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -111,8 +113,8 @@ impl Filesystem for HelloWorld {
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -142,10 +144,12 @@ impl Filesystem for HelloWorld {
             return Err(libc::ENOENT.into());
         }
 
-        if offset as usize >= CONTENT.len() {
+        let offset = fuse3::offset_to_usize(offset)?;
+
+        if offset >= CONTENT.len() {
             Ok(ReplyData { data: Bytes::new() })
         } else {
-            let mut data = &CONTENT.as_bytes()[offset as usize..];
+            let mut data = &CONTENT.as_bytes()[offset..];
 
             if data.len() > size as usize {
                 data = &data[..size as usize];
@@ -177,29 +181,21 @@ impl Filesystem for HelloWorld {
             return Err(libc::ENOENT.into());
         }
 
-        let entries = vec![
-            Ok(DirectoryEntry {
-                inode: PARENT_INODE,
-                kind: FileType::Directory,
-                name: OsString::from("."),
-                offset: 1,
-            }),
-            Ok(DirectoryEntry {
-                inode: PARENT_INODE,
-                kind: FileType::Directory,
-                name: OsString::from(".."),
-                offset: 2,
-            }),
-            Ok(DirectoryEntry {
-                inode: FILE_INODE,
-                kind: FileType::RegularFile,
-                name: OsString::from(FILE_NAME),
-                offset: 3,
-            }),
-        ];
+        // "." and ".." are synthesized by the session, offsets 1 and 2 are reserved for them
+        let entries = vec![Ok(DirectoryEntry {
+            inode: FILE_INODE,
+            kind: FileType::RegularFile,
+            name: OsString::from(FILE_NAME),
+            offset: 3,
+        })];
+
+        // the offset is never negative, it is replied by the filesystem
+        let offset = u64::try_from(offset).map_err(|_| Errno::from(libc::EINVAL))?;
+        let offset = fuse3::offset_to_usize(offset)?;
 
         Ok(ReplyDirectory {
-            entries: stream::iter(entries.into_iter().skip(offset as usize)),
+            parent: Some(PARENT_INODE),
+            entries: stream::iter(entries.into_iter().skip(offset.saturating_sub(2))),
         })
     }
 
@@ -232,83 +228,37 @@ impl Filesystem for HelloWorld {
             return Err(libc::ENOENT.into());
         }
 
-        let entries = vec![
-            Ok(DirectoryEntryPlus {
-                inode: PARENT_INODE,
-                generation: 0,
-                kind: FileType::Directory,
-                name: OsString::from("."),
-                offset: 1,
-                attr: FileAttr {
-                    ino: PARENT_INODE,
-                    size: 0,
-                    blocks: 0,
-                    atime: SystemTime::now().into(),
-                    mtime: SystemTime::now().into(),
-                    ctime: SystemTime::now().into(),
-                    kind: FileType::Directory,
-                    perm: PARENT_MODE,
-                    nlink: 0,
-                    uid: 0,
-                    gid: 0,
-                    rdev: 0,
-                    blksize: 0,
-                },
-                entry_ttl: TTL,
-                attr_ttl: TTL,
-            }),
-            Ok(DirectoryEntryPlus {
-                inode: PARENT_INODE,
-                generation: 0,
-                kind: FileType::Directory,
-                name: OsString::from(".."),
-                offset: 2,
-                attr: FileAttr {
-                    ino: PARENT_INODE,
-                    size: 0,
-                    blocks: 0,
-                    atime: SystemTime::now().into(),
-                    mtime: SystemTime::now().into(),
-                    ctime: SystemTime::now().into(),
-                    kind: FileType::Directory,
-                    perm: PARENT_MODE,
-                    nlink: 0,
-                    uid: 0,
-                    gid: 0,
-                    rdev: 0,
-                    blksize: 0,
-                },
-                entry_ttl: TTL,
-                attr_ttl: TTL,
-            }),
-            Ok(DirectoryEntryPlus {
-                inode: FILE_INODE,
-                generation: 0,
-                kind: FileType::Directory,
-                name: OsString::from(FILE_NAME),
-                offset: 3,
-                attr: FileAttr {
-                    ino: FILE_INODE,
-                    size: CONTENT.len() as _,
-                    blocks: 0,
-                    atime: SystemTime::now().into(),
-                    mtime: SystemTime::now().into(),
-                    ctime: SystemTime::now().into(),
-                    kind: FileType::RegularFile,
-                    perm: FILE_MODE,
-                    nlink: 0,
-                    uid: 0,
-                    gid: 0,
-                    rdev: 0,
-                    blksize: 0,
-                },
-                entry_ttl: TTL,
-                attr_ttl: TTL,
-            }),
-        ];
+        // "." and ".." are synthesized by the session, offsets 1 and 2 are reserved for them
+        let entries = vec![Ok(DirectoryEntryPlus {
+            inode: FILE_INODE,
+            generation: 0,
+            kind: FileType::Directory,
+            name: OsString::from(FILE_NAME),
+            offset: 3,
+            attr: FileAttr {
+                ino: FILE_INODE,
+                size: CONTENT.len() as _,
+                blocks: 0,
+                atime: SystemTime::now().into(),
+                mtime: SystemTime::now().into(),
+                ctime: SystemTime::now().into(),
+                kind: FileType::RegularFile,
+                perm: FILE_MODE,
+                nlink: 0,
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
+                rdev: 0,
+                blksize: 0,
+            },
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+        })];
+
+        let offset = fuse3::offset_to_usize(offset)?;
 
         Ok(ReplyDirectoryPlus {
-            entries: stream::iter(entries.into_iter().skip(offset as usize)),
+            parent: Some(PARENT_INODE),
+            entries: stream::iter(entries.into_iter().skip(offset.saturating_sub(2))),
         })
     }
 
@@ -329,11 +279,14 @@ async fn main() {
     let gid = unsafe { libc::getgid() };
 
     let mut mount_options = MountOptions::default();
-    mount_options.uid(uid).gid(gid).read_only(true);
+    mount_options
+        .uid(uid)
+        .gid(gid)
+        .read_only(true)
+        .synthesize_dot_entries(true);
 
     let mount_path = mount_path.expect("no mount point specified");
-    Session::new(mount_options)
-        .mount_with_unprivileged(HelloWorld {}, mount_path)
+    fuse3::mount_with_unprivileged(HelloWorld {}, mount_path, mount_options)
         .await
         .unwrap()
         .await
@@ -3,14 +3,16 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::{self, Cursor, Read};
 use std::num::NonZeroU32;
+use std::os::unix::ffi::OsStrExt;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use std::vec::IntoIter;
 
 use bytes::{Buf, BytesMut};
+use fuse3::raw::flags::RenameFlags;
 use fuse3::raw::prelude::*;
-use fuse3::{Errno, Inode, MountOptions, Result};
+use fuse3::{Errno, Inode, MountOptions, Opcode, Result, Timestamp};
 use futures_util::stream;
 use futures_util::stream::{Empty, Iter};
 use futures_util::StreamExt;
@@ -23,6 +25,9 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{fmt, Registry};
 
 const TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: f64 = 4096f64;
+// the content is kept in memory, the larger files are rejected with EFBIG
+const MAX_FILE_SIZE: usize = 1 << 30;
 
 #[derive(Debug, Clone)]
 enum Entry {
@@ -32,77 +37,63 @@ enum Entry {
 
 impl Entry {
     async fn attr(&self) -> FileAttr {
-        const BLOCK_SIZE: f64 = 4096f64;
-
         match self {
             Entry::Dir(dir) => {
                 let nlink = Arc::strong_count(dir) - 1;
-                let dir = dir.read().await;
 
-                FileAttr {
-                    ino: dir.inode,
-                    size: 4096,
-                    blocks: 1,
-                    atime: SystemTime::UNIX_EPOCH.into(),
-                    mtime: SystemTime::UNIX_EPOCH.into(),
-                    ctime: SystemTime::UNIX_EPOCH.into(),
-                    kind: FileType::Directory,
-                    perm: fuse3::perm_from_mode_and_kind(FileType::Directory, dir.mode),
-                    nlink: nlink as _,
-                    uid: 0,
-                    gid: 0,
-                    rdev: 0,
-                    blksize: BLOCK_SIZE as _,
-                }
+                dir.read().await.attr(nlink)
             }
 
             Entry::File(file) => {
                 let nlink = Arc::strong_count(file) - 1;
-                let file = file.read().await;
 
-                FileAttr {
-                    ino: file.inode,
-                    size: file.content.len() as _,
-                    blocks: (file.content.len() as f64 / BLOCK_SIZE).ceil() as _,
-                    atime: SystemTime::UNIX_EPOCH.into(),
-                    mtime: SystemTime::UNIX_EPOCH.into(),
-                    ctime: SystemTime::UNIX_EPOCH.into(),
-                    kind: FileType::RegularFile,
-                    perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, file.mode),
-                    nlink: nlink as _,
-                    uid: 0,
-                    gid: 0,
-                    rdev: 0,
-                    blksize: BLOCK_SIZE as _,
-                }
+                file.read().await.attr(nlink)
             }
         }
     }
 
-    async fn set_attr(&self, set_attr: SetAttr) -> FileAttr {
+    async fn set_attr(&self, set_attr: SetAttr) -> Result<FileAttr> {
+        // the attr is built before releasing the lock, otherwise a concurrent write may change
+        // the content between the truncate and the reply, and the reply size is torn
         match self {
             Entry::Dir(dir) => {
+                let nlink = Arc::strong_count(dir) - 1;
                 let mut dir = dir.write().await;
 
                 if let Some(mode) = set_attr.mode {
                     dir.mode = mode;
                 }
+
+                dir.times.set(&set_attr);
+
+                Ok(dir.attr(nlink))
             }
 
             Entry::File(file) => {
+                let nlink = Arc::strong_count(file) - 1;
                 let mut file = file.write().await;
 
                 if let Some(size) = set_attr.size {
-                    file.content.truncate(size as _);
+                    let size = file_size(size)?;
+                    if size <= file.content.len() {
+                        file.content.truncate(size);
+                    } else {
+                        // ftruncate to a larger size, the hole is filled with zero
+                        file.content.resize(size, 0);
+                    }
+
+                    file.dirty = true;
                 }
 
                 if let Some(mode) = set_attr.mode {
                     file.mode = mode;
                 }
+
+                file.times.set(&set_attr);
+
+                Ok(file.attr(nlink))
             }
         }
-
-        self.attr().await
     }
 
     fn is_dir(&self) -> bool {
@@ -125,14 +116,6 @@ impl Entry {
         }
     }
 
-    fn kind(&self) -> FileType {
-        if self.is_dir() {
-            FileType::Directory
-        } else {
-            FileType::RegularFile
-        }
-    }
-
     async fn name(&self) -> OsString {
         match self {
             Entry::Dir(dir) => dir.read().await.name.clone(),
@@ -148,6 +131,7 @@ struct Dir {
     name: OsString,
     children: BTreeMap<OsString, Entry>,
     mode: mode_t,
+    times: Times,
 }
 
 #[derive(Debug)]
@@ -155,14 +139,111 @@ struct File {
     inode: u64,
     parent: u64,
     name: OsString,
+    // a regular file or a symbolic link, the content of a symbolic link is its target
+    kind: FileType,
     content: Vec<u8>,
     mode: mode_t,
+    times: Times,
+    // content is changed and not persisted yet
+    dirty: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Times {
+    atime: Timestamp,
+    mtime: Timestamp,
+    ctime: Timestamp,
+}
+
+impl Times {
+    fn now() -> Self {
+        let now = SystemTime::now().into();
+
+        Self {
+            atime: now,
+            mtime: now,
+            ctime: now,
+        }
+    }
+
+    fn set(&mut self, set_attr: &SetAttr) {
+        if let Some(atime) = set_attr.atime {
+            self.atime = atime;
+        }
+
+        if let Some(mtime) = set_attr.mtime {
+            self.mtime = mtime;
+        }
+
+        // any metadata change bumps the ctime, even only the atime or mtime is set
+        self.ctime = set_attr.ctime.unwrap_or_else(|| SystemTime::now().into());
+    }
+}
+
+impl Dir {
+    fn attr(&self, nlink: usize) -> FileAttr {
+        FileAttr {
+            ino: self.inode,
+            size: 4096,
+            blocks: 1,
+            atime: self.times.atime,
+            mtime: self.times.mtime,
+            ctime: self.times.ctime,
+            kind: FileType::Directory,
+            perm: fuse3::perm_from_mode_and_kind(FileType::Directory, self.mode),
+            nlink: nlink as _,
+            // the owner in attr is what `stat` shows, report the mount uid and gid
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: BLOCK_SIZE as _,
+        }
+    }
+}
+
+impl File {
+    fn attr(&self, nlink: usize) -> FileAttr {
+        FileAttr {
+            ino: self.inode,
+            size: self.content.len() as _,
+            blocks: (self.content.len() as f64 / BLOCK_SIZE).ceil() as _,
+            atime: self.times.atime,
+            mtime: self.times.mtime,
+            ctime: self.times.ctime,
+            kind: self.kind,
+            perm: fuse3::perm_from_mode_and_kind(self.kind, self.mode),
+            nlink: nlink as _,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: BLOCK_SIZE as _,
+        }
+    }
 }
 
 #[derive(Debug)]
 struct InnerFs {
     inode_map: BTreeMap<u64, Entry>,
     inode_gen: AtomicU64,
+    // the open flags of the opened files
+    fh_map: BTreeMap<u64, u32>,
+    fh_gen: AtomicU64,
+}
+
+impl InnerFs {
+    fn new_fh(&mut self, flags: u32) -> u64 {
+        let fh = self.fh_gen.fetch_add(1, Ordering::Relaxed);
+
+        self.fh_map.insert(fh, flags);
+
+        fh
+    }
+
+    fn is_append(&self, fh: u64) -> bool {
+        self.fh_map
+            .get(&fh)
+            .is_some_and(|flags| flags & libc::O_APPEND as u32 != 0)
+    }
 }
 
 #[derive(Debug)]
@@ -176,6 +257,7 @@ impl Default for Fs {
             name: OsString::from("/"),
             children: BTreeMap::new(),
             mode: 0o755,
+            times: Times::now(),
         })));
 
         let mut inode_map = BTreeMap::new();
@@ -185,6 +267,8 @@ impl Default for Fs {
         Self(RwLock::new(InnerFs {
             inode_map,
             inode_gen: AtomicU64::new(2),
+            fh_map: BTreeMap::new(),
+            fh_gen: AtomicU64::new(1),
         }))
     }
 }
@@ -205,6 +289,13 @@ impl Filesystem for Fs {
         info!("destroy done")
     }
 
+    fn capabilities(&self) -> FsCapabilities {
+        // memfs isn't backed by a block device and its files are always ready
+        FsCapabilities::all()
+            .without(Opcode::FUSE_BMAP)
+            .without(Opcode::FUSE_POLL)
+    }
+
     async fn lookup(&self, _req: Request, parent: u64, name: &OsStr) -> Result<ReplyEntry> {
         let inner = self.0.read().await;
 
@@ -224,7 +315,8 @@ impl Filesystem for Fs {
                 .await;
 
             Ok(ReplyEntry {
-                ttl: TTL,
+                entry_ttl: TTL,
+                attr_ttl: TTL,
                 attr,
                 generation: 0,
             })
@@ -240,7 +332,7 @@ impl Filesystem for Fs {
         _req: Request,
         inode: u64,
         _fh: Option<u64>,
-        _flags: u32,
+        _flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
         Ok(ReplyAttr {
             ttl: TTL,
@@ -273,10 +365,83 @@ impl Filesystem for Fs {
                 .get(&inode)
                 .ok_or_else(|| Errno::from(libc::ENOENT))?
                 .set_attr(set_attr)
-                .await,
+                .await?,
         })
     }
 
+    async fn readlink(&self, _req: Request, inode: u64) -> Result<ReplyData> {
+        let inner = self.0.read().await;
+
+        let entry = inner
+            .inode_map
+            .get(&inode)
+            .ok_or_else(|| Errno::from(libc::ENOENT))?;
+
+        if let Entry::File(file) = entry {
+            let file = file.read().await;
+
+            if file.kind == FileType::Symlink {
+                return Ok(ReplyData::symlink(OsStr::from_bytes(&file.content)));
+            }
+        }
+
+        Err(libc::EINVAL.into())
+    }
+
+    async fn symlink(
+        &self,
+        _req: Request,
+        parent: u64,
+        name: &OsStr,
+        link: &OsStr,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyEntry> {
+        let mut inner = self.0.write().await;
+
+        let entry = inner
+            .inode_map
+            .get(&parent)
+            .ok_or_else(|| Errno::from(libc::ENOENT))?;
+
+        if let Entry::Dir(dir) = entry {
+            let mut dir = dir.write().await;
+
+            if dir.children.contains_key(name) {
+                return Err(libc::EEXIST.into());
+            }
+
+            let new_inode = inner.inode_gen.fetch_add(1, Ordering::Relaxed);
+
+            let entry = Entry::File(Arc::new(RwLock::new(File {
+                inode: new_inode,
+                parent,
+                name: name.to_os_string(),
+                kind: FileType::Symlink,
+                content: link.as_bytes().to_vec(),
+                dirty: false,
+                mode: 0o777,
+                times: Times::now(),
+            })));
+
+            let attr = entry.attr().await;
+
+            dir.children.insert(name.to_os_string(), entry.clone());
+
+            drop(dir);
+
+            inner.inode_map.insert(new_inode, entry);
+
+            Ok(ReplyEntry {
+                entry_ttl: TTL,
+                attr_ttl: TTL,
+                attr,
+                generation: 0,
+            })
+        } else {
+            Err(libc::ENOTDIR.into())
+        }
+    }
+
     async fn mkdir(
         &self,
         _req: Request,
@@ -284,6 +449,7 @@ impl Filesystem for Fs {
         name: &OsStr,
         mode: u32,
         _umask: u32,
+        _security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inner = self.0.write().await;
 
@@ -307,6 +473,7 @@ impl Filesystem for Fs {
                 name: name.to_owned(),
                 children: BTreeMap::new(),
                 mode: mode as mode_t,
+                times: Times::now(),
             })));
 
             let attr = entry.attr().await;
@@ -318,7 +485,8 @@ impl Filesystem for Fs {
             inner.inode_map.insert(new_inode, entry);
 
             Ok(ReplyEntry {
-                ttl: TTL,
+                entry_ttl: TTL,
+                attr_ttl: TTL,
                 attr,
                 generation: 0,
             })
@@ -493,14 +661,15 @@ impl Filesystem for Fs {
         }
 
         Ok(ReplyEntry {
-            ttl: TTL,
+            entry_ttl: TTL,
+            attr_ttl: TTL,
             attr: entry.attr().await,
             generation: 0,
         })
     }
 
-    async fn open(&self, _req: Request, inode: u64, _flags: u32) -> Result<ReplyOpen> {
-        let inner = self.0.read().await;
+    async fn open(&self, _req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
+        let mut inner = self.0.write().await;
 
         let entry = inner
             .inode_map
@@ -508,7 +677,10 @@ impl Filesystem for Fs {
             .ok_or_else(|| Errno::from(libc::ENOENT))?;
 
         if matches!(entry, Entry::File(_)) {
-            Ok(ReplyOpen { fh: 0, flags: 0 })
+            Ok(ReplyOpen {
+                fh: inner.new_fh(flags),
+                flags: 0,
+            })
         } else {
             Err(libc::EISDIR.into())
         }
@@ -555,7 +727,7 @@ impl Filesystem for Fs {
         &self,
         _req: Request,
         inode: u64,
-        _fh: u64,
+        fh: u64,
         offset: u64,
         mut data: &[u8],
         _write_flags: u32,
@@ -571,8 +743,21 @@ impl Filesystem for Fs {
         if let Entry::File(file) = entry {
             let mut file = file.write().await;
 
-            if file.content.len() > offset as _ {
-                let mut content = &mut file.content[offset as _..];
+            // the offset kernel sends may be stale, the append write always goes to the end of
+            // the file
+            let offset = if inner.is_append(fh) {
+                file.content.len()
+            } else {
+                fuse3::offset_to_usize(offset)?
+            };
+            if offset.saturating_add(data.len()) > MAX_FILE_SIZE {
+                return Err(libc::EFBIG.into());
+            }
+
+            file.dirty = true;
+
+            if file.content.len() > offset {
+                let mut content = &mut file.content[offset..];
 
                 if content.len() > data.len() {
                     io::copy(&mut data, &mut content).unwrap();
@@ -590,7 +775,7 @@ impl Filesystem for Fs {
                     written: data.len() as _,
                 })
             } else {
-                file.content.resize(offset as _, 0);
+                file.content.resize(offset, 0);
 
                 file.content.extend_from_slice(data);
 
@@ -606,12 +791,39 @@ impl Filesystem for Fs {
     async fn release(
         &self,
         _req: Request,
-        _inode: u64,
-        _fh: u64,
+        inode: u64,
+        fh: u64,
         _flags: u32,
         _lock_owner: u64,
-        _flush: bool,
+        _release_flags: ReleaseFlags,
     ) -> Result<()> {
+        let mut inner = self.0.write().await;
+
+        inner.fh_map.remove(&fh);
+
+        // flush may be called many times when fd is duplicated, but release is called only once
+        // when the last fd is closed, so persist data here
+        if let Some(Entry::File(file)) = inner.inode_map.get(&inode) {
+            // the inode map holds the only reference, it is an unnamed tmpfile never linked
+            let unnamed = Arc::strong_count(file) == 1;
+
+            let mut file = file.write().await;
+
+            if file.dirty {
+                file.dirty = false;
+
+                debug!(inode, "persist file content on release");
+            }
+
+            drop(file);
+
+            if unnamed {
+                debug!(inode, "remove unnamed tmpfile on release");
+
+                inner.inode_map.remove(&inode);
+            }
+        }
+
         Ok(())
     }
 
@@ -634,6 +846,7 @@ impl Filesystem for Fs {
         name: &OsStr,
         mode: u32,
         flags: u32,
+        _security_context: Option<SecurityContext>,
     ) -> Result<ReplyCreated> {
         let mut inner = self.0.write().await;
 
@@ -645,8 +858,34 @@ impl Filesystem for Fs {
         if let Entry::Dir(dir) = entry {
             let mut dir = dir.write().await;
 
-            if dir.children.get(name).is_some() {
-                return Err(libc::EEXIST.into());
+            // the file is created by others after the lookup, open it unless O_EXCL is set
+            if let Some(child) = dir.children.get(name) {
+                if flags & libc::O_EXCL as u32 > 0 {
+                    return Err(libc::EEXIST.into());
+                }
+
+                let Entry::File(file) = child else {
+                    return Err(libc::EISDIR.into());
+                };
+
+                if flags & libc::O_TRUNC as u32 > 0 {
+                    let mut file = file.write().await;
+
+                    file.content.clear();
+                    file.dirty = true;
+                }
+
+                let attr = child.attr().await;
+
+                drop(dir);
+
+                return Ok(ReplyCreated {
+                    ttl: TTL,
+                    attr,
+                    generation: 0,
+                    fh: inner.new_fh(flags),
+                    flags: 0,
+                });
             }
 
             let new_inode = inner.inode_gen.fetch_add(1, Ordering::Relaxed);
@@ -655,8 +894,11 @@ impl Filesystem for Fs {
                 inode: new_inode,
                 parent,
                 name: name.to_os_string(),
+                kind: FileType::RegularFile,
                 content: vec![],
+                dirty: false,
                 mode: mode as mode_t,
+                times: Times::now(),
             })));
 
             let attr = entry.attr().await;
@@ -671,14 +913,64 @@ impl Filesystem for Fs {
                 ttl: TTL,
                 attr,
                 generation: 0,
-                fh: 0,
-                flags,
+                fh: inner.new_fh(flags),
+                // the reply flags are the FOPEN_* flags, not the open flags
+                flags: 0,
             })
         } else {
             Err(libc::ENOTDIR.into())
         }
     }
 
+    async fn tmpfile(
+        &self,
+        _req: Request,
+        parent: u64,
+        mode: u32,
+        flags: u32,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        let mut inner = self.0.write().await;
+
+        if !inner
+            .inode_map
+            .get(&parent)
+            .ok_or_else(|| Errno::from(libc::ENOENT))?
+            .is_dir()
+        {
+            return Err(libc::ENOTDIR.into());
+        }
+
+        let new_inode = inner.inode_gen.fetch_add(1, Ordering::Relaxed);
+
+        // the unnamed file is only in the inode map, link will add it to a directory
+        let entry = Entry::File(Arc::new(RwLock::new(File {
+            inode: new_inode,
+            parent,
+            name: OsString::new(),
+            kind: FileType::RegularFile,
+            content: vec![],
+            dirty: false,
+            mode: mode as mode_t,
+            times: Times::now(),
+        })));
+
+        let mut attr = entry.attr().await;
+        // kernel drops the nlink of the unnamed file to 0 itself
+        attr.nlink = 1;
+
+        inner.inode_map.insert(new_inode, entry);
+
+        Ok(ReplyCreated {
+            ttl: TTL,
+            attr,
+            generation: 0,
+            fh: inner.new_fh(flags),
+            // the reply flags are the FOPEN_* flags, not the open flags
+            flags: 0,
+        })
+    }
+
     async fn interrupt(&self, _req: Request, _unique: u64) -> Result<()> {
         Ok(())
     }
@@ -702,7 +994,10 @@ impl Filesystem for Fs {
         if let Entry::File(file) = entry {
             let mut file = file.write().await;
 
-            let new_size = (offset + length) as usize;
+            let new_size = offset
+                .checked_add(length)
+                .ok_or_else(|| Errno::from(libc::EFBIG))?;
+            let new_size = file_size(new_size)?;
 
             let size = file.content.len();
 
@@ -774,7 +1069,7 @@ impl Filesystem for Fs {
                         let inode = entry.inode().await;
                         let attr = entry.attr().await;
 
-                        Some((inode, entry.kind(), name.to_os_string(), attr, i as i64 + 3))
+                        Some((inode, attr.kind, name.to_os_string(), attr, i as i64 + 3))
                     },
                 ))
                 .map(|(inode, kind, name, attr, offset)| DirectoryEntryPlus {
@@ -787,12 +1082,13 @@ impl Filesystem for Fs {
                     entry_ttl: TTL,
                     attr_ttl: TTL,
                 })
-                .skip(offset as _)
+                .skip(fuse3::offset_to_usize(offset)?)
                 .map(Ok)
                 .collect::<Vec<_>>()
                 .await;
 
             Ok(ReplyDirectoryPlus {
+                parent: None,
                 entries: stream::iter(children),
             })
         } else {
@@ -807,8 +1103,13 @@ impl Filesystem for Fs {
         name: &OsStr,
         new_parent: u64,
         new_name: &OsStr,
-        _flags: u32,
+        flags: u32,
     ) -> Result<()> {
+        // the memfs can't exchange or check the new name atomically
+        if !RenameFlags::from(flags).is_empty() {
+            return Err(libc::EINVAL.into());
+        }
+
         self.rename(req, parent, name, new_parent, new_name).await
     }
 
@@ -835,11 +1136,7 @@ impl Filesystem for Fs {
             } else if whence == libc::SEEK_END {
                 let content_size = file.read().await.content.len();
 
-                if content_size >= offset as _ {
-                    content_size as u64 - offset
-                } else {
-                    0
-                }
+                (content_size as u64).saturating_sub(offset)
             } else {
                 return Err(libc::EINVAL.into());
             };
@@ -862,12 +1159,17 @@ impl Filesystem for Fs {
         length: u64,
         flags: u64,
     ) -> Result<ReplyCopyFileRange> {
+        // the copy_file_range flags are not the write flags, no flag is defined yet
+        if flags != 0 {
+            return Err(libc::EINVAL.into());
+        }
+
         let data = self.read(req, inode, fh_in, off_in, length as _).await?;
 
         let data = data.data.as_ref();
 
         let ReplyWrite { written } = self
-            .write(req, inode_out, fh_out, off_out, data, 0, flags as _)
+            .write(req, inode_out, fh_out, off_out, data, 0, 0)
             .await?;
 
         Ok(ReplyCopyFileRange {
@@ -876,6 +1178,17 @@ impl Filesystem for Fs {
     }
 }
 
+/// convert the new size of a file, it is rejected with `EFBIG` when it is larger than
+/// [`MAX_FILE_SIZE`].
+fn file_size(size: u64) -> Result<usize> {
+    let size = fuse3::offset_to_usize(size)?;
+    if size > MAX_FILE_SIZE {
+        return Err(libc::EFBIG.into());
+    }
+
+    Ok(size)
+}
+
 fn log_init() {
     let layer = fmt::layer()
         .pretty()
@@ -0,0 +1,600 @@
+//! A memory filesystem which locks every inode separately.
+//!
+//! The memfs example uses one big lock for the whole tree, so every operation is serialized. Here
+//! the inode table lock is only held to find a node, and each node has its own lock, so reads and
+//! writes on different files never block each other.
+//!
+//! fuse3 enables `FUSE_PARALLEL_DIROPS` when kernel supports it, then kernel doesn't serialize
+//! lookup and readdir in the same directory. The filesystem must handle that itself: operations
+//! which only read a directory take the directory read lock, operations which change a directory
+//! take the directory write lock.
+//!
+//! To avoid deadlock, the parent directory is always locked before the child. When rename locks
+//! two directories, the smaller inode is locked first; kernel also serializes cross directory
+//! renames and doesn't run lookup in a directory which is being renamed into or out of.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use std::vec::IntoIter;
+
+use bytes::Bytes;
+use fuse3::raw::prelude::*;
+use fuse3::{Errno, Inode, MountOptions, Opcode, Priority, Result};
+use futures_util::stream;
+use futures_util::stream::{Empty, Iter};
+use libc::mode_t;
+use tokio::signal;
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use tracing::metadata::LevelFilter;
+use tracing::{info, subscriber};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{fmt, Registry};
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INODE: Inode = 1;
+
+#[derive(Debug)]
+enum Content {
+    Dir(BTreeMap<OsString, Inode>),
+    File(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Node {
+    inode: Inode,
+    parent: Inode,
+    mode: mode_t,
+    content: Content,
+}
+
+impl Node {
+    fn kind(&self) -> FileType {
+        match self.content {
+            Content::Dir(_) => FileType::Directory,
+            Content::File(_) => FileType::RegularFile,
+        }
+    }
+
+    fn attr(&self) -> FileAttr {
+        const BLOCK_SIZE: u64 = 4096;
+
+        let (size, nlink) = match &self.content {
+            Content::Dir(_) => (BLOCK_SIZE, 2),
+            Content::File(content) => (content.len() as u64, 1),
+        };
+
+        FileAttr {
+            ino: self.inode,
+            size,
+            blocks: size.div_ceil(BLOCK_SIZE),
+            atime: SystemTime::UNIX_EPOCH.into(),
+            mtime: SystemTime::UNIX_EPOCH.into(),
+            ctime: SystemTime::UNIX_EPOCH.into(),
+            kind: self.kind(),
+            perm: fuse3::perm_from_mode_and_kind(self.kind(), self.mode),
+            nlink,
+            // the owner in attr is what `stat` shows, report the mount uid and gid
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: BLOCK_SIZE as _,
+        }
+    }
+
+    fn children(&self) -> Result<&BTreeMap<OsString, Inode>> {
+        match &self.content {
+            Content::Dir(children) => Ok(children),
+            Content::File(_) => Err(Errno::new_is_not_dir()),
+        }
+    }
+
+    fn children_mut(&mut self) -> Result<&mut BTreeMap<OsString, Inode>> {
+        match &mut self.content {
+            Content::Dir(children) => Ok(children),
+            Content::File(_) => Err(Errno::new_is_not_dir()),
+        }
+    }
+
+    fn file_content_mut(&mut self) -> Result<&mut Vec<u8>> {
+        match &mut self.content {
+            Content::Dir(_) => Err(Errno::new_is_dir()),
+            Content::File(content) => Ok(content),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Fs {
+    // only hold this lock to find or insert a node, never hold it across an await point
+    nodes: Mutex<HashMap<Inode, Arc<RwLock<Node>>>>,
+    inode_gen: AtomicU64,
+}
+
+impl Default for Fs {
+    fn default() -> Self {
+        let root = Node {
+            inode: ROOT_INODE,
+            parent: ROOT_INODE,
+            mode: 0o755,
+            content: Content::Dir(BTreeMap::new()),
+        };
+
+        Self {
+            nodes: Mutex::new(HashMap::from([(ROOT_INODE, Arc::new(RwLock::new(root)))])),
+            inode_gen: AtomicU64::new(ROOT_INODE + 1),
+        }
+    }
+}
+
+impl Fs {
+    fn node(&self, inode: Inode) -> Result<Arc<RwLock<Node>>> {
+        self.nodes
+            .lock()
+            .unwrap()
+            .get(&inode)
+            .cloned()
+            .ok_or_else(Errno::new_not_exist)
+    }
+
+    fn insert_node(&self, node: Node) {
+        self.nodes
+            .lock()
+            .unwrap()
+            .insert(node.inode, Arc::new(RwLock::new(node)));
+    }
+
+    fn remove_node(&self, inode: Inode) {
+        self.nodes.lock().unwrap().remove(&inode);
+    }
+
+    async fn new_child(
+        &self,
+        parent: Inode,
+        name: &OsStr,
+        mode: u32,
+        content: Content,
+    ) -> Result<FileAttr> {
+        let parent_node = self.node(parent)?;
+        // lock the parent to make sure no one creates the same name at the same time
+        let mut parent_node = parent_node.write().await;
+        let children = parent_node.children_mut()?;
+
+        if children.contains_key(name) {
+            return Err(Errno::new_exist());
+        }
+
+        let inode = self.inode_gen.fetch_add(1, Ordering::Relaxed);
+        let node = Node {
+            inode,
+            parent,
+            mode: mode as mode_t,
+            content,
+        };
+        let attr = node.attr();
+
+        self.insert_node(node);
+        children.insert(name.to_owned(), inode);
+
+        Ok(attr)
+    }
+
+    async fn remove_child(&self, parent: Inode, name: &OsStr, is_dir: bool) -> Result<()> {
+        let parent_node = self.node(parent)?;
+        let mut parent_node = parent_node.write().await;
+        let children = parent_node.children_mut()?;
+
+        let inode = *children.get(name).ok_or_else(Errno::new_not_exist)?;
+        let node = self.node(inode)?;
+        let node = node.read().await;
+
+        match (&node.content, is_dir) {
+            (Content::Dir(children), true) if !children.is_empty() => {
+                return Err(libc::ENOTEMPTY.into())
+            }
+            (Content::Dir(_), false) => return Err(Errno::new_is_dir()),
+            (Content::File(_), true) => return Err(Errno::new_is_not_dir()),
+            _ => {}
+        }
+
+        children.remove(name);
+        self.remove_node(inode);
+
+        Ok(())
+    }
+}
+
+impl Filesystem for Fs {
+    type DirEntryStream<'a>
+        = Empty<Result<DirectoryEntry>>
+    where
+        Self: 'a;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: NonZeroU32::new(128 * 1024).unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {
+        info!("destroy done")
+    }
+
+    async fn lookup(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<ReplyEntry> {
+        let parent_node = self.node(parent)?;
+        // read lock, lookups in the same directory can run at the same time
+        let parent_node = parent_node.read().await;
+
+        let inode = *parent_node
+            .children()?
+            .get(name)
+            .ok_or_else(Errno::new_not_exist)?;
+        let attr = self.node(inode)?.read().await.attr();
+
+        Ok(ReplyEntry {
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+            attr,
+            generation: 0,
+        })
+    }
+
+    async fn getattr(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: Option<u64>,
+        _flags: GetattrFlags,
+    ) -> Result<ReplyAttr> {
+        Ok(ReplyAttr {
+            ttl: TTL,
+            attr: self.node(inode)?.read().await.attr(),
+        })
+    }
+
+    async fn setattr(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: Option<u64>,
+        set_attr: SetAttr,
+    ) -> Result<ReplyAttr> {
+        let node = self.node(inode)?;
+        let mut node = node.write().await;
+
+        if let Some(mode) = set_attr.mode {
+            node.mode = mode;
+        }
+
+        if let Some(size) = set_attr.size {
+            node.file_content_mut()?.resize(size as _, 0);
+        }
+
+        Ok(ReplyAttr {
+            ttl: TTL,
+            attr: node.attr(),
+        })
+    }
+
+    async fn mkdir(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        mode: u32,
+        _umask: u32,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyEntry> {
+        let attr = self
+            .new_child(parent, name, mode, Content::Dir(BTreeMap::new()))
+            .await?;
+
+        Ok(ReplyEntry {
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+            attr,
+            generation: 0,
+        })
+    }
+
+    async fn unlink(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<()> {
+        self.remove_child(parent, name, false).await
+    }
+
+    async fn rmdir(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<()> {
+        self.remove_child(parent, name, true).await
+    }
+
+    async fn rename(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        new_parent: Inode,
+        new_name: &OsStr,
+    ) -> Result<()> {
+        let parent_node = self.node(parent)?;
+        let new_parent_node = self.node(new_parent)?;
+
+        // lock the smaller inode first, so two renames in opposite directions won't deadlock
+        let (mut parent_guard, mut new_parent_guard): (
+            RwLockWriteGuard<Node>,
+            Option<RwLockWriteGuard<Node>>,
+        ) = if parent == new_parent {
+            (parent_node.write().await, None)
+        } else if parent < new_parent {
+            let parent_guard = parent_node.write().await;
+
+            (parent_guard, Some(new_parent_node.write().await))
+        } else {
+            let new_parent_guard = new_parent_node.write().await;
+
+            (parent_node.write().await, Some(new_parent_guard))
+        };
+
+        let inode = *parent_guard
+            .children()?
+            .get(name)
+            .ok_or_else(Errno::new_not_exist)?;
+
+        let new_children = match &mut new_parent_guard {
+            None => parent_guard.children_mut()?,
+            Some(new_parent_guard) => new_parent_guard.children_mut()?,
+        };
+
+        if let Some(&old_inode) = new_children.get(new_name) {
+            if old_inode == inode {
+                return Ok(());
+            }
+
+            let old_node = self.node(old_inode)?;
+            if let Content::Dir(children) = &old_node.read().await.content {
+                if !children.is_empty() {
+                    return Err(libc::ENOTEMPTY.into());
+                }
+            }
+
+            self.remove_node(old_inode);
+        }
+
+        new_children.insert(new_name.to_owned(), inode);
+        parent_guard.children_mut()?.remove(name);
+
+        // both parents are locked, the child is locked after them
+        self.node(inode)?.write().await.parent = new_parent;
+
+        Ok(())
+    }
+
+    async fn open(&self, _req: Request, inode: Inode, _flags: u32) -> Result<ReplyOpen> {
+        match self.node(inode)?.read().await.content {
+            Content::File(_) => Ok(ReplyOpen { fh: 0, flags: 0 }),
+            Content::Dir(_) => Err(Errno::new_is_dir()),
+        }
+    }
+
+    async fn read(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let node = self.node(inode)?;
+        // read lock, reads of the same file can run at the same time
+        let node = node.read().await;
+
+        match &node.content {
+            Content::Dir(_) => Err(Errno::new_is_dir()),
+            Content::File(content) => {
+                let start = usize::try_from(offset)
+                    .unwrap_or(usize::MAX)
+                    .min(content.len());
+                let end = start.saturating_add(size as _).min(content.len());
+
+                Ok(ReplyData {
+                    data: Bytes::copy_from_slice(&content[start..end]),
+                })
+            }
+        }
+    }
+
+    async fn write(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: u64,
+        offset: u64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: u32,
+    ) -> Result<ReplyWrite> {
+        let node = self.node(inode)?;
+        // only this file is locked, writes to other files are not blocked
+        let mut node = node.write().await;
+        let content = node.file_content_mut()?;
+
+        let offset = fuse3::offset_to_usize(offset)?;
+        let end = offset
+            .checked_add(data.len())
+            .ok_or_else(|| Errno::from(libc::EFBIG))?;
+        if content.len() < end {
+            content.resize(end, 0);
+        }
+
+        content[offset..end].copy_from_slice(data);
+
+        Ok(ReplyWrite {
+            written: data.len() as _,
+        })
+    }
+
+    async fn release(
+        &self,
+        _req: Request,
+        _inode: Inode,
+        _fh: u64,
+        _flags: u32,
+        _lock_owner: u64,
+        _release_flags: ReleaseFlags,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn flush(&self, _req: Request, _inode: Inode, _fh: u64, _lock_owner: u64) -> Result<()> {
+        Ok(())
+    }
+
+    async fn access(&self, _req: Request, _inode: Inode, _mask: u32) -> Result<()> {
+        Ok(())
+    }
+
+    async fn create(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        mode: u32,
+        _flags: u32,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        let attr = self
+            .new_child(parent, name, mode, Content::File(vec![]))
+            .await?;
+
+        Ok(ReplyCreated {
+            ttl: TTL,
+            attr,
+            generation: 0,
+            fh: 0,
+            // the reply flags are the FOPEN_* flags, not the open flags
+            flags: 0,
+        })
+    }
+
+    type DirEntryPlusStream<'a>
+        = Iter<IntoIter<Result<DirectoryEntryPlus>>>
+    where
+        Self: 'a;
+
+    async fn readdirplus(
+        &self,
+        _req: Request,
+        parent: Inode,
+        _fh: u64,
+        offset: u64,
+        _lock_owner: u64,
+    ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'_>>> {
+        let dir = self.node(parent)?;
+        let dir = dir.read().await;
+
+        let mut entries = vec![
+            (dir.inode, OsString::from("."), dir.attr()),
+            (
+                dir.parent,
+                OsString::from(".."),
+                if dir.parent == dir.inode {
+                    dir.attr()
+                } else {
+                    self.node(dir.parent)?.read().await.attr()
+                },
+            ),
+        ];
+
+        for (name, &inode) in dir.children()? {
+            // the child may be removed, but the directory is read locked, so it won't happen
+            let attr = self.node(inode)?.read().await.attr();
+
+            entries.push((inode, name.clone(), attr));
+        }
+
+        let entries = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (inode, name, attr))| {
+                Ok(DirectoryEntryPlus {
+                    inode,
+                    generation: 0,
+                    kind: attr.kind,
+                    name,
+                    offset: i as i64 + 1,
+                    attr,
+                    entry_ttl: TTL,
+                    attr_ttl: TTL,
+                })
+            })
+            .skip(fuse3::offset_to_usize(offset)?)
+            .collect::<Vec<_>>();
+
+        Ok(ReplyDirectoryPlus {
+            parent: None,
+            entries: stream::iter(entries),
+        })
+    }
+}
+
+fn log_init() {
+    let layer = fmt::layer()
+        .pretty()
+        .with_target(true)
+        .with_writer(io::stderr);
+
+    let layered = Registry::default().with(layer).with(LevelFilter::INFO);
+
+    subscriber::set_global_default(layered).unwrap();
+}
+
+#[tokio::main]
+async fn main() {
+    log_init();
+
+    let args = env::args_os().skip(1).take(1).collect::<Vec<_>>();
+
+    let mount_path = args.first();
+
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+
+    let not_unprivileged = env::var("NOT_UNPRIVILEGED").ok().as_deref() == Some("1");
+
+    let mut mount_options = MountOptions::default();
+    mount_options
+        .fs_name("parallel_memfs")
+        .force_readdir_plus(true)
+        .uid(uid)
+        .gid(gid)
+        // FUSE_LOOKUP and FUSE_GETATTR are cheap, don't let them wait behind the bulk IO
+        .request_class(|opcode| match opcode {
+            Opcode::FUSE_LOOKUP | Opcode::FUSE_GETATTR => Priority::High,
+            _ => Priority::Normal,
+        });
+
+    let mount_path = mount_path.expect("no mount point specified");
+
+    let mut mount_handle = if !not_unprivileged {
+        Session::new(mount_options)
+            .mount_with_unprivileged(Fs::default(), mount_path)
+            .await
+            .unwrap()
+    } else {
+        Session::new(mount_options)
+            .mount(Fs::default(), mount_path)
+            .await
+            .unwrap()
+    };
+
+    let handle = &mut mount_handle;
+
+    tokio::select! {
+        res = handle => res.unwrap(),
+        _ = signal::ctrl_c() => {
+            mount_handle.unmount().await.unwrap()
+        }
+    }
+}
@@ -3,11 +3,13 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::{Cursor, Read, Write};
 use std::num::NonZeroU32;
+use std::os::unix::ffi::OsStrExt;
 use std::time::{Duration, SystemTime};
 use std::vec::IntoIter;
 
 use bytes::{Buf, BufMut, BytesMut};
 use fuse3::path::prelude::*;
+use fuse3::raw::flags::RenameFlags;
 use fuse3::{Errno, MountOptions, Result};
 use futures_util::stream::{Empty, Iter};
 use futures_util::{stream, StreamExt};
@@ -17,7 +19,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, Level};
 
 const TTL: Duration = Duration::from_secs(1);
-const SEPARATOR: char = '/';
+const SEPARATOR: u8 = b'/';
 
 #[derive(Debug)]
 enum Entry {
@@ -37,8 +39,9 @@ impl Entry {
                 kind: FileType::Directory,
                 perm: fuse3::perm_from_mode_and_kind(FileType::Directory, dir.mode),
                 nlink: 0,
-                uid: 0,
-                gid: 0,
+                // the owner in attr is what `stat` shows, report the mount uid and gid
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
                 rdev: 0,
                 blksize: 0,
             },
@@ -52,8 +55,8 @@ impl Entry {
                 kind: FileType::RegularFile,
                 perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, file.mode),
                 nlink: 0,
-                uid: 0,
-                gid: 0,
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
                 rdev: 0,
                 blksize: 0,
             },
@@ -154,26 +157,22 @@ impl PathFilesystem for Fs {
     async fn destroy(&self, _req: Request) {}
 
     async fn lookup(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<ReplyEntry> {
-        let parent = parent.to_string_lossy();
-        let name = name.to_string_lossy();
-        let mut paths = split_path(&parent);
-        paths.push(name.as_ref());
+        let mut paths = split_path(parent);
+        paths.push(name);
 
         let mut entry = &self.0.read().await.root;
 
         for path in paths {
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
         }
 
         Ok(ReplyEntry {
-            ttl: TTL,
+            entry_ttl: TTL,
+            attr_ttl: TTL,
             attr: entry.attr(),
         })
     }
@@ -185,22 +184,19 @@ impl PathFilesystem for Fs {
         _req: Request,
         path: Option<&OsStr>,
         _fh: Option<u64>,
-        _flags: u32,
+        _flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
+        let path = path.ok_or_else(Errno::new_not_exist)?;
 
-        debug!("get attr path {}", path);
+        debug!("get attr path {:?}", path);
 
-        let paths = split_path(&path);
+        let paths = split_path(path);
 
         let mut entry = &self.0.read().await.root;
 
         for path in paths {
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -219,8 +215,8 @@ impl PathFilesystem for Fs {
         _fh: Option<u64>,
         set_attr: SetAttr,
     ) -> Result<ReplyAttr> {
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
-        let paths = split_path(&path);
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let paths = split_path(path);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -228,7 +224,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -248,9 +244,9 @@ impl PathFilesystem for Fs {
         name: &OsStr,
         mode: u32,
         _umask: u32,
+        _security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
-        let path = parent.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(parent);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -258,7 +254,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -279,15 +275,18 @@ impl PathFilesystem for Fs {
 
             dir.children.insert(name.to_owned(), entry);
 
-            Ok(ReplyEntry { ttl: TTL, attr })
+            Ok(ReplyEntry {
+                entry_ttl: TTL,
+                attr_ttl: TTL,
+                attr,
+            })
         } else {
             Err(Errno::new_is_not_dir())
         }
     }
 
     async fn unlink(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<()> {
-        let path = parent.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(parent);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -295,7 +294,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -321,8 +320,7 @@ impl PathFilesystem for Fs {
     }
 
     async fn rmdir(&self, _req: Request, parent: &OsStr, name: &OsStr) -> Result<()> {
-        let path = parent.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(parent);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -330,7 +328,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -363,18 +361,14 @@ impl PathFilesystem for Fs {
         parent: &OsStr,
         name: &OsStr,
     ) -> Result<()> {
-        let origin_parent = origin_parent.to_string_lossy();
-        let origin_parent_paths = split_path(&origin_parent);
+        let origin_parent_paths = split_path(origin_parent);
 
         let inner_fs = &mut *self.0.write().await;
         let mut origin_parent_entry = &inner_fs.root;
 
         for path in &origin_parent_paths {
             if let Entry::Dir(dir) = origin_parent_entry {
-                origin_parent_entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                origin_parent_entry = dir.children.get(*path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -386,15 +380,11 @@ impl PathFilesystem for Fs {
 
         let mut parent_entry = &inner_fs.root;
 
-        let parent = parent.to_string_lossy();
-        let parent_paths = split_path(&parent);
+        let parent_paths = split_path(parent);
 
         for path in &parent_paths {
             if let Entry::Dir(dir) = parent_entry {
-                parent_entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                parent_entry = dir.children.get(*path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -408,7 +398,7 @@ impl PathFilesystem for Fs {
 
         for path in origin_parent_paths {
             if let Entry::Dir(dir) = origin_parent_entry {
-                origin_parent_entry = dir.children.get_mut(OsStr::new(path)).unwrap();
+                origin_parent_entry = dir.children.get_mut(path).unwrap();
             } else {
                 unreachable!()
             }
@@ -426,7 +416,7 @@ impl PathFilesystem for Fs {
 
         for path in parent_paths {
             if let Entry::Dir(dir) = parent_entry {
-                parent_entry = dir.children.get_mut(OsStr::new(path)).unwrap();
+                parent_entry = dir.children.get_mut(path).unwrap();
             } else {
                 unreachable!()
             }
@@ -442,19 +432,15 @@ impl PathFilesystem for Fs {
     }
 
     async fn open(&self, _req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
-        let path = path.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(path);
 
-        debug!("open path {}", path);
+        debug!("open path {:?}", path);
 
         let mut entry = &self.0.read().await.root;
 
         for path in paths {
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -475,19 +461,16 @@ impl PathFilesystem for Fs {
         offset: u64,
         size: u32,
     ) -> Result<ReplyData> {
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
-        let paths = split_path(&path);
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let paths = split_path(path);
 
-        debug!("read path {}", path);
+        debug!("read path {:?}", path);
 
         let mut entry = &self.0.read().await.root;
 
         for path in paths {
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -525,10 +508,10 @@ impl PathFilesystem for Fs {
         _write_flags: u32,
         _flags: u32,
     ) -> Result<ReplyWrite> {
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
-        let paths = split_path(&path);
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let paths = split_path(path);
 
-        debug!("write path {}, paths {:?}", path, paths);
+        debug!("write path {:?}, paths {:?}", path, paths);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -536,7 +519,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -549,7 +532,7 @@ impl PathFilesystem for Fs {
             return Err(Errno::new_is_dir());
         };
 
-        let offset = offset as usize;
+        let offset = fuse3::offset_to_usize(offset)?;
 
         if offset < file.content.len() {
             let mut content = &mut file.content.as_mut()[offset..];
@@ -579,7 +562,7 @@ impl PathFilesystem for Fs {
         _fh: u64,
         _flags: u32,
         _lock_owner: u64,
-        _flush: bool,
+        _release_flags: ReleaseFlags,
     ) -> Result<()> {
         Ok(())
     }
@@ -615,11 +598,11 @@ impl PathFilesystem for Fs {
         name: &OsStr,
         mode: u32,
         flags: u32,
+        _security_context: Option<SecurityContext>,
     ) -> Result<ReplyCreated> {
-        let path = parent.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(parent);
 
-        debug!("create parent path {}, name {:?}", path, name);
+        debug!("create parent path {:?}, name {:?}", parent, name);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -627,7 +610,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -675,8 +658,8 @@ impl PathFilesystem for Fs {
     ) -> Result<()> {
         use std::os::raw::c_int;
 
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
-        let paths = split_path(&path);
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let paths = split_path(path);
 
         let mut entry = &mut self.0.write().await.root;
 
@@ -684,7 +667,7 @@ impl PathFilesystem for Fs {
             if let Entry::Dir(dir) = entry {
                 entry = dir
                     .children
-                    .get_mut(OsStr::new(path))
+                    .get_mut(path)
                     .ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
@@ -697,21 +680,24 @@ impl PathFilesystem for Fs {
             return Err(Errno::new_is_dir());
         };
 
-        let offset = offset as usize;
-        let length = length as usize;
+        let offset = fuse3::offset_to_usize(offset)?;
+        let length = fuse3::offset_to_usize(length)?;
+        let end = offset
+            .checked_add(length)
+            .ok_or_else(|| Errno::from(libc::EFBIG))?;
 
         match mode as c_int {
             0 => {
-                if offset + length > file.content.len() {
-                    file.content.resize(offset + length, 0);
+                if end > file.content.len() {
+                    file.content.resize(end, 0);
                 }
 
                 Ok(())
             }
 
             libc::FALLOC_FL_KEEP_SIZE => {
-                if offset + length > file.content.len() {
-                    file.content.reserve(offset + length - file.content.len());
+                if end > file.content.len() {
+                    file.content.reserve(end - file.content.len());
                 }
 
                 Ok(())
@@ -734,8 +720,7 @@ impl PathFilesystem for Fs {
         offset: u64,
         _lock_owner: u64,
     ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'a>>> {
-        let path = parent.to_string_lossy();
-        let paths = split_path(&path);
+        let paths = split_path(parent);
 
         let mut entry = &self.0.read().await.root;
         let mut parent = entry;
@@ -744,10 +729,7 @@ impl PathFilesystem for Fs {
             parent = entry;
 
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -780,7 +762,7 @@ impl PathFilesystem for Fs {
                         entry_ttl: TTL,
                         attr_ttl: TTL,
                     })
-                    .skip(offset as _)
+                    .skip(fuse3::offset_to_usize(offset)?)
                     .map(Ok)
                     .collect::<Vec<_>>()
                     .await;
@@ -800,8 +782,13 @@ impl PathFilesystem for Fs {
         origin_name: &OsStr,
         parent: &OsStr,
         name: &OsStr,
-        _flags: u32,
+        flags: u32,
     ) -> Result<()> {
+        // the memfs can't exchange or check the new name atomically
+        if !RenameFlags::from(flags).is_empty() {
+            return Err(libc::EINVAL.into());
+        }
+
         self.rename(req, origin_parent, origin_name, parent, name)
             .await
     }
@@ -814,17 +801,14 @@ impl PathFilesystem for Fs {
         offset: u64,
         whence: u32,
     ) -> Result<ReplyLSeek> {
-        let path = path.ok_or_else(Errno::new_not_exist)?.to_string_lossy();
-        let paths = split_path(&path);
+        let path = path.ok_or_else(Errno::new_not_exist)?;
+        let paths = split_path(path);
 
         let mut entry = &self.0.read().await.root;
 
         for path in paths {
             if let Entry::Dir(dir) = entry {
-                entry = dir
-                    .children
-                    .get(OsStr::new(path))
-                    .ok_or_else(Errno::new_not_exist)?;
+                entry = dir.children.get(path).ok_or_else(Errno::new_not_exist)?;
             } else {
                 return Err(Errno::new_is_not_dir());
             }
@@ -841,13 +825,7 @@ impl PathFilesystem for Fs {
         let offset = if whence == libc::SEEK_CUR || whence == libc::SEEK_SET {
             offset
         } else if whence == libc::SEEK_END {
-            let size = file.content.len();
-
-            if size >= offset as _ {
-                size as u64 - offset
-            } else {
-                0
-            }
+            (file.content.len() as u64).saturating_sub(offset)
         } else {
             return Err(libc::EINVAL.into());
         };
@@ -867,13 +845,18 @@ impl PathFilesystem for Fs {
         length: u64,
         flags: u64,
     ) -> Result<ReplyCopyFileRange> {
+        // the copy_file_range flags are not the write flags, no flag is defined yet
+        if flags != 0 {
+            return Err(libc::EINVAL.into());
+        }
+
         let data = self
             .read(req, from_path, fh_in, offset_in, length as _)
             .await?;
 
-        // write_flags set to 0 because we don't care it in this example implement
+        // write_flags and flags set to 0 because we don't care them in this example implement
         let ReplyWrite { written } = self
-            .write(req, to_path, fh_out, offset_out, &data.data, 0, flags as _)
+            .write(req, to_path, fh_out, offset_out, &data.data, 0, 0)
             .await?;
 
         Ok(ReplyCopyFileRange {
@@ -882,11 +865,16 @@ impl PathFilesystem for Fs {
     }
 }
 
-fn split_path(path: &str) -> Vec<&str> {
+fn split_path(path: &OsStr) -> Vec<&OsStr> {
     if path == "/" {
         vec![]
     } else {
-        path.split(SEPARATOR).skip(1).collect()
+        // split the bytes, the path may be not valid UTF-8
+        path.as_bytes()
+            .split(|byte| *byte == SEPARATOR)
+            .skip(1)
+            .map(OsStr::from_bytes)
+            .collect()
     }
 }
 
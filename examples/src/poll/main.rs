@@ -1,6 +1,9 @@
 use std::ffi::{OsStr, OsString};
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom};
 use std::iter::Skip;
 use std::num::NonZeroU32;
+use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -9,8 +12,9 @@ use std::time::{Duration, SystemTime};
 use std::vec::IntoIter;
 
 use bytes::Bytes;
+use fuse3::raw::flags::FOPEN_NONSEEKABLE;
 use fuse3::raw::prelude::*;
-use fuse3::{MountOptions, Result};
+use fuse3::{Errno, MountOptions, Result};
 use futures_util::stream;
 use futures_util::stream::Iter;
 use mio::unix::SourceFd;
@@ -51,7 +55,8 @@ impl Filesystem for Poll {
         }
 
         Ok(ReplyEntry {
-            ttl: TTL,
+            entry_ttl: TTL,
+            attr_ttl: TTL,
             attr: FileAttr {
                 ino: FILE_INODE,
                 size: CONTENT.len() as u64,
@@ -62,8 +67,9 @@ impl Filesystem for Poll {
                 kind: FileType::RegularFile,
                 perm: FILE_MODE,
                 nlink: 0,
-                uid: 0,
-                gid: 0,
+                // the owner in attr is what `stat` shows, report the mount uid and gid
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
                 rdev: 0,
                 blksize: 0,
             },
@@ -76,7 +82,7 @@ impl Filesystem for Poll {
         _req: Request,
         inode: u64,
         _fh: Option<u64>,
-        _flags: u32,
+        _flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
         if inode == PARENT_INODE {
             Ok(ReplyAttr {
@@ -91,8 +97,8 @@ impl Filesystem for Poll {
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -110,8 +116,8 @@ impl Filesystem for Poll {
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -122,11 +128,24 @@ impl Filesystem for Poll {
     }
 
     async fn open(&self, _req: Request, inode: u64, flags: u32) -> Result<ReplyOpen> {
-        if inode != PARENT_INODE && inode != FILE_INODE {
-            return Err(libc::ENOENT.into());
-        }
+        match inode {
+            PARENT_INODE => Ok(ReplyOpen { fh: 1, flags: 0 }),
+
+            // the stream isn't ready, the nonblocking open would block
+            FILE_INODE
+                if flags & libc::O_NONBLOCK as u32 != 0 && !self.ready.load(Ordering::SeqCst) =>
+            {
+                Err(libc::EAGAIN.into())
+            }
 
-        Ok(ReplyOpen { fh: 1, flags })
+            // the file is a stream, it can't be seeked
+            FILE_INODE => Ok(ReplyOpen {
+                fh: 1,
+                flags: FOPEN_NONSEEKABLE,
+            }),
+
+            _ => Err(libc::ENOENT.into()),
+        }
     }
 
     async fn read(
@@ -141,10 +160,12 @@ impl Filesystem for Poll {
             return Err(libc::ENOENT.into());
         }
 
-        if offset as usize >= CONTENT.len() {
+        let offset = fuse3::offset_to_usize(offset)?;
+
+        if offset >= CONTENT.len() {
             Ok(ReplyData { data: Bytes::new() })
         } else {
-            let mut data = &CONTENT.as_bytes()[offset as usize..];
+            let mut data = &CONTENT.as_bytes()[offset..];
 
             if data.len() > size as usize {
                 data = &data[..size as usize];
@@ -197,8 +218,13 @@ impl Filesystem for Poll {
             }),
         ];
 
+        // the offset is never negative, it is replied by the filesystem
+        let offset = u64::try_from(offset).map_err(|_| Errno::from(libc::EINVAL))?;
+        let offset = fuse3::offset_to_usize(offset)?;
+
         Ok(ReplyDirectory {
-            entries: stream::iter(entries.into_iter().skip(offset as usize)),
+            parent: None,
+            entries: stream::iter(entries.into_iter().skip(offset)),
         })
     }
 
@@ -248,8 +274,8 @@ impl Filesystem for Poll {
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -272,8 +298,8 @@ impl Filesystem for Poll {
                     kind: FileType::Directory,
                     perm: PARENT_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -296,8 +322,8 @@ impl Filesystem for Poll {
                     kind: FileType::RegularFile,
                     perm: FILE_MODE,
                     nlink: 0,
-                    uid: 0,
-                    gid: 0,
+                    uid: unsafe { libc::getuid() },
+                    gid: unsafe { libc::getgid() },
                     rdev: 0,
                     blksize: 0,
                 },
@@ -306,8 +332,11 @@ impl Filesystem for Poll {
             }),
         ];
 
+        let offset = fuse3::offset_to_usize(offset)?;
+
         Ok(ReplyDirectoryPlus {
-            entries: stream::iter(entries.into_iter().skip(offset as usize)),
+            parent: None,
+            entries: stream::iter(entries.into_iter().skip(offset)),
         })
     }
 
@@ -343,9 +372,10 @@ impl Filesystem for Poll {
 
                 ready.store(true, Ordering::SeqCst);
 
-                notify.wakeup(kh).await;
-
-                debug!("notify done");
+                match notify.wakeup(kh).await {
+                    Err(err) => debug!("notify failed: {}", err),
+                    Ok(_) => debug!("notify done"),
+                }
             });
         }
 
@@ -402,6 +432,14 @@ fn poll_file(mount_path: &OsStr) {
     let mut path = PathBuf::from(mount_path.to_os_string());
     path.push(FILE_NAME);
 
+    // the stream isn't ready, the nonblocking open fails with EAGAIN
+    let err = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&path)
+        .unwrap_err();
+    info!("nonblocking open not ready file failed: {}", err);
+
     let file = std::fs::File::open(&path).unwrap();
 
     let fd = file.as_raw_fd();
@@ -422,4 +460,8 @@ fn poll_file(mount_path: &OsStr) {
     }
 
     poll.registry().deregister(&mut fd).unwrap();
+
+    // the file is opened with FOPEN_NONSEEKABLE, seek will fail with ESPIPE
+    let err = (&file).seek(SeekFrom::Start(0)).unwrap_err();
+    info!("seek non-seekable file failed: {}", err);
 }
@@ -0,0 +1,476 @@
+//! A flat memory filesystem which supports `RENAME_WHITEOUT`, it can be used as the upper layer
+//! of an overlay filesystem.
+//!
+//! When overlayfs renames or removes an entry which exists in the lower layer, it asks the upper
+//! layer to rename with `RENAME_WHITEOUT`, the upper layer must create a whiteout object, a
+//! character device with device number 0/0, at the old name to hide the lower entry.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::num::NonZeroU32;
+use std::time::{Duration, SystemTime};
+use std::vec::IntoIter;
+
+use bytes::Bytes;
+use fuse3::raw::flags::RenameFlags;
+use fuse3::raw::prelude::*;
+use fuse3::{Errno, Inode, MountOptions, Result};
+use futures_util::stream;
+use futures_util::stream::{Empty, Iter};
+use tokio::signal;
+use tokio::sync::RwLock;
+use tracing::{debug, Level};
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INODE: Inode = 1;
+
+#[derive(Debug)]
+struct Node {
+    kind: FileType,
+    perm: u16,
+    rdev: u32,
+    content: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    // the filesystem only has the root directory
+    entries: BTreeMap<OsString, Inode>,
+    nodes: BTreeMap<Inode, Node>,
+    next_inode: Inode,
+}
+
+impl Inner {
+    fn attr(&self, inode: Inode) -> Result<FileAttr> {
+        if inode == ROOT_INODE {
+            return Ok(new_attr(ROOT_INODE, FileType::Directory, 0o755, 0, 0));
+        }
+
+        let node = self.nodes.get(&inode).ok_or_else(Errno::new_not_exist)?;
+
+        Ok(new_attr(
+            inode,
+            node.kind,
+            node.perm,
+            node.rdev,
+            node.content.len() as _,
+        ))
+    }
+
+    fn insert(&mut self, name: &OsStr, node: Node) -> Result<FileAttr> {
+        if self.entries.contains_key(name) {
+            return Err(Errno::new_exist());
+        }
+
+        let inode = self.next_inode;
+        self.next_inode += 1;
+
+        self.nodes.insert(inode, node);
+        self.entries.insert(name.to_owned(), inode);
+
+        self.attr(inode)
+    }
+
+    fn remove(&mut self, name: &OsStr) -> Result<()> {
+        let inode = self.entries.remove(name).ok_or_else(Errno::new_not_exist)?;
+        self.nodes.remove(&inode);
+
+        Ok(())
+    }
+}
+
+fn new_attr(ino: Inode, kind: FileType, perm: u16, rdev: u32, size: u64) -> FileAttr {
+    FileAttr {
+        ino,
+        size,
+        blocks: 0,
+        atime: SystemTime::UNIX_EPOCH.into(),
+        mtime: SystemTime::UNIX_EPOCH.into(),
+        ctime: SystemTime::UNIX_EPOCH.into(),
+        kind,
+        perm,
+        nlink: 1,
+        // the owner in attr is what `stat` shows, report the mount uid and gid
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+        rdev,
+        blksize: 4096,
+    }
+}
+
+#[derive(Debug)]
+struct Fs(RwLock<Inner>);
+
+impl Default for Fs {
+    fn default() -> Self {
+        Self(RwLock::new(Inner {
+            entries: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            next_inode: ROOT_INODE + 1,
+        }))
+    }
+}
+
+impl Filesystem for Fs {
+    type DirEntryStream<'a>
+        = Empty<Result<DirectoryEntry>>
+    where
+        Self: 'a;
+
+    async fn init(&self, _req: Request) -> Result<ReplyInit> {
+        Ok(ReplyInit {
+            max_write: NonZeroU32::new(16 * 1024).unwrap(),
+        })
+    }
+
+    async fn destroy(&self, _req: Request) {}
+
+    async fn lookup(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<ReplyEntry> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        let inner = self.0.read().await;
+        let inode = *inner.entries.get(name).ok_or_else(Errno::new_not_exist)?;
+
+        Ok(ReplyEntry {
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+            attr: inner.attr(inode)?,
+            generation: 0,
+        })
+    }
+
+    async fn getattr(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: Option<u64>,
+        _flags: GetattrFlags,
+    ) -> Result<ReplyAttr> {
+        Ok(ReplyAttr {
+            ttl: TTL,
+            attr: self.0.read().await.attr(inode)?,
+        })
+    }
+
+    async fn mknod(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        mode: u32,
+        rdev: u32,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyEntry> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        // overlayfs may create the whiteout by mknod too, when the filesystem doesn't support
+        // RENAME_WHITEOUT
+        let kind = match FileType::from_mode(mode) {
+            Some(kind @ (FileType::CharDevice | FileType::RegularFile)) => kind,
+            _ => return Err(libc::EPERM.into()),
+        };
+
+        let attr = self.0.write().await.insert(
+            name,
+            Node {
+                kind,
+                perm: fuse3::perm_from_mode_and_kind(kind, mode),
+                rdev,
+                content: vec![],
+            },
+        )?;
+
+        Ok(ReplyEntry {
+            entry_ttl: TTL,
+            attr_ttl: TTL,
+            attr,
+            generation: 0,
+        })
+    }
+
+    async fn unlink(&self, _req: Request, parent: Inode, name: &OsStr) -> Result<()> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        self.0.write().await.remove(name)
+    }
+
+    async fn rename(
+        &self,
+        req: Request,
+        parent: Inode,
+        name: &OsStr,
+        new_parent: Inode,
+        new_name: &OsStr,
+    ) -> Result<()> {
+        self.rename2(req, parent, name, new_parent, new_name, 0)
+            .await
+    }
+
+    async fn open(&self, _req: Request, inode: Inode, _flags: u32) -> Result<ReplyOpen> {
+        self.0.read().await.attr(inode)?;
+
+        Ok(ReplyOpen { fh: 0, flags: 0 })
+    }
+
+    async fn read(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: u64,
+        offset: u64,
+        size: u32,
+    ) -> Result<ReplyData> {
+        let inner = self.0.read().await;
+        let content = &inner
+            .nodes
+            .get(&inode)
+            .ok_or_else(Errno::new_not_exist)?
+            .content;
+
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(content.len());
+        let end = start.saturating_add(size as _).min(content.len());
+
+        Ok(ReplyData {
+            data: Bytes::copy_from_slice(&content[start..end]),
+        })
+    }
+
+    async fn write(
+        &self,
+        _req: Request,
+        inode: Inode,
+        _fh: u64,
+        offset: u64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: u32,
+    ) -> Result<ReplyWrite> {
+        let mut inner = self.0.write().await;
+        let content = &mut inner
+            .nodes
+            .get_mut(&inode)
+            .ok_or_else(Errno::new_not_exist)?
+            .content;
+
+        let offset = fuse3::offset_to_usize(offset)?;
+        let end = offset
+            .checked_add(data.len())
+            .ok_or_else(|| Errno::from(libc::EFBIG))?;
+        if content.len() < end {
+            content.resize(end, 0);
+        }
+
+        content[offset..end].copy_from_slice(data);
+
+        Ok(ReplyWrite {
+            written: data.len() as _,
+        })
+    }
+
+    async fn access(&self, _req: Request, _inode: Inode, _mask: u32) -> Result<()> {
+        Ok(())
+    }
+
+    async fn create(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        mode: u32,
+        _flags: u32,
+        _security_context: Option<SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        let attr = self.0.write().await.insert(
+            name,
+            Node {
+                kind: FileType::RegularFile,
+                perm: fuse3::perm_from_mode_and_kind(FileType::RegularFile, mode),
+                rdev: 0,
+                content: vec![],
+            },
+        )?;
+
+        Ok(ReplyCreated {
+            ttl: TTL,
+            attr,
+            generation: 0,
+            fh: 0,
+            // the reply flags are the FOPEN_* flags, not the open flags
+            flags: 0,
+        })
+    }
+
+    type DirEntryPlusStream<'a>
+        = Iter<IntoIter<Result<DirectoryEntryPlus>>>
+    where
+        Self: 'a;
+
+    async fn readdirplus(
+        &self,
+        _req: Request,
+        parent: Inode,
+        _fh: u64,
+        offset: u64,
+        _lock_owner: u64,
+    ) -> Result<ReplyDirectoryPlus<Self::DirEntryPlusStream<'_>>> {
+        if parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        let inner = self.0.read().await;
+        let root_attr = inner.attr(ROOT_INODE)?;
+
+        let mut entries = vec![
+            (OsString::from("."), root_attr),
+            (OsString::from(".."), root_attr),
+        ];
+        for (name, &inode) in &inner.entries {
+            entries.push((name.clone(), inner.attr(inode)?));
+        }
+
+        let entries = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, attr))| {
+                Ok(DirectoryEntryPlus {
+                    inode: attr.ino,
+                    generation: 0,
+                    kind: attr.kind,
+                    name,
+                    offset: i as i64 + 1,
+                    attr,
+                    entry_ttl: TTL,
+                    attr_ttl: TTL,
+                })
+            })
+            .skip(fuse3::offset_to_usize(offset)?)
+            .collect::<Vec<_>>();
+
+        Ok(ReplyDirectoryPlus {
+            parent: None,
+            entries: stream::iter(entries),
+        })
+    }
+
+    async fn rename2(
+        &self,
+        _req: Request,
+        parent: Inode,
+        name: &OsStr,
+        new_parent: Inode,
+        new_name: &OsStr,
+        flags: u32,
+    ) -> Result<()> {
+        if parent != ROOT_INODE || new_parent != ROOT_INODE {
+            return Err(Errno::new_is_not_dir());
+        }
+
+        let flags = RenameFlags::from(flags);
+        if !flags
+            .unsupported(RenameFlags::NOREPLACE | RenameFlags::WHITEOUT)
+            .is_empty()
+        {
+            return Err(libc::EINVAL.into());
+        }
+
+        let mut inner = self.0.write().await;
+
+        let inode = *inner.entries.get(name).ok_or_else(Errno::new_not_exist)?;
+        if name == new_name {
+            return Ok(());
+        }
+
+        if inner.entries.contains_key(new_name) {
+            if flags.contains(RenameFlags::NOREPLACE) {
+                return Err(Errno::new_exist());
+            }
+
+            inner.remove(new_name)?;
+        }
+
+        inner.entries.remove(name);
+        inner.entries.insert(new_name.to_owned(), inode);
+
+        if flags.contains(RenameFlags::WHITEOUT) {
+            debug!(?name, "create whiteout");
+
+            // the whiteout must be created atomically with the rename, the write lock is still
+            // held here
+            inner.insert(
+                name,
+                Node {
+                    kind: FileType::CharDevice,
+                    perm: 0,
+                    rdev: 0,
+                    content: vec![],
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn log_init() {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    log_init();
+
+    let args = env::args_os().skip(1).take(1).collect::<Vec<_>>();
+
+    let mount_path = args.first();
+
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+
+    let mut mount_options = MountOptions::default();
+    mount_options
+        .fs_name("whiteout")
+        .force_readdir_plus(true)
+        .uid(uid)
+        .gid(gid);
+
+    let mount_path = mount_path.expect("no mount point specified");
+
+    let not_unprivileged = env::var("NOT_UNPRIVILEGED").ok().as_deref() == Some("1");
+
+    let mut mount_handle = if !not_unprivileged {
+        Session::new(mount_options)
+            .mount_with_unprivileged(Fs::default(), mount_path)
+            .await
+            .unwrap()
+    } else {
+        Session::new(mount_options)
+            .mount(Fs::default(), mount_path)
+            .await
+            .unwrap()
+    };
+
+    let handle = &mut mount_handle;
+
+    tokio::select! {
+        res = handle => res.unwrap(),
+        _ = signal::ctrl_c() => {
+            mount_handle.unmount().await.unwrap()
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![no_main]
+
+use fuse3::raw::protocol;
+use libfuzzer_sys::fuzz_target;
+
+const FUSE_RENAME: u32 = 12;
+const FUSE_CREATE: u32 = 35;
+const FUSE_RENAME2: u32 = 45;
+const FUSE_REMOVEMAPPING: u32 = 49;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((in_header, body)) = protocol::decode_in_header(data) else {
+        return;
+    };
+
+    match in_header.opcode {
+        FUSE_CREATE => {
+            let _ = protocol::decode_create(&in_header, body);
+        }
+
+        FUSE_RENAME => {
+            let _ = protocol::decode_rename(body);
+        }
+
+        FUSE_RENAME2 => {
+            let _ = protocol::decode_rename2(body);
+        }
+
+        FUSE_REMOVEMAPPING => {
+            let _ = protocol::decode_remove_mapping(body);
+        }
+
+        _ => {}
+    }
+});
@@ -1,9 +1,11 @@
+use std::ffi::OsStr;
 use std::mem;
+use std::os::unix::ffi::OsStrExt;
 
 use bincode::{DefaultOptions, Options};
 use nix::sys::stat::mode_t;
 
-use crate::FileType;
+use crate::{Errno, FileType};
 
 pub trait Apply: Sized {
     fn apply<F>(mut self, f: F) -> Self
@@ -22,6 +24,14 @@ pub fn get_first_null_position(data: impl AsRef<[u8]>) -> Option<usize> {
     data.as_ref().iter().position(|char| *char == 0)
 }
 
+/// check if the `name` is a single path component, it must not be empty, `.` or `..`, and must
+/// not contain `/`.
+pub fn is_valid_name(name: &OsStr) -> bool {
+    let name = name.as_bytes();
+
+    !name.is_empty() && name != b"." && name != b".." && !name.contains(&b'/')
+}
+
 // Some platforms like Linux x86_64 have mode_t = u32, and lint warns of a trivial_numeric_casts.
 // But others like macOS x86_64 have mode_t = u16, requiring a typecast. So, just silence lint.
 #[cfg(target_os = "linux")]
@@ -63,3 +73,54 @@ pub fn get_bincode_config() -> impl Options {
         .allow_trailing_bytes()
         .with_fixint_encoding()
 }
+
+/// converts a 64-bit offset or size to `usize`, returns `EINVAL` instead of truncating when
+/// the value exceeds `usize::MAX` on 32-bit targets.
+pub fn offset_to_usize(value: u64) -> Result<usize, Errno> {
+    usize::try_from(value).map_err(|_| Errno::from(libc::EINVAL))
+}
+
+/// returns the memory page size of the system.
+pub fn page_size() -> u32 {
+    // Safety: sysconf with _SC_PAGESIZE has no side effect
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+    if size <= 0 {
+        4096
+    } else {
+        size as _
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_name() {
+        assert!(is_valid_name(OsStr::new("file")));
+        assert!(is_valid_name(OsStr::new("...")));
+        assert!(is_valid_name(OsStr::new(".hidden")));
+
+        for name in ["", ".", "..", "a/b", "/", "dir/"] {
+            assert!(!is_valid_name(OsStr::new(name)), "{name:?} is valid");
+        }
+    }
+
+    #[test]
+    fn offset_to_usize_pass_through() {
+        assert_eq!(offset_to_usize(0), Ok(0));
+        assert_eq!(offset_to_usize(4096), Ok(4096));
+        assert_eq!(offset_to_usize(usize::MAX as u64), Ok(usize::MAX));
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn offset_to_usize_overflow() {
+        assert_eq!(offset_to_usize(u64::MAX), Err(Errno::from(libc::EINVAL)));
+        assert_eq!(
+            offset_to_usize(usize::MAX as u64 + 1),
+            Err(Errno::from(libc::EINVAL))
+        );
+    }
+}
@@ -8,23 +8,45 @@
 //!
 //! # Features:
 //!
-//! - `file-lock`: enable POSIX file lock feature.
+//! - `file-lock`: enable POSIX file lock and `flock` feature.
 //! - `async-io-runtime`: use [async_io](https://docs.rs/async-io) and
 //!   [async-global-executor](https://docs.rs/async-global-executor) to drive async io and task.
 //! - `tokio-runtime`: use [tokio](https://docs.rs/tokio) runtime to drive async io and task.
 //! - `unprivileged`: allow mount filesystem without root permission by using `fusermount3`.
+//! - `small-footprint`: reduce the heap usage for the memory limited environment, such as the
+//!   embedded Linux. The replies which are not larger than 160 bytes, like the header only, attr,
+//!   entry and open replies, are stored inline in the reply queue without a separate buffer
+//!   allocation, larger replies like read, readdir and xattr data still allocate. The queue of
+//!   pending replies is bounded to 64 replies plus one for each in flight request, so the reply
+//!   queue memory ceiling is about `(64 + in flight requests) * 200` bytes, besides the data of
+//!   large replies. When the queue is full, dispatching new requests waits the replies to be
+//!   written.
 //!
 //! # Notes:
 //!
-//! You must enable `async-io-runtime` or `tokio-runtime` feature.
+//! You must enable `async-io-runtime` or `tokio-runtime` feature, but not both of them, otherwise
+//! the build fails with an error.
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+#[cfg(not(any(feature = "async-io-runtime", feature = "tokio-runtime")))]
+compile_error!(
+    "fuse3 needs a runtime, enable one of the `tokio-runtime` and `async-io-runtime` features"
+);
+
+#[cfg(all(feature = "async-io-runtime", feature = "tokio-runtime"))]
+compile_error!(
+    "the `tokio-runtime` and `async-io-runtime` features of fuse3 conflict, enable only one of them"
+);
+
+use std::ffi::OsString;
 #[cfg(any(
     all(target_os = "linux", feature = "unprivileged"),
     target_os = "macos"
 ))]
 use std::io::{self, ErrorKind};
+#[cfg(feature = "file-lock")]
+use std::ops::Range;
 #[cfg(target_os = "macos")]
 use std::path::Path;
 #[cfg(any(
@@ -35,15 +57,29 @@ use std::path::PathBuf;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub use errno::Errno;
-pub use helper::{mode_from_kind_and_perm, perm_from_mode_and_kind};
-pub use mount_options::MountOptions;
+pub use helper::{mode_from_kind_and_perm, offset_to_usize, perm_from_mode_and_kind};
+pub use mount_options::{MountOptions, MountOptionsError, Priority};
 use nix::sys::stat::mode_t;
+#[cfg(feature = "file-lock")]
+use raw::abi::fuse_file_lock;
+pub use raw::abi::fuse_opcode as Opcode;
 use raw::abi::{
     fuse_setattr_in, FATTR_ATIME, FATTR_ATIME_NOW, FATTR_CTIME, FATTR_GID, FATTR_LOCKOWNER,
     FATTR_MODE, FATTR_MTIME, FATTR_MTIME_NOW, FATTR_SIZE, FATTR_UID,
 };
 #[cfg(target_os = "macos")]
 use raw::abi::{FATTR_BKUPTIME, FATTR_CHGTIME, FATTR_CRTIME, FATTR_FLAGS};
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+pub use raw::mount;
+#[cfg(all(
+    any(feature = "async-io-runtime", feature = "tokio-runtime"),
+    any(
+        all(target_os = "linux", feature = "unprivileged"),
+        all(target_os = "freebsd", feature = "unprivileged"),
+        target_os = "macos"
+    )
+))]
+pub use raw::mount_with_unprivileged;
 
 mod errno;
 mod helper;
@@ -51,10 +87,25 @@ mod mount_options;
 pub mod notify;
 pub mod path;
 pub mod raw;
+pub mod xattr;
 
 /// Filesystem Inode.
 pub type Inode = u64;
 
+/// the inode of the filesystem root, it is the mount point.
+///
+/// # Notes:
+///
+/// kernel never looks up the root, it knows the root by this inode from the mount, sends
+/// `getattr` on it before any other request, so the filesystem must have the root ready when
+/// mounting. The inode 0 is never a valid inode, a lookup reply with inode 0 is a negative entry.
+///
+/// the root is fixed by the protocol, it can't be changed by the filesystem, a backend which has
+/// its own root inode number must map it to this inode, and map the parent of its children back.
+/// The root must be a directory, the session calls `getattr` on it after `init`, and fails the
+/// mount if it isn't a directory.
+pub const FUSE_ROOT_ID: Inode = 1;
+
 /// pre-defined Result, the Err type is [`Errno`].
 pub type Result<T> = std::result::Result<T, Errno>;
 
@@ -90,6 +141,56 @@ impl FileType {
             FileType::Socket => libc::S_IFSOCK,
         }
     }
+
+    /// get the [`FileType`] from the file type bits of `mode`, the permission bits are ignored.
+    /// It returns `None` if the file type bits are unknown.
+    pub const fn from_mode(mode: mode_t) -> Option<Self> {
+        match mode & libc::S_IFMT {
+            libc::S_IFIFO => Some(FileType::NamedPipe),
+            libc::S_IFCHR => Some(FileType::CharDevice),
+            libc::S_IFBLK => Some(FileType::BlockDevice),
+            libc::S_IFDIR => Some(FileType::Directory),
+            libc::S_IFREG => Some(FileType::RegularFile),
+            libc::S_IFLNK => Some(FileType::Symlink),
+            libc::S_IFSOCK => Some(FileType::Socket),
+            _ => None,
+        }
+    }
+
+    /// convert [`FileType`] into the `d_type` of a directory entry, such as `DT_DIR`.
+    pub const fn dirent_type(self) -> u8 {
+        match self {
+            FileType::NamedPipe => libc::DT_FIFO,
+            FileType::CharDevice => libc::DT_CHR,
+            FileType::BlockDevice => libc::DT_BLK,
+            FileType::Directory => libc::DT_DIR,
+            FileType::RegularFile => libc::DT_REG,
+            FileType::Symlink => libc::DT_LNK,
+            FileType::Socket => libc::DT_SOCK,
+        }
+    }
+}
+
+impl From<std::fs::FileType> for FileType {
+    fn from(file_type: std::fs::FileType) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_dir() {
+            FileType::Directory
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_fifo() {
+            FileType::NamedPipe
+        } else if file_type.is_char_device() {
+            FileType::CharDevice
+        } else if file_type.is_block_device() {
+            FileType::BlockDevice
+        } else if file_type.is_socket() {
+            FileType::Socket
+        } else {
+            FileType::RegularFile
+        }
+    }
 }
 
 impl From<FileType> for mode_t {
@@ -98,6 +199,90 @@ impl From<FileType> for mode_t {
     }
 }
 
+/// a mapping of the DAX window to remove, it is the argument of
+/// [`remove_mapping`][crate::raw::Filesystem::remove_mapping].
+#[cfg(feature = "dax")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RemoveMapping {
+    /// the offset in the DAX window.
+    pub moffset: u64,
+    /// the length of the mapping.
+    pub len: u64,
+}
+
+/// the type of a POSIX file lock.
+#[cfg(feature = "file-lock")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LockKind {
+    /// the shared lock, `F_RDLCK`.
+    Read,
+    /// the exclusive lock, `F_WRLCK`.
+    Write,
+    /// the unlock, `F_UNLCK`, in the getlk reply it means no lock conflicts.
+    Unlock,
+}
+
+#[cfg(feature = "file-lock")]
+impl LockKind {
+    fn from_raw(r#type: u32) -> Option<Self> {
+        match r#type as libc::c_int {
+            t if t == libc::F_RDLCK as libc::c_int => Some(Self::Read),
+            t if t == libc::F_WRLCK as libc::c_int => Some(Self::Write),
+            t if t == libc::F_UNLCK as libc::c_int => Some(Self::Unlock),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> u32 {
+        (match self {
+            Self::Read => libc::F_RDLCK,
+            Self::Write => libc::F_WRLCK,
+            Self::Unlock => libc::F_UNLCK,
+        }) as u32
+    }
+}
+
+/// a POSIX file lock, it is the argument of getlk and setlk, and the getlk reply.
+#[cfg(feature = "file-lock")]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FileLock {
+    /// the locked bytes, the lock to the end of file ends at [`FileLock::END_OF_FILE`].
+    pub range: Range<u64>,
+    /// the lock type.
+    pub kind: LockKind,
+    /// the pid of the process which requests the lock, in the getlk reply it is the pid of the
+    /// process which holds the conflicting lock.
+    pub pid: u32,
+    /// the owner of the lock, it is ignored in the getlk reply.
+    pub owner: u64,
+}
+
+#[cfg(feature = "file-lock")]
+impl FileLock {
+    /// the end of the range which locks to the end of file, kernel uses the max file offset for
+    /// it.
+    pub const END_OF_FILE: u64 = i64::MAX as u64 + 1;
+
+    /// the kernel lock range is inclusive, it is `None` if the lock type is invalid.
+    pub(crate) fn from_raw(lock: &fuse_file_lock, owner: u64) -> Option<Self> {
+        Some(Self {
+            range: lock.start..lock.end.saturating_add(1),
+            kind: LockKind::from_raw(lock.r#type)?,
+            pid: lock.pid,
+            owner,
+        })
+    }
+
+    pub(crate) fn to_raw(&self) -> fuse_file_lock {
+        fuse_file_lock {
+            start: self.range.start,
+            end: self.range.end.saturating_sub(1),
+            r#type: self.kind.to_raw(),
+            pid: self.pid,
+        }
+    }
+}
+
 /// the setattr argument.
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct SetAttr {
@@ -111,16 +296,25 @@ pub struct SetAttr {
     pub size: Option<u64>,
     /// the lock_owner argument.
     pub lock_owner: Option<u64>,
-    /// set file or directory atime.
+    /// set file or directory atime, it is `None` when the atime is not changed, like the
+    /// `UTIME_OMIT` of `utimensat(2)`.
     pub atime: Option<Timestamp>,
-    /// set file or directory mtime.
+    /// set file or directory mtime, it is `None` when the mtime is not changed.
     pub mtime: Option<Timestamp>,
+    /// the atime is set to the current time, like the `UTIME_NOW` of `utimensat(2)`, instead of
+    /// an explicit time, the `atime` is the current time when the request is received.
+    pub atime_now: bool,
+    /// the mtime is set to the current time, like the `atime_now`.
+    pub mtime_now: bool,
     /// set file or directory ctime.
     pub ctime: Option<Timestamp>,
+    /// set file or directory creation time.
     #[cfg(target_os = "macos")]
     pub crtime: Option<Timestamp>,
+    /// set file or directory change time, it is set by `FATTR_CHGTIME` instead of `ctime`.
     #[cfg(target_os = "macos")]
     pub chgtime: Option<Timestamp>,
+    /// set file or directory backup time.
     #[cfg(target_os = "macos")]
     pub bkuptime: Option<Timestamp>,
     #[cfg(target_os = "macos")]
@@ -161,6 +355,7 @@ impl From<&fuse_setattr_in> for SetAttr {
 
         if setattr_in.valid & FATTR_ATIME_NOW > 0 {
             set_attr.atime = Some(SystemTime::now().into());
+            set_attr.atime_now = true;
         }
 
         if setattr_in.valid & FATTR_MTIME > 0 {
@@ -169,6 +364,7 @@ impl From<&fuse_setattr_in> for SetAttr {
 
         if setattr_in.valid & FATTR_MTIME_NOW > 0 {
             set_attr.mtime = Some(SystemTime::now().into());
+            set_attr.mtime_now = true;
         }
 
         if setattr_in.valid & FATTR_LOCKOWNER > 0 {
@@ -181,17 +377,17 @@ impl From<&fuse_setattr_in> for SetAttr {
 
         #[cfg(target_os = "macos")]
         if setattr_in.valid & FATTR_CRTIME > 0 {
-            set_attr.ctime = fsai2ts!(setattr_in.crtime, setattr_in.crtimensec);
+            set_attr.crtime = fsai2ts!(setattr_in.crtime, setattr_in.crtimensec);
         }
 
         #[cfg(target_os = "macos")]
         if setattr_in.valid & FATTR_CHGTIME > 0 {
-            set_attr.ctime = fsai2ts!(setattr_in.chgtime, setattr_in.chgtimensec);
+            set_attr.chgtime = fsai2ts!(setattr_in.chgtime, setattr_in.chgtimensec);
         }
 
         #[cfg(target_os = "macos")]
         if setattr_in.valid & FATTR_BKUPTIME > 0 {
-            set_attr.ctime = fsai2ts!(setattr_in.bkuptime, setattr_in.bkuptimensec);
+            set_attr.bkuptime = fsai2ts!(setattr_in.bkuptime, setattr_in.bkuptimensec);
         }
 
         #[cfg(target_os = "macos")]
@@ -203,11 +399,22 @@ impl From<&fuse_setattr_in> for SetAttr {
     }
 }
 
+/// the security context of the creating process, it is delivered with the create, mkdir, mknod,
+/// symlink and tmpfile requests when the `security_context` of [`MountOptions`] is enabled on
+/// Linux, the filesystem can use it to label the new file, as the xattr named `name`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SecurityContext {
+    /// the name of the security context, like `security.selinux`.
+    pub name: OsString,
+    /// the value of the security context.
+    pub value: Vec<u8>,
+}
+
 /// A file's timestamp, according to FUSE.
 ///
 /// Nearly the same as a `libc::timespec`, except for the width of the nsec
-/// field.
-// Could implement From for Duration, and/or libc::timespec, if desired
+/// field. A timestamp before the epoch has a negative `sec` and a positive `nsec`, like
+/// `timespec`, e.g. 0.5 second before the epoch is `sec: -1, nsec: 500_000_000`.
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct Timestamp {
     pub sec: i64,
@@ -225,16 +432,87 @@ impl Timestamp {
 
 impl From<SystemTime> for Timestamp {
     fn from(t: SystemTime) -> Self {
-        let d = t
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_else(|_| Duration::from_secs(0));
-        Timestamp {
-            sec: d.as_secs().try_into().unwrap_or(i64::MAX),
-            nsec: d.subsec_nanos(),
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => Timestamp {
+                sec: d.as_secs().try_into().unwrap_or(i64::MAX),
+                nsec: d.subsec_nanos(),
+            },
+
+            Err(err) => {
+                let d = err.duration();
+                let sec = i64::try_from(d.as_secs()).map_or(i64::MIN, |sec| -sec);
+
+                if d.subsec_nanos() == 0 {
+                    Timestamp { sec, nsec: 0 }
+                } else {
+                    Timestamp {
+                        sec: sec.saturating_sub(1),
+                        nsec: 1_000_000_000 - d.subsec_nanos(),
+                    }
+                }
+            }
         }
     }
 }
 
+/// convert to [`SystemTime`], it fails with `EINVAL` if `nsec` is not less than
+/// 1_000_000_000, or `EOVERFLOW` if the time can't be represented by [`SystemTime`].
+impl TryFrom<Timestamp> for SystemTime {
+    type Error = Errno;
+
+    fn try_from(ts: Timestamp) -> std::result::Result<Self, Self::Error> {
+        if ts.nsec >= 1_000_000_000 {
+            return Err(libc::EINVAL.into());
+        }
+
+        let time = if ts.sec >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs(ts.sec as u64))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(ts.sec.unsigned_abs()))
+        };
+
+        time.and_then(|time| time.checked_add(Duration::from_nanos(ts.nsec as u64)))
+            .ok_or_else(|| libc::EOVERFLOW.into())
+    }
+}
+
+/// convert to `timespec`, the `sec` saturates if `time_t` is narrower than 64 bits, and the
+/// invalid `nsec` saturates to 999_999_999.
+impl From<Timestamp> for libc::timespec {
+    fn from(ts: Timestamp) -> Self {
+        let tv_sec = libc::time_t::try_from(ts.sec).unwrap_or(if ts.sec < 0 {
+            libc::time_t::MIN
+        } else {
+            libc::time_t::MAX
+        });
+
+        libc::timespec {
+            tv_sec,
+            tv_nsec: ts.nsec.min(999_999_999) as _,
+        }
+    }
+}
+
+/// convert from `timespec`, it fails with `EINVAL` if `tv_nsec` is not in the range
+/// `0..1_000_000_000`.
+impl TryFrom<libc::timespec> for Timestamp {
+    type Error = Errno;
+
+    // time_t is i64 on most targets, but it is i32 on some 32-bit targets
+    #[allow(clippy::useless_conversion)]
+    fn try_from(ts: libc::timespec) -> std::result::Result<Self, Self::Error> {
+        let nsec = u32::try_from(ts.tv_nsec)
+            .ok()
+            .filter(|nsec| *nsec < 1_000_000_000)
+            .ok_or_else(|| Errno::from(libc::EINVAL))?;
+
+        Ok(Timestamp {
+            sec: i64::from(ts.tv_sec),
+            nsec,
+        })
+    }
+}
+
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 fn find_fusermount3() -> io::Result<PathBuf> {
     which::which("fusermount3").map_err(|err| {
@@ -258,3 +536,158 @@ fn find_macfuse_mount() -> io::Result<PathBuf> {
         ))
     }
 }
+
+/// build the error of the mount binary which exits with failure, its exit status and stderr are
+/// included, so the reason like macFUSE is not loaded or blocked by the system can be found.
+#[cfg(target_os = "macos")]
+fn mount_binary_error(binary: &str, status: std::process::ExitStatus, stderr: &[u8]) -> io::Error {
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim();
+
+    if stderr.is_empty() {
+        io::Error::other(format!("{binary} run failed, {status}"))
+    } else {
+        io::Error::other(format!("{binary} run failed, {status}: {stderr}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_type_from_mode() {
+        for kind in [
+            FileType::NamedPipe,
+            FileType::CharDevice,
+            FileType::BlockDevice,
+            FileType::Directory,
+            FileType::RegularFile,
+            FileType::Symlink,
+            FileType::Socket,
+        ] {
+            assert_eq!(FileType::from_mode(kind.const_into_mode_t()), Some(kind));
+
+            // the permission bits are ignored
+            assert_eq!(
+                FileType::from_mode(kind.const_into_mode_t() | 0o4755),
+                Some(kind)
+            );
+        }
+
+        assert_eq!(FileType::from_mode(0o644), None);
+    }
+
+    #[test]
+    fn timestamp_system_time() {
+        let time = UNIX_EPOCH + Duration::new(10, 5);
+        let ts = Timestamp::from(time);
+        assert_eq!(ts, Timestamp::new(10, 5));
+        assert_eq!(SystemTime::try_from(ts).unwrap(), time);
+
+        // 0.5 second before the epoch
+        let time = UNIX_EPOCH - Duration::from_millis(500);
+        let ts = Timestamp::from(time);
+        assert_eq!(ts, Timestamp::new(-1, 500_000_000));
+        assert_eq!(SystemTime::try_from(ts).unwrap(), time);
+
+        let time = UNIX_EPOCH - Duration::from_secs(2);
+        assert_eq!(Timestamp::from(time), Timestamp::new(-2, 0));
+
+        assert_eq!(
+            SystemTime::try_from(Timestamp::new(0, 1_000_000_000)).unwrap_err(),
+            Errno::from(libc::EINVAL)
+        );
+    }
+
+    #[test]
+    fn timestamp_timespec() {
+        let timespec = libc::timespec::from(Timestamp::new(-1, 500_000_000));
+        assert_eq!(timespec.tv_sec, -1);
+        assert_eq!(timespec.tv_nsec, 500_000_000);
+        assert_eq!(
+            Timestamp::try_from(timespec).unwrap(),
+            Timestamp::new(-1, 500_000_000)
+        );
+
+        // the invalid nsec saturates
+        let timespec = libc::timespec::from(Timestamp::new(1, 1_000_000_000));
+        assert_eq!(timespec.tv_nsec, 999_999_999);
+
+        for tv_nsec in [-1, 1_000_000_000] {
+            let timespec = libc::timespec { tv_sec: 0, tv_nsec };
+
+            assert_eq!(
+                Timestamp::try_from(timespec).unwrap_err(),
+                Errno::from(libc::EINVAL)
+            );
+        }
+    }
+
+    /// a zeroed `fuse_setattr_in` with the `valid` bits.
+    fn setattr_in(valid: u32) -> fuse_setattr_in {
+        let mut setattr_in = raw::protocol::decode_body::<fuse_setattr_in>(
+            &[0; std::mem::size_of::<fuse_setattr_in>()],
+        )
+        .unwrap();
+        setattr_in.valid = valid;
+
+        setattr_in
+    }
+
+    #[test]
+    fn set_attr_times() {
+        let mut mtime_in = setattr_in(FATTR_MTIME);
+        mtime_in.atime = 1;
+        mtime_in.mtime = 2;
+        mtime_in.mtimensec = 3;
+
+        // only the mtime is set, the atime is omitted
+        let set_attr = SetAttr::from(&mtime_in);
+        assert_eq!(set_attr.mtime, Some(Timestamp::new(2, 3)));
+        assert!(!set_attr.mtime_now);
+        assert_eq!(set_attr.atime, None);
+        assert!(!set_attr.atime_now);
+
+        let now = Timestamp::from(SystemTime::now());
+        let set_attr = SetAttr::from(&setattr_in(FATTR_ATIME | FATTR_ATIME_NOW));
+        assert!(set_attr.atime_now);
+        assert!(set_attr.atime.unwrap() >= now);
+        assert_eq!(set_attr.mtime, None);
+        assert!(!set_attr.mtime_now);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn set_attr_macos_times() {
+        let mut times_in = setattr_in(0);
+        times_in.ctime = 1;
+        times_in.crtime = 2;
+        times_in.crtimensec = 3;
+        times_in.chgtime = 4;
+        times_in.chgtimensec = 5;
+        times_in.bkuptime = 6;
+        times_in.bkuptimensec = 7;
+
+        times_in.valid = FATTR_CRTIME;
+        let set_attr = SetAttr::from(&times_in);
+        assert_eq!(set_attr.crtime, Some(Timestamp::new(2, 3)));
+        assert_eq!(set_attr.chgtime, None);
+        assert_eq!(set_attr.bkuptime, None);
+        assert_eq!(set_attr.ctime, None);
+
+        times_in.valid = FATTR_CHGTIME;
+        let set_attr = SetAttr::from(&times_in);
+        assert_eq!(set_attr.chgtime, Some(Timestamp::new(4, 5)));
+        assert_eq!(set_attr.crtime, None);
+        assert_eq!(set_attr.bkuptime, None);
+        assert_eq!(set_attr.ctime, None);
+
+        times_in.valid = FATTR_BKUPTIME;
+        let set_attr = SetAttr::from(&times_in);
+        assert_eq!(set_attr.bkuptime, Some(Timestamp::new(6, 7)));
+        assert_eq!(set_attr.crtime, None);
+        assert_eq!(set_attr.chgtime, None);
+        assert_eq!(set_attr.ctime, None);
+    }
+}
@@ -1,12 +1,30 @@
-use std::ffi::OsString;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroUsize};
+use std::os::unix::ffi::OsStrExt;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::os::unix::io::RawFd;
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use nix::sys::stat::mode_t;
 
 #[cfg(target_os = "freebsd")]
 use nix::mount::Nmount;
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 use nix::unistd;
 
+use crate::raw::abi::DEFAULT_MAX_BACKGROUND;
+use crate::raw::Request;
+use crate::xattr::{self, XattrNamespace};
+use crate::{Errno, Opcode};
+
+/// the default of [`MountOptions::max_concurrent_normal_requests`].
+pub(crate) const DEFAULT_MAX_CONCURRENT_NORMAL_REQUESTS: usize = 64;
+
 /// mount options.
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct MountOptions {
@@ -43,15 +61,140 @@ pub struct MountOptions {
     pub(crate) no_open_dir_support: bool,
     pub(crate) handle_killpriv: bool,
     pub(crate) write_back: bool,
+    pub(crate) keep_cache: bool,
     pub(crate) force_readdir_plus: bool,
+    pub(crate) synthesize_dot_entries: bool,
+    pub(crate) track_lookups: bool,
+    pub(crate) default_file_mode: Option<u16>,
+    pub(crate) default_dir_mode: Option<u16>,
+    pub(crate) max_write: Option<NonZeroU32>,
+    pub(crate) max_background: Option<NonZeroU16>,
+    pub(crate) congestion_threshold: Option<NonZeroU16>,
+    pub(crate) max_concurrent_bulk_ops: Option<NonZeroUsize>,
+    pub(crate) max_concurrent_normal_requests: Option<NonZeroUsize>,
+    #[cfg(feature = "file-lock")]
+    pub(crate) flock: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) security_context: bool,
+    pub(crate) xattr_namespaces: Option<Vec<XattrNamespace>>,
 
     // Other FUSE mount options
     // default 40000
     pub(crate) rootmode: Option<u32>,
+
+    // Hooks
+    pub(crate) on_unknown_opcode: Option<Hook<UnknownOpcodeHook>>,
+    pub(crate) spawner: Option<Hook<Spawner>>,
+    pub(crate) request_class: Option<Hook<RequestClassifier>>,
+    pub(crate) access_filter: Option<Hook<AccessFilter>>,
+    pub(crate) shutdown_signal: Option<Hook<ShutdownSignal>>,
+}
+
+/// the hook type of [`MountOptions::on_unknown_opcode`].
+pub(crate) type UnknownOpcodeHook = dyn Fn(u32, &[u8]) + Send + Sync;
+
+/// the hook type of [`MountOptions::spawner`].
+pub(crate) type Spawner = dyn Fn(BoxFuture<'static, ()>) + Send + Sync;
+
+/// the hook type of [`MountOptions::request_class`].
+pub(crate) type RequestClassifier = dyn Fn(Opcode) -> Priority + Send + Sync;
+
+/// the hook type of [`MountOptions::access_filter`].
+pub(crate) type AccessFilter = dyn Fn(&Request, Opcode) -> Result<(), Errno> + Send + Sync;
+
+/// the hook type of [`MountOptions::shutdown_signal`], the future is taken by the first mount.
+pub(crate) type ShutdownSignal = Mutex<Option<BoxFuture<'static, ()>>>;
+
+/// the priority of a request, see [`MountOptions::request_class`].
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Priority {
+    /// the request waits for the normal request budget before it is handled, such as the bulk
+    /// IO requests.
+    #[default]
+    Normal,
+    /// the request is handled at once, such as the cheap metadata requests.
+    High,
+}
+
+/// a user provided callback stored in [`MountOptions`].
+///
+/// Two hooks are equal only when they are the same callback.
+pub(crate) struct Hook<F: ?Sized>(pub(crate) Arc<F>);
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> Debug for Hook<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+impl<F: ?Sized> PartialEq for Hook<F> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<F: ?Sized> Eq for Hook<F> {}
+
+/// the error returned by [`MountOptions::validate`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MountOptionsError {
+    /// `allow_other` and `allow_root` are mutually exclusive.
+    AllowOtherWithAllowRoot,
+    /// `nonempty` is set in the custom options, it is not a kernel mount option, use
+    /// [`MountOptions::nonempty`] instead.
+    NonemptyInCustomOptions,
+    /// the `congestion_threshold` is larger than the `max_background`.
+    CongestionThresholdAboveMaxBackground {
+        congestion_threshold: u16,
+        max_background: u16,
+    },
+}
+
+impl Display for MountOptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MountOptionsError::AllowOtherWithAllowRoot => {
+                f.write_str("allow_other and allow_root are mutually exclusive")
+            }
+
+            MountOptionsError::NonemptyInCustomOptions => f.write_str(
+                "nonempty is not a kernel mount option, use MountOptions::nonempty instead",
+            ),
+
+            MountOptionsError::CongestionThresholdAboveMaxBackground {
+                congestion_threshold,
+                max_background,
+            } => write!(
+                f,
+                "congestion_threshold {congestion_threshold} is larger than max_background \
+                 {max_background}"
+            ),
+        }
+    }
+}
+
+impl Error for MountOptionsError {}
+
+impl From<MountOptionsError> for io::Error {
+    fn from(err: MountOptionsError) -> Self {
+        io::Error::new(ErrorKind::InvalidInput, err)
+    }
 }
 
 impl MountOptions {
     /// set fuse filesystem mount `user_id`, default is current uid.
+    ///
+    /// # Notes:
+    ///
+    /// it is the owner of the mount, not the owner of files, the owner of files is the `uid` and
+    /// `gid` in [`FileAttr`][crate::raw::reply::FileAttr].
     pub fn uid(&mut self, uid: u32) -> &mut Self {
         self.uid.replace(uid);
 
@@ -66,6 +209,13 @@ impl MountOptions {
     }
 
     /// set fuse filesystem name, default is **fuse**.
+    ///
+    /// # Notes:
+    ///
+    /// the name can contain spaces, commas and non-ASCII characters. The `\` and `,` are escaped
+    /// when the name is passed as a mount option, such as the `fsname` of `fusermount3`, and each
+    /// `-o` option of `mount_macfuse` is a separate argument, so the spaces are kept. The
+    /// `/proc/self/mountinfo` shows a space as `\040`.
     pub fn fs_name(&mut self, name: impl Into<String>) -> &mut Self {
         self.fs_name.replace(name.into());
 
@@ -80,7 +230,8 @@ impl MountOptions {
         self
     }
 
-    /// set fuse filesystem `allow_root` mount option, default is disable.
+    /// set fuse filesystem `allow_root` mount option, default is disable, it can't be used with
+    /// [`allow_other`][MountOptions::allow_other].
     pub fn allow_root(&mut self, allow_root: bool) -> &mut Self {
         self.allow_root = allow_root;
 
@@ -95,6 +246,12 @@ impl MountOptions {
     }
 
     /// set fuse filesystem `ro` mount option, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// the kernel rejects the writes of a read only mount, fuse3 also replies `EROFS` for the
+    /// requests which modify the filesystem and the open with the write flags, so the filesystem
+    /// won't see them, and the [`write_back`][MountOptions::write_back] is not enabled.
     pub fn read_only(&mut self, read_only: bool) -> &mut Self {
         self.read_only = read_only;
 
@@ -120,6 +277,34 @@ impl MountOptions {
         self
     }
 
+    /// set the permission of the non-directory files whose replied attributes have the
+    /// permission 0, only the permission bits of `mode` are used, default is unset.
+    ///
+    /// # Notes:
+    ///
+    /// when [`default_permissions`][MountOptions::default_permissions] is enabled, kernel checks
+    /// the permission bits of the attributes, the files of a filesystem which doesn't track the
+    /// modes can't be accessed, such as a read only assets filesystem. The permission is applied
+    /// to the lookup, getattr, setattr, statx, readdirplus replies and the replies which create
+    /// an entry.
+    pub fn default_file_mode(&mut self, mode: mode_t) -> &mut Self {
+        self.default_file_mode.replace((mode & 0o7777) as _);
+
+        self
+    }
+
+    /// set the permission of the directories whose replied attributes have the permission 0,
+    /// only the permission bits of `mode` are used, default is unset.
+    ///
+    /// # Notes:
+    ///
+    /// see [`default_file_mode`][MountOptions::default_file_mode].
+    pub fn default_dir_mode(&mut self, mode: mode_t) -> &mut Self {
+        self.default_dir_mode.replace((mode & 0o7777) as _);
+
+        self
+    }
+
     /// don't apply umask to file mode on create operations, default is disable.
     pub fn dont_mask(&mut self, dont_mask: bool) -> &mut Self {
         self.dont_mask = dont_mask;
@@ -148,6 +333,24 @@ impl MountOptions {
         self
     }
 
+    /// add the [`FOPEN_KEEP_CACHE`] to the open, opendir, create and tmpfile replies, so kernel
+    /// keeps the cached pages of a file across opens, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// the flag is not added when the reply has [`FOPEN_DIRECT_IO`]. Kernel won't know the file
+    /// is changed out-of-band any more, the filesystem is responsible for invalidating the cache
+    /// by [`Notify::invalid_inode`][crate::notify::Notify::invalid_inode] when the file contents
+    /// change.
+    ///
+    /// [`FOPEN_KEEP_CACHE`]: crate::raw::flags::FOPEN_KEEP_CACHE
+    /// [`FOPEN_DIRECT_IO`]: crate::raw::flags::FOPEN_DIRECT_IO
+    pub fn keep_cache(&mut self, keep_cache: bool) -> &mut Self {
+        self.keep_cache = keep_cache;
+
+        self
+    }
+
     /// try to set the `FUSE_WRITEBACK_CACHE` enable write back cache for buffered writes, default
     /// is disable.
     ///
@@ -171,13 +374,264 @@ impl MountOptions {
         self
     }
 
+    /// synthesize the "." and ".." entries in readdir and readdirplus, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// the entries are only synthesized when the filesystem sets the `parent` of
+    /// [`ReplyDirectory`][crate::raw::reply::ReplyDirectory] or
+    /// [`ReplyDirectoryPlus`][crate::raw::reply::ReplyDirectoryPlus]. The offsets 1 and 2 are
+    /// reserved for "." and "..", the entries given by filesystem must use the offsets greater
+    /// than 2, and the filesystem should list from the first entry when the `offset` is not
+    /// greater than 2.
+    pub fn synthesize_dot_entries(&mut self, synthesize_dot_entries: bool) -> &mut Self {
+        self.synthesize_dot_entries = synthesize_dot_entries;
+
+        self
+    }
+
+    /// count the lookups of each inode replied to kernel and subtract the forgets, to debug the
+    /// lookup count leaks of the filesystem, default is disable.
+    ///
+    /// The lookups are counted by the lookup, mknod, mkdir, symlink, link, create, tmpfile and
+    /// readdirplus replies, a warning is logged when a forget is more than the lookups of the
+    /// inode, or a reply gives a different generation to an inode which is not forgotten, the
+    /// inodes which are not forgotten are logged when unmounting.
+    ///
+    /// # Notes:
+    ///
+    /// the kernel doesn't send the forgets of the cached inodes when unmounting, their lookups
+    /// are dropped implicitly, so the logged inodes at unmount are the inodes which are still
+    /// cached by kernel, not always the leaks. The root inode is never forgotten, it is not
+    /// counted. It adds a lock to the replies, don't enable it in production.
+    pub fn track_lookups(&mut self, track_lookups: bool) -> &mut Self {
+        self.track_lookups = track_lookups;
+
+        self
+    }
+
+    /// set the max size of a write request, default is the `max_write` in
+    /// [`ReplyInit`][crate::raw::reply::ReplyInit].
+    ///
+    /// # Notes:
+    ///
+    /// the negotiated max write size is the minimum of this option, the `max_write` in
+    /// [`ReplyInit`][crate::raw::reply::ReplyInit] and the max size kernel can send.
+    pub fn max_write(&mut self, max_write: NonZeroU32) -> &mut Self {
+        self.max_write = Some(max_write);
+
+        self
+    }
+
+    /// set the max number of the background requests kernel sends at the same time, like the
+    /// readahead and the async direct IO requests, default is 12.
+    ///
+    /// # Notes:
+    ///
+    /// kernel limits it by the `/proc/sys/fs/fuse/max_user_bgreq` when the mount is not mounted
+    /// by root.
+    pub fn max_background(&mut self, max_background: NonZeroU16) -> &mut Self {
+        self.max_background = Some(max_background);
+
+        self
+    }
+
+    /// set the number of the background requests which makes kernel mark the filesystem
+    /// congested, the writeback and readahead wait when it is congested, default is 75% of the
+    /// [`max_background`][MountOptions::max_background].
+    ///
+    /// # Notes:
+    ///
+    /// it must not be larger than the `max_background`, otherwise the mount returns
+    /// [`MountOptionsError::CongestionThresholdAboveMaxBackground`].
+    pub fn congestion_threshold(&mut self, congestion_threshold: NonZeroU16) -> &mut Self {
+        self.congestion_threshold = Some(congestion_threshold);
+
+        self
+    }
+
+    /// limit the number of the read, readdir and readdirplus requests which are handled at the
+    /// same time, the others wait in queue, default is no limit.
+    ///
+    /// # Notes:
+    ///
+    /// each of them may hold a reply buffer of the request size, such as the `max_write` sized
+    /// read data and the readdir buffer, so it bounds the memory of the large directory listings
+    /// and reads. The waiting requests still count as in flight, and can be interrupted.
+    pub fn max_concurrent_bulk_ops(&mut self, max_concurrent_bulk_ops: NonZeroUsize) -> &mut Self {
+        self.max_concurrent_bulk_ops = Some(max_concurrent_bulk_ops);
+
+        self
+    }
+
+    /// limit the number of the [`Priority::Normal`] requests which are handled at the same time
+    /// when the [`request_class`][MountOptions::request_class] is set, the rest of the runtime is
+    /// reserved for the [`Priority::High`] requests, default is 64.
+    ///
+    /// # Notes:
+    ///
+    /// the request which may wait for another request, such as `FUSE_SETLKW` and `FUSE_POLL`,
+    /// should be classified as [`Priority::High`], otherwise the waiting requests may hold all the
+    /// budget and the requests they wait for are never handled.
+    pub fn max_concurrent_normal_requests(
+        &mut self,
+        max_concurrent_normal_requests: NonZeroUsize,
+    ) -> &mut Self {
+        self.max_concurrent_normal_requests = Some(max_concurrent_normal_requests);
+
+        self
+    }
+
+    #[cfg(feature = "file-lock")]
+    /// try to set the `FUSE_FLOCK_LOCKS` to handle `flock(2)` by
+    /// [`Filesystem::flock`][crate::raw::Filesystem::flock], default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    pub fn flock(&mut self, flock: bool) -> &mut Self {
+        self.flock = flock;
+
+        self
+    }
+
+    #[cfg(target_os = "linux")]
+    /// try to set the `FUSE_SECURITY_CTX` to receive the security context of the creating process
+    /// with the create, mkdir, mknod, symlink and tmpfile requests, default is disable.
+    ///
+    /// # Notes:
+    ///
+    /// it requires Linux 6.3 or later, the security context is `None` if the kernel doesn't
+    /// support it or no LSM, like SELinux, provides the context.
+    pub fn security_context(&mut self, security_context: bool) -> &mut Self {
+        self.security_context = security_context;
+
+        self
+    }
+
+    /// only allow the extended attributes in the `namespaces`, the getxattr, setxattr and
+    /// removexattr requests of the other namespaces are replied `EOPNOTSUPP` directly, default
+    /// allows all namespaces.
+    ///
+    /// # Notes:
+    ///
+    /// the listxattr requests are not filtered, the filesystem should not list the names which
+    /// are not allowed. When the SELinux is active, [`XattrNamespace::Security`] should be
+    /// allowed too.
+    pub fn xattr_namespaces(&mut self, namespaces: &[XattrNamespace]) -> &mut Self {
+        self.xattr_namespaces = Some(namespaces.to_vec());
+
+        self
+    }
+
+    /// set a spawner which spawns the request handle tasks, default is the runtime spawn function.
+    ///
+    /// It is useful for tracking the in-flight requests, such as putting all tasks in a
+    /// `JoinSet`. The spawner must poll the task to completion, or the request will never be
+    /// replied.
+    pub fn spawner<F>(&mut self, spawner: F) -> &mut Self
+    where
+        F: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+    {
+        self.spawner = Some(Hook(Arc::new(spawner)));
+
+        self
+    }
+
+    /// set a classifier which decides the [`Priority`] of a request by its opcode, default is
+    /// none, all requests are handled at once.
+    ///
+    /// # Notes:
+    ///
+    /// all requests are still spawned by the [`spawner`][MountOptions::spawner], but only
+    /// [`max_concurrent_normal_requests`][MountOptions::max_concurrent_normal_requests] of the
+    /// [`Priority::Normal`] requests are handled at the same time, the others wait without being
+    /// polled by the runtime, so the [`Priority::High`] requests won't be stuck behind a flood of
+    /// bulk IO tasks. The classifier is called in the dispatch task for every request, it should
+    /// be cheap and must not block.
+    pub fn request_class<F>(&mut self, classifier: F) -> &mut Self
+    where
+        F: Fn(Opcode) -> Priority + Send + Sync + 'static,
+    {
+        self.request_class = Some(Hook(Arc::new(classifier)));
+
+        self
+    }
+
+    /// set a filter which is called with the request and its opcode before the request is
+    /// handled, if it returns an error, the request is replied with the errno directly and the
+    /// filesystem won't see it, default is none.
+    ///
+    /// # Notes:
+    ///
+    /// the requests which have no reply or are managed by fuse3 itself, such as forget, batch
+    /// forget, interrupt, notify reply, init and destroy, are not filtered. The release and
+    /// releasedir are not filtered too, the kernel ignores their errors, so the filesystem would
+    /// never free the file handles. The filter is called in the dispatch task for every request,
+    /// it should be cheap and must not block.
+    pub fn access_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&Request, Opcode) -> Result<(), Errno> + Send + Sync + 'static,
+    {
+        self.access_filter = Some(Hook(Arc::new(filter)));
+
+        self
+    }
+
+    /// set a future which shuts down the filesystem when it is ready, the mount path is unmounted
+    /// and [`Filesystem::destroy`][crate::raw::Filesystem::destroy] is called, then the
+    /// [`MountHandle`][crate::raw::MountHandle] returns, default is none.
+    ///
+    /// # Notes:
+    ///
+    /// the future is used by the first mount with these options or their clones. It is dropped
+    /// when the filesystem is unmounted by other ways, such as
+    /// [`MountHandle::unmount`][crate::raw::MountHandle::unmount].
+    pub fn shutdown_signal<F>(&mut self, signal: F) -> &mut Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_signal = Some(Hook(Arc::new(Mutex::new(Some(Box::pin(signal))))));
+
+        self
+    }
+
     /// set custom options for fuse filesystem, the custom options will be used in mount
+    ///
+    /// # Notes:
+    ///
+    /// on macOS, the custom options are passed as one `-o` option of `mount_macfuse`.
     pub fn custom_options(&mut self, custom_options: impl Into<OsString>) -> &mut Self {
         self.custom_options = Some(custom_options.into());
 
         self
     }
 
+    /// set a hook which will be called when receive an opcode fuse3 doesn't know, the hook
+    /// arguments are the raw opcode and the request data without the `fuse_in_header`, default is
+    /// none.
+    ///
+    /// # Notes:
+    ///
+    /// the request is still replied with `ENOSYS` after the hook is called.
+    pub fn on_unknown_opcode<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(u32, &[u8]) + Send + Sync + 'static,
+    {
+        self.on_unknown_opcode = Some(Hook(Arc::new(hook)));
+
+        self
+    }
+
+    /// check if the extended attribute `name` is allowed by the
+    /// [`xattr_namespaces`](Self::xattr_namespaces).
+    pub(crate) fn is_xattr_allowed(&self, name: &OsStr) -> bool {
+        match &self.xattr_namespaces {
+            None => true,
+            Some(namespaces) => namespaces.contains(&xattr::namespace(name)),
+        }
+    }
+
     #[cfg(target_os = "freebsd")]
     pub(crate) fn build(&self) -> Nmount {
         let mut nmount = Nmount::new();
@@ -206,6 +660,47 @@ impl MountOptions {
         nmount
     }
 
+    /// check the conflicting options, it is called automatically when mount, and the mount
+    /// returns an [`io::Error`] with kind [`ErrorKind::InvalidInput`] which wraps the
+    /// [`MountOptionsError`].
+    pub fn validate(&self) -> Result<(), MountOptionsError> {
+        if self.allow_other && self.allow_root {
+            return Err(MountOptionsError::AllowOtherWithAllowRoot);
+        }
+
+        if let Some(custom_options) = &self.custom_options {
+            if custom_options
+                .as_bytes()
+                .split(|c| *c == b',' || c.is_ascii_whitespace())
+                .any(|option| option == b"nonempty")
+            {
+                return Err(MountOptionsError::NonemptyInCustomOptions);
+            }
+        }
+
+        let (max_background, congestion_threshold) = self.background_limits();
+        if congestion_threshold > max_background {
+            return Err(MountOptionsError::CongestionThresholdAboveMaxBackground {
+                congestion_threshold,
+                max_background,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// the `max_background` and `congestion_threshold` of the init reply.
+    pub(crate) fn background_limits(&self) -> (u16, u16) {
+        let max_background = self
+            .max_background
+            .map_or(DEFAULT_MAX_BACKGROUND, NonZeroU16::get);
+        let congestion_threshold = self
+            .congestion_threshold
+            .map_or((u32::from(max_background) * 3 / 4) as u16, NonZeroU16::get);
+
+        (max_background, congestion_threshold)
+    }
+
     #[cfg(target_os = "linux")]
     pub(crate) fn build(&self, fd: RawFd) -> OsString {
         let mut opts = vec![
@@ -243,23 +738,31 @@ impl MountOptions {
         options
     }
 
+    /// the `mount_macfuse` arguments, each `-o` and its option are separate arguments, so the
+    /// option can contain spaces.
     #[cfg(target_os = "macos")]
-    pub(crate) fn build(&self) -> OsString {
-        let mut opts = vec![String::from("-o fsname=ofs")];
+    pub(crate) fn build(&self) -> Vec<OsString> {
+        let mut opts = vec![format!(
+            "fsname={}",
+            escape_option_value(self.fs_name.as_deref().unwrap_or("ofs"))
+        )];
 
         if self.allow_root {
-            opts.push("-o allow_root".to_string());
+            opts.push("allow_root".to_string());
         }
 
         if self.allow_other {
-            opts.push("-o allow_other".to_string());
+            opts.push("allow_other".to_string());
         }
 
-        let mut options = OsString::from(opts.join(" "));
+        let mut options = opts
+            .into_iter()
+            .flat_map(|opt| [OsString::from("-o"), OsString::from(opt)])
+            .collect::<Vec<_>>();
 
         if let Some(custom_options) = &self.custom_options {
-            options.push(" ");
-            options.push(custom_options);
+            options.push(OsString::from("-o"));
+            options.push(custom_options.clone());
         }
 
         options
@@ -279,7 +782,7 @@ impl MountOptions {
             format!("rootmode={}", self.rootmode.unwrap_or(40000)),
             format!(
                 "fsname={}",
-                self.fs_name.as_ref().unwrap_or(&"fuse".to_string())
+                escape_option_value(self.fs_name.as_deref().unwrap_or("fuse"))
             ),
         ];
 
@@ -391,3 +894,145 @@ impl MountOptions {
         flags
     }
 }
+
+/// escape the `\` and `,` in a mount option value with `\`, so the value won't be split into
+/// multiple options by the mount helper.
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    target_os = "macos"
+))]
+fn escape_option_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if c == '\\' || c == ',' {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn non_zero(n: u16) -> NonZeroU16 {
+        NonZeroU16::new(n).unwrap()
+    }
+
+    #[test]
+    fn validate_valid_options() {
+        assert_eq!(MountOptions::default().validate(), Ok(()));
+
+        let mut options = MountOptions::default();
+        options
+            .allow_other(true)
+            .custom_options("nonempty_dir,ro")
+            .max_background(non_zero(16))
+            .congestion_threshold(non_zero(16));
+        assert_eq!(options.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_allow_other_with_allow_root() {
+        let mut options = MountOptions::default();
+        options.allow_other(true).allow_root(true);
+
+        assert_eq!(
+            options.validate(),
+            Err(MountOptionsError::AllowOtherWithAllowRoot)
+        );
+    }
+
+    #[test]
+    fn validate_nonempty_in_custom_options() {
+        for custom_options in ["nonempty", "ro,nonempty", "-o nonempty"] {
+            let mut options = MountOptions::default();
+            options.custom_options(custom_options);
+
+            assert_eq!(
+                options.validate(),
+                Err(MountOptionsError::NonemptyInCustomOptions),
+                "{custom_options}"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_congestion_threshold_above_max_background() {
+        let mut options = MountOptions::default();
+        options
+            .max_background(non_zero(8))
+            .congestion_threshold(non_zero(9));
+
+        assert_eq!(
+            options.validate(),
+            Err(MountOptionsError::CongestionThresholdAboveMaxBackground {
+                congestion_threshold: 9,
+                max_background: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn background_limits_default_congestion_threshold() {
+        assert_eq!(
+            MountOptions::default().background_limits(),
+            (DEFAULT_MAX_BACKGROUND, DEFAULT_MAX_BACKGROUND * 3 / 4)
+        );
+
+        let mut options = MountOptions::default();
+        options.max_background(non_zero(100));
+        assert_eq!(options.background_limits(), (100, 75));
+
+        // the default threshold follows the max background without overflow
+        options.max_background(non_zero(u16::MAX));
+        assert_eq!(
+            options.background_limits(),
+            (u16::MAX, (u32::from(u16::MAX) * 3 / 4) as u16)
+        );
+    }
+
+    #[test]
+    fn background_limits_reject_congestion_threshold_above_max_background() {
+        let mut options = MountOptions::default();
+        options.congestion_threshold(non_zero(DEFAULT_MAX_BACKGROUND + 1));
+
+        assert_eq!(
+            options.background_limits(),
+            (DEFAULT_MAX_BACKGROUND, DEFAULT_MAX_BACKGROUND + 1)
+        );
+        assert_eq!(
+            options.validate(),
+            Err(MountOptionsError::CongestionThresholdAboveMaxBackground {
+                congestion_threshold: DEFAULT_MAX_BACKGROUND + 1,
+                max_background: DEFAULT_MAX_BACKGROUND,
+            })
+        );
+
+        // the explicit threshold is kept when it is not above the max background
+        options.max_background(non_zero(DEFAULT_MAX_BACKGROUND + 1));
+        assert_eq!(
+            options.background_limits(),
+            (DEFAULT_MAX_BACKGROUND + 1, DEFAULT_MAX_BACKGROUND + 1)
+        );
+        assert_eq!(options.validate(), Ok(()));
+    }
+
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "macos"
+    ))]
+    #[test]
+    fn escape_option_value_escapes_backslash_and_comma() {
+        assert_eq!(escape_option_value("fuse"), "fuse");
+        assert_eq!(escape_option_value("my fs"), "my fs");
+        assert_eq!(escape_option_value("a,b"), "a\\,b");
+        assert_eq!(escape_option_value("a\\b"), "a\\\\b");
+        assert_eq!(escape_option_value("\\,"), "\\\\\\,");
+        assert_eq!(escape_option_value("文件 系统"), "文件 系统");
+    }
+}
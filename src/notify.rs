@@ -1,114 +1,302 @@
 //! notify kernel.
 
 use std::ffi::OsString;
+use std::io;
 use std::os::unix::ffi::OsStrExt;
 
-use bincode::Options;
 use bytes::{Buf, Bytes};
-use futures_channel::mpsc::UnboundedSender;
 use futures_util::future::Either;
 use futures_util::sink::SinkExt;
+use tracing::debug;
 
-use crate::helper::get_bincode_config;
 use crate::raw::abi::{
     fuse_notify_code, fuse_notify_delete_out, fuse_notify_inval_entry_out,
     fuse_notify_inval_inode_out, fuse_notify_poll_wakeup_out, fuse_notify_retrieve_out,
-    fuse_notify_store_out, fuse_out_header, FUSE_NOTIFY_DELETE_OUT_SIZE,
-    FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE, FUSE_NOTIFY_INVAL_INODE_OUT_SIZE,
-    FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE, FUSE_NOTIFY_RETRIEVE_OUT_SIZE, FUSE_NOTIFY_STORE_OUT_SIZE,
-    FUSE_OUT_HEADER_SIZE,
+    fuse_notify_store_out, FUSE_NOTIFY_DELETE_OUT_SIZE, FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE,
+    FUSE_NOTIFY_INVAL_INODE_OUT_SIZE, FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE,
+    FUSE_NOTIFY_RETRIEVE_OUT_SIZE, FUSE_NOTIFY_STORE_OUT_SIZE,
 };
-use crate::raw::FuseData;
+use crate::raw::{protocol, FuseData, ReplyBuffer, Response, ResponseSender};
 
 #[derive(Debug, Clone)]
 /// notify kernel there are something need to handle.
+///
+/// # Notes:
+///
+/// all the notify methods return an [`io::ErrorKind::NotConnected`] error when the fuse
+/// connection is closed, such as the filesystem is unmounted, the notification is dropped then.
 pub struct Notify {
-    sender: UnboundedSender<FuseData>,
+    sender: ResponseSender,
 }
 
 impl Notify {
-    pub(crate) fn new(sender: UnboundedSender<FuseData>) -> Self {
+    pub(crate) fn new(sender: ResponseSender) -> Self {
         Self { sender }
     }
 
     /// notify kernel there are something need to handle. If notify failed, the `kind` will be
     /// return in `Err`.
     async fn notify(&mut self, kind: NotifyKind) -> Result<(), NotifyKind> {
-        let data = match &kind {
-            NotifyKind::Wakeup { kh } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_POLL as i32,
-                    unique: 0,
-                };
+        let data = kind.encode();
 
-                let wakeup_out = fuse_notify_poll_wakeup_out { kh: *kh };
+        self.sender.send(Response::Reply(data)).await.or(Err(kind))
+    }
 
-                let mut data =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE);
+    /// send the notification, it fails with [`io::ErrorKind::NotConnected`] when the fuse
+    /// connection is closed, such as the filesystem is unmounted.
+    async fn send(&mut self, kind: NotifyKind) -> io::Result<()> {
+        self.notify(kind).await.map_err(|_| not_connected())
+    }
 
-                get_bincode_config()
-                    .serialize_into(&mut data, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data, &wakeup_out)
-                    .expect("vec size is not enough");
+    /// try to notify kernel the IO is ready, kernel can wakeup the waiting program.
+    pub async fn wakeup(mut self, kh: u64) -> io::Result<()> {
+        self.send(NotifyKind::Wakeup { kh }).await
+    }
+
+    /// try to notify the cache invalidation about an inode.
+    pub async fn invalid_inode(mut self, inode: u64, offset: i64, len: i64) -> io::Result<()> {
+        self.send(NotifyKind::InvalidInode { inode, offset, len })
+            .await
+    }
+
+    /// try to notify the invalidation about a directory entry.
+    pub async fn invalid_entry(mut self, parent: u64, name: OsString) -> io::Result<()> {
+        self.send(NotifyKind::InvalidEntry { parent, name }).await
+    }
 
-                Either::Left(data)
+    /// try to notify a directory entry has been deleted.
+    pub async fn delete(mut self, parent: u64, child: u64, name: OsString) -> io::Result<()> {
+        self.send(NotifyKind::Delete {
+            parent,
+            child,
+            name,
+        })
+        .await
+    }
+
+    /// try to push the data in an inode for updating the kernel cache.
+    pub async fn store(mut self, inode: u64, offset: u64, mut data: impl Buf) -> io::Result<()> {
+        self.send(NotifyKind::Store {
+            inode,
+            offset,
+            data: data.copy_to_bytes(data.remaining()),
+        })
+        .await
+    }
+
+    /// send many notifications in one call, such as invalidating many entries to flush the
+    /// kernel cache. All the notifications are encoded into one buffer which is sent to the
+    /// reply task as one message, instead of sending each of them like calling the notify
+    /// method for each of them.
+    ///
+    /// # Notes:
+    ///
+    /// the reply task still writes the notifications to the fuse connection one by one in
+    /// order, because the fuse connection takes one notification in one write. The data of the
+    /// [`NotifyOp::Store`] is copied into the buffer.
+    pub async fn batch(&self, ops: impl IntoIterator<Item = NotifyOp>) -> io::Result<()> {
+        let mut data = ReplyBuffer::new();
+
+        for op in ops {
+            if let Some(store_data) = NotifyKind::from(op).encode_into(&mut data) {
+                data.extend_from_slice(store_data);
             }
+        }
 
-            NotifyKind::InvalidInode { inode, offset, len } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_INODE_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_NOTIFY_INVAL_INODE as i32,
-                    unique: 0,
-                };
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.sender
+            .clone()
+            .send(Response::NotifyBatch(data))
+            .await
+            .map_err(|_| not_connected())
+    }
+
+    /// try to retrieve data in an inode from the kernel cache.
+    pub async fn retrieve(
+        mut self,
+        notify_unique: u64,
+        inode: u64,
+        offset: u64,
+        size: u32,
+    ) -> io::Result<()> {
+        self.send(NotifyKind::Retrieve {
+            notify_unique,
+            inode,
+            offset,
+            size,
+        })
+        .await
+    }
+}
+
+fn not_connected() -> io::Error {
+    debug!("fuse connection is closed, drop the notification");
+
+    io::Error::new(io::ErrorKind::NotConnected, "fuse connection is closed")
+}
+
+/// a notification which can be sent by [`Notify::batch`], it is the same as calling the notify
+/// method which has the same name.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NotifyOp {
+    /// notify the cache invalidation about an inode, like [`Notify::invalid_inode`].
+    InvalidInode { inode: u64, offset: i64, len: i64 },
+
+    /// notify the invalidation about a directory entry, like [`Notify::invalid_entry`].
+    InvalidEntry { parent: u64, name: OsString },
+
+    /// notify a directory entry has been deleted, like [`Notify::delete`].
+    Delete {
+        parent: u64,
+        child: u64,
+        name: OsString,
+    },
+
+    /// push the data in an inode for updating the kernel cache, like [`Notify::store`].
+    Store {
+        inode: u64,
+        offset: u64,
+        data: Bytes,
+    },
+}
+
+impl From<NotifyOp> for NotifyKind {
+    fn from(op: NotifyOp) -> Self {
+        match op {
+            NotifyOp::InvalidInode { inode, offset, len } => {
+                NotifyKind::InvalidInode { inode, offset, len }
+            }
+
+            NotifyOp::InvalidEntry { parent, name } => NotifyKind::InvalidEntry { parent, name },
+
+            NotifyOp::Delete {
+                parent,
+                child,
+                name,
+            } => NotifyKind::Delete {
+                parent,
+                child,
+                name,
+            },
+
+            NotifyOp::Store {
+                inode,
+                offset,
+                data,
+            } => NotifyKind::Store {
+                inode,
+                offset,
+                data,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+/// the kind of notify.
+enum NotifyKind {
+    /// notify the IO is ready.
+    Wakeup { kh: u64 },
+
+    // TODO need check is right or not
+    /// notify the cache invalidation about an inode.
+    InvalidInode { inode: u64, offset: i64, len: i64 },
+
+    /// notify the invalidation about a directory entry.
+    InvalidEntry { parent: u64, name: OsString },
+
+    /// notify a directory entry has been deleted.
+    Delete {
+        parent: u64,
+        child: u64,
+        name: OsString,
+    },
+
+    /// push the data in an inode for updating the kernel cache.
+    Store {
+        inode: u64,
+        offset: u64,
+        data: Bytes,
+    },
 
+    /// retrieve data in an inode from the kernel cache.
+    Retrieve {
+        notify_unique: u64,
+        inode: u64,
+        offset: u64,
+        size: u32,
+    },
+}
+
+impl NotifyKind {
+    /// encode the notification to the data which is written to the fuse connection.
+    fn encode(&self) -> FuseData {
+        let mut data = ReplyBuffer::new();
+
+        match self.encode_into(&mut data) {
+            None => Either::Left(data),
+            Some(store_data) => Either::Right((data, store_data.clone())),
+        }
+    }
+
+    /// encode the notification to the end of `out`, the data of the store notification isn't
+    /// copied into `out`, it is returned and must be written right after `out`.
+    fn encode_into(&self, out: &mut ReplyBuffer) -> Option<&Bytes> {
+        match self {
+            NotifyKind::Wakeup { kh } => {
+                let wakeup_out = fuse_notify_poll_wakeup_out { kh: *kh };
+
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_POLL as i32,
+                    FUSE_NOTIFY_POLL_WAKEUP_OUT_SIZE,
+                );
+                protocol::encode_struct(out, &wakeup_out);
+
+                None
+            }
+
+            NotifyKind::InvalidInode { inode, offset, len } => {
                 let invalid_inode_out = fuse_notify_inval_inode_out {
                     ino: *inode,
                     off: *offset,
                     len: *len,
                 };
 
-                let mut data =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_INODE_OUT_SIZE);
-
-                get_bincode_config()
-                    .serialize_into(&mut data, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data, &invalid_inode_out)
-                    .expect("vec size is not enough");
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_NOTIFY_INVAL_INODE as i32,
+                    FUSE_NOTIFY_INVAL_INODE_OUT_SIZE,
+                );
+                protocol::encode_struct(out, &invalid_inode_out);
 
-                Either::Left(data)
+                None
             }
 
             NotifyKind::InvalidEntry { parent, name } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_NOTIFY_INVAL_ENTRY as i32,
-                    unique: 0,
-                };
-
                 let invalid_entry_out = fuse_notify_inval_entry_out {
                     parent: *parent,
                     namelen: name.len() as _,
                     _padding: 0,
                 };
 
-                let mut data =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE);
-
-                get_bincode_config()
-                    .serialize_into(&mut data, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data, &invalid_entry_out)
-                    .expect("vec size is not enough");
-
-                // TODO should I add null at the end?
-
-                Either::Right((data, Bytes::copy_from_slice(name.as_bytes())))
+                // kernel requires the name is terminated by null
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_NOTIFY_INVAL_ENTRY as i32,
+                    FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE + name.len() + 1,
+                );
+                protocol::encode_struct(out, &invalid_entry_out);
+                out.extend_from_slice(name.as_bytes());
+                out.push(0);
+
+                None
             }
 
             NotifyKind::Delete {
@@ -116,12 +304,6 @@ impl Notify {
                 child,
                 name,
             } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_DELETE_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_NOTIFY_DELETE as i32,
-                    unique: 0,
-                };
-
                 let delete_out = fuse_notify_delete_out {
                     parent: *parent,
                     child: *child,
@@ -129,19 +311,18 @@ impl Notify {
                     _padding: 0,
                 };
 
-                let mut data =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_DELETE_OUT_SIZE);
-
-                get_bincode_config()
-                    .serialize_into(&mut data, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data, &delete_out)
-                    .expect("vec size is not enough");
-
-                // TODO should I add null at the end?
-
-                Either::Right((data, Bytes::copy_from_slice(name.as_bytes())))
+                // kernel requires the name is terminated by null
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_NOTIFY_DELETE as i32,
+                    FUSE_NOTIFY_DELETE_OUT_SIZE + name.len() + 1,
+                );
+                protocol::encode_struct(out, &delete_out);
+                out.extend_from_slice(name.as_bytes());
+                out.push(0);
+
+                None
             }
 
             NotifyKind::Store {
@@ -149,12 +330,6 @@ impl Notify {
                 offset,
                 data,
             } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_STORE_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_NOTIFY_STORE as i32,
-                    unique: 0,
-                };
-
                 let store_out = fuse_notify_store_out {
                     nodeid: *inode,
                     offset: *offset,
@@ -162,17 +337,15 @@ impl Notify {
                     _padding: 0,
                 };
 
-                let mut data_buf =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_STORE_OUT_SIZE);
-
-                get_bincode_config()
-                    .serialize_into(&mut data_buf, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data_buf, &store_out)
-                    .expect("vec size is not enough");
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_NOTIFY_STORE as i32,
+                    FUSE_NOTIFY_STORE_OUT_SIZE + data.len(),
+                );
+                protocol::encode_struct(out, &store_out);
 
-                Either::Right((data_buf, data.clone()))
+                Some(data)
             }
 
             NotifyKind::Retrieve {
@@ -181,12 +354,6 @@ impl Notify {
                 offset,
                 size,
             } => {
-                let out_header = fuse_out_header {
-                    len: (FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_RETRIEVE_OUT_SIZE) as u32,
-                    error: fuse_notify_code::FUSE_NOTIFY_RETRIEVE as i32,
-                    unique: 0,
-                };
-
                 let retrieve_out = fuse_notify_retrieve_out {
                     notify_unique: *notify_unique,
                     nodeid: *inode,
@@ -195,107 +362,119 @@ impl Notify {
                     _padding: 0,
                 };
 
-                let mut data =
-                    Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_RETRIEVE_OUT_SIZE);
-
-                get_bincode_config()
-                    .serialize_into(&mut data, &out_header)
-                    .expect("vec size is not enough");
-                get_bincode_config()
-                    .serialize_into(&mut data, &retrieve_out)
-                    .expect("vec size is not enough");
+                protocol::encode_out_header(
+                    out,
+                    0,
+                    fuse_notify_code::FUSE_NOTIFY_RETRIEVE as i32,
+                    FUSE_NOTIFY_RETRIEVE_OUT_SIZE,
+                );
+                protocol::encode_struct(out, &retrieve_out);
 
-                Either::Left(data)
+                None
             }
-        };
-
-        self.sender.send(data).await.or(Err(kind))
+        }
     }
+}
 
-    /// try to notify kernel the IO is ready, kernel can wakeup the waiting program.
-    pub async fn wakeup(mut self, kh: u64) {
-        let _ = self.notify(NotifyKind::Wakeup { kh }).await;
-    }
+#[cfg(test)]
+mod tests {
+    use futures_util::{FutureExt, StreamExt};
 
-    /// try to notify the cache invalidation about an inode.
-    pub async fn invalid_inode(mut self, inode: u64, offset: i64, len: i64) {
-        let _ = self
-            .notify(NotifyKind::InvalidInode { inode, offset, len })
-            .await;
-    }
+    use super::*;
+    use crate::raw::abi::FUSE_OUT_HEADER_SIZE;
+    use crate::raw::ResponseReceiver;
 
-    /// try to notify the invalidation about a directory entry.
-    pub async fn invalid_entry(mut self, parent: u64, name: OsString) {
-        let _ = self.notify(NotifyKind::InvalidEntry { parent, name }).await;
+    const ENTRIES: usize = 1000;
+
+    /// the channel is large enough to queue all the notifications without a reply task.
+    fn channel() -> (ResponseSender, ResponseReceiver) {
+        #[cfg(not(feature = "small-footprint"))]
+        let channel = futures_channel::mpsc::unbounded();
+
+        #[cfg(feature = "small-footprint")]
+        let channel = futures_channel::mpsc::channel(ENTRIES * 2);
+
+        channel
     }
 
-    /// try to notify a directory entry has been deleted.
-    pub async fn delete(mut self, parent: u64, child: u64, name: OsString) {
-        let _ = self
-            .notify(NotifyKind::Delete {
-                parent,
-                child,
-                name,
-            })
-            .await;
+    fn invalid_entries() -> impl Iterator<Item = NotifyOp> {
+        (0..ENTRIES).map(|i| NotifyOp::InvalidEntry {
+            parent: 1,
+            name: format!("entry-{i}").into(),
+        })
     }
 
-    /// try to push the data in an inode for updating the kernel cache.
-    pub async fn store(mut self, inode: u64, offset: u64, mut data: impl Buf) {
-        let _ = self
-            .notify(NotifyKind::Store {
-                inode,
-                offset,
-                data: data.copy_to_bytes(data.remaining()),
-            })
-            .await;
+    fn drain(receiver: &mut ResponseReceiver) -> Vec<Response> {
+        let mut messages = vec![];
+        while let Some(Some(message)) = receiver.next().now_or_never() {
+            messages.push(message);
+        }
+
+        messages
     }
 
-    /// try to retrieve data in an inode from the kernel cache.
-    pub async fn retrieve(mut self, notify_unique: u64, inode: u64, offset: u64, size: u32) {
-        let _ = self
-            .notify(NotifyKind::Retrieve {
-                notify_unique,
-                inode,
-                offset,
-                size,
-            })
-            .await;
+    #[test]
+    fn invalid_entry_len() {
+        let Either::Left(data) = NotifyKind::InvalidEntry {
+            parent: 1,
+            name: "name".into(),
+        }
+        .encode() else {
+            panic!("invalid entry has no extend data");
+        };
+
+        let len = FUSE_OUT_HEADER_SIZE + FUSE_NOTIFY_INVAL_ENTRY_OUT_SIZE + 5;
+        assert_eq!(data.len(), len);
+        assert_eq!(protocol::decode_out_header_len(&data), Some(len));
+        assert!(data.ends_with(b"name\0"));
     }
-}
 
-#[derive(Debug)]
-/// the kind of notify.
-enum NotifyKind {
-    /// notify the IO is ready.
-    Wakeup { kh: u64 },
+    #[test]
+    fn store_len() {
+        let Either::Right((data, store_data)) = NotifyKind::Store {
+            inode: 2,
+            offset: 0,
+            data: Bytes::from_static(b"data"),
+        }
+        .encode() else {
+            panic!("store data isn't copied");
+        };
 
-    // TODO need check is right or not
-    /// notify the cache invalidation about an inode.
-    InvalidInode { inode: u64, offset: i64, len: i64 },
+        assert_eq!(store_data, "data");
+        assert_eq!(
+            protocol::decode_out_header_len(&data),
+            Some(data.len() + store_data.len())
+        );
+    }
 
-    /// notify the invalidation about a directory entry.
-    InvalidEntry { parent: u64, name: OsString },
+    #[test]
+    fn batch_is_one_message() {
+        let (sender, mut receiver) = channel();
+        let notify = Notify::new(sender);
 
-    /// notify a directory entry has been deleted.
-    Delete {
-        parent: u64,
-        child: u64,
-        name: OsString,
-    },
+        notify
+            .batch(invalid_entries())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
 
-    /// push the data in an inode for updating the kernel cache.
-    Store {
-        inode: u64,
-        offset: u64,
-        data: Bytes,
-    },
+        let messages = drain(&mut receiver);
+        assert_eq!(messages.len(), 1);
 
-    /// retrieve data in an inode from the kernel cache.
-    Retrieve {
-        notify_unique: u64,
-        inode: u64,
-        offset: u64,
-        size: u32,
-    },
+        let Response::NotifyBatch(data) = &messages[0] else {
+            panic!("batch is not sent as a notify batch");
+        };
+        let mut data = &data[..];
+
+        // the notifications are one after another, same as they are sent one by one
+        for op in invalid_entries() {
+            let Either::Left(expect) = NotifyKind::from(op).encode() else {
+                unreachable!()
+            };
+
+            assert_eq!(&data[..expect.len()], &expect[..]);
+            data = &data[expect.len()..];
+        }
+        assert!(data.is_empty());
+    }
 }
@@ -1,28 +1,72 @@
-use slab::Slab;
+use std::collections::BTreeSet;
+use std::ops::Range;
 
 use crate::Inode;
 
 #[derive(Debug)]
 pub struct InodeGenerator {
-    slab: Slab<()>,
+    // the inodes which are never allocated start from it
+    next_inode: Inode,
+    released: BTreeSet<Inode>,
 }
 
 impl InodeGenerator {
     pub fn new() -> Self {
-        let mut slab = Slab::new();
-        // drop 0 key
-        slab.insert(());
-
-        Self { slab }
+        Self {
+            // drop 0 inode
+            next_inode: 1,
+            released: BTreeSet::new(),
+        }
     }
 
     pub fn allocate_inode(&mut self) -> Inode {
-        self.slab.insert(()) as _
+        if let Some(inode) = self.released.pop_first() {
+            return inode;
+        }
+
+        let inode = self.next_inode;
+        self.next_inode += 1;
+
+        inode
+    }
+
+    /// allocate `n` contiguous inodes at once, the released inodes are not reused by it so the
+    /// range is always contiguous.
+    pub fn allocate_batch(&mut self, n: u64) -> Range<Inode> {
+        let start = self.next_inode;
+        self.next_inode += n;
+
+        start..self.next_inode
     }
 
     pub fn release_inode(&mut self, inode: Inode) {
-        if self.slab.contains(inode as _) {
-            self.slab.remove(inode as _);
+        if inode != 0 && inode < self.next_inode {
+            self.released.insert(inode);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_batch() {
+        let mut generator = InodeGenerator::new();
+        assert_eq!(generator.allocate_inode(), 1);
+        assert_eq!(generator.allocate_inode(), 2);
+
+        generator.release_inode(1);
+
+        // the released inode is not in the batch, the batch is contiguous
+        assert_eq!(generator.allocate_batch(3), 3..6);
+        assert_eq!(generator.allocate_batch(0), 6..6);
+
+        assert_eq!(generator.allocate_inode(), 1);
+        assert_eq!(generator.allocate_inode(), 6);
+
+        // the inode of a batch can be released and reused
+        generator.release_inode(4);
+        assert_eq!(generator.allocate_inode(), 4);
+    }
+}
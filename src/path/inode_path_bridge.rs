@@ -1,13 +1,13 @@
 use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fmt::{self, Debug, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::vec::IntoIter;
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_lock::RwLock;
 use bytes::Bytes;
-use futures_util::stream::{self, Iter, Stream, StreamExt};
+use futures_util::stream::{self, Iter, Stream, StreamExt, TryStreamExt};
 use slab::Slab;
 #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
 use tokio::sync::RwLock;
@@ -16,12 +16,15 @@ use super::inode_generator::InodeGenerator;
 use super::path_filesystem::PathFilesystem;
 use crate::helper::Apply;
 use crate::notify::Notify;
+use crate::raw::flags::{GetattrFlags, ReleaseFlags, RenameFlags};
 use crate::raw::reply::*;
-use crate::raw::{Filesystem, Request};
-use crate::{Errno, SetAttr};
-use crate::{Inode, Result};
-
-const ROOT_INODE: Inode = 1;
+use crate::raw::{Filesystem, FsCapabilities, Request};
+#[cfg(feature = "file-lock")]
+use crate::FileLock;
+#[cfg(feature = "dax")]
+use crate::RemoveMapping;
+use crate::{Errno, SecurityContext, SetAttr};
+use crate::{Inode, Result, FUSE_ROOT_ID};
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 struct Name {
@@ -47,7 +50,7 @@ impl InodeNameManager {
         let names = self.inode_to_names.get(&inode)?;
         let name = names.iter().next().unwrap();
 
-        if name.parent == ROOT_INODE {
+        if name.parent == FUSE_ROOT_ID {
             Some(PathBuf::from("/").apply(|path| path.push(&name.name)))
         } else {
             Some(
@@ -87,14 +90,20 @@ impl InodeNameManager {
     fn insert_name(&mut self, name: Name) -> Inode {
         let inode = self.inode_generator.allocate_inode();
 
+        self.insert_name_with_inode(name, inode);
+
+        inode
+    }
+
+    /// insert the name with the inode which is allocated already, like the inode allocated by
+    /// [`InodeGenerator::allocate_batch`].
+    fn insert_name_with_inode(&mut self, name: Name, inode: Inode) {
         self.name_to_inode.insert(name.clone(), inode);
 
         let mut names = HashSet::with_capacity(1);
         names.insert(name);
 
         self.inode_to_names.insert(inode, names);
-
-        inode
     }
 
     fn get_name_inode(&self, name: &Name) -> Option<Inode> {
@@ -121,7 +130,7 @@ impl<FS> InodePathBridge<FS> {
 
         let root_inode = inode_name_manager.inode_generator.allocate_inode();
 
-        assert_eq!(root_inode, ROOT_INODE);
+        assert_eq!(root_inode, FUSE_ROOT_ID);
 
         // root parent is itself
         inode_name_manager.inode_to_names.insert(
@@ -158,6 +167,10 @@ where
         self.path_filesystem.destroy(req).await
     }
 
+    fn capabilities(&self) -> FsCapabilities {
+        self.path_filesystem.capabilities()
+    }
+
     async fn lookup(&self, req: Request, parent: u64, name: &OsStr) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
 
@@ -186,7 +199,8 @@ where
                     .unwrap_or_else(|| inode_name_manager.insert_name(name));
 
                 Ok(ReplyEntry {
-                    ttl: entry.ttl,
+                    entry_ttl: entry.entry_ttl,
+                    attr_ttl: entry.attr_ttl,
                     attr: (inode, entry.attr).into(),
                     generation: 0,
                 })
@@ -219,7 +233,7 @@ where
         req: Request,
         inode: u64,
         fh: Option<u64>,
-        flags: u32,
+        flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager.get_absolute_path(inode);
@@ -271,6 +285,7 @@ where
         parent: u64,
         name: &OsStr,
         link: &OsStr,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
@@ -279,7 +294,7 @@ where
 
         match self
             .path_filesystem
-            .symlink(req, parent_path.as_ref(), name, link)
+            .symlink(req, parent_path.as_ref(), name, link, security_context)
             .await
         {
             Err(err) => {
@@ -299,7 +314,8 @@ where
                     .unwrap_or_else(|| inode_name_manager.insert_name(name));
 
                 Ok(ReplyEntry {
-                    ttl: entry.ttl,
+                    entry_ttl: entry.entry_ttl,
+                    attr_ttl: entry.attr_ttl,
                     attr: (inode, entry.attr).into(),
                     generation: 0,
                 })
@@ -314,6 +330,7 @@ where
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
@@ -322,7 +339,14 @@ where
 
         match self
             .path_filesystem
-            .mknod(req, parent_path.as_ref(), name, mode, rdev)
+            .mknod(
+                req,
+                parent_path.as_ref(),
+                name,
+                mode,
+                rdev,
+                security_context,
+            )
             .await
         {
             Err(err) => {
@@ -342,7 +366,8 @@ where
                     .unwrap_or_else(|| inode_name_manager.insert_name(name));
 
                 Ok(ReplyEntry {
-                    ttl: entry.ttl,
+                    entry_ttl: entry.entry_ttl,
+                    attr_ttl: entry.attr_ttl,
                     attr: (inode, entry.attr).into(),
                     generation: 0,
                 })
@@ -357,6 +382,7 @@ where
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
@@ -365,7 +391,14 @@ where
 
         match self
             .path_filesystem
-            .mkdir(req, parent_path.as_ref(), name, mode, umask)
+            .mkdir(
+                req,
+                parent_path.as_ref(),
+                name,
+                mode,
+                umask,
+                security_context,
+            )
             .await
         {
             Err(err) => {
@@ -385,7 +418,8 @@ where
                     .unwrap_or_else(|| inode_name_manager.insert_name(name));
 
                 Ok(ReplyEntry {
-                    ttl: entry.ttl,
+                    entry_ttl: entry.entry_ttl,
+                    attr_ttl: entry.attr_ttl,
                     attr: (inode, entry.attr).into(),
                     generation: 0,
                 })
@@ -470,6 +504,8 @@ where
             .get_absolute_path(new_parent)
             .ok_or_else(Errno::new_not_exist)?;
 
+        check_rename_loop(&origin_parent_path, name, &new_parent_path, new_name, false)?;
+
         // here is very complex so don't modify the inode_name_manager when error
         self.path_filesystem
             .rename(
@@ -525,7 +561,8 @@ where
             .unwrap_or_else(|| inode_name_manager.insert_name(name));
 
         Ok(ReplyEntry {
-            ttl: entry.ttl,
+            entry_ttl: entry.entry_ttl,
+            attr_ttl: entry.attr_ttl,
             attr: (inode, entry.attr).into(),
             generation: 0,
         })
@@ -594,6 +631,35 @@ where
             .await
     }
 
+    async fn write_bytes(
+        &self,
+        req: Request,
+        inode: u64,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: u32,
+        flags: u32,
+    ) -> Result<ReplyWrite> {
+        let path = self
+            .inode_name_manager
+            .read()
+            .await
+            .get_absolute_path(inode);
+
+        self.path_filesystem
+            .write_bytes(
+                req,
+                path.as_ref().map(|path| path.as_ref()),
+                fh,
+                offset,
+                data,
+                write_flags,
+                flags,
+            )
+            .await
+    }
+
     async fn statfs(&self, req: Request, inode: u64) -> Result<ReplyStatFs> {
         let inode_name_manager = self.inode_name_manager.read().await;
         let path = inode_name_manager
@@ -610,7 +676,7 @@ where
         fh: u64,
         flags: u32,
         lock_owner: u64,
-        flush: bool,
+        release_flags: ReleaseFlags,
     ) -> Result<()> {
         let path = self
             .inode_name_manager
@@ -625,7 +691,7 @@ where
                 fh,
                 flags,
                 lock_owner,
-                flush,
+                release_flags,
             )
             .await
     }
@@ -782,6 +848,7 @@ where
         }
 
         Ok(ReplyDirectory {
+            parent: None,
             entries: stream::iter(entry_list),
         })
     }
@@ -808,19 +875,28 @@ where
             .await
     }
 
-    #[allow(clippy::too_many_arguments)]
     #[cfg(feature = "file-lock")]
-    async fn getlk(
+    async fn getlk(&self, req: Request, inode: u64, fh: u64, lock: FileLock) -> Result<ReplyLock> {
+        let path = self
+            .inode_name_manager
+            .read()
+            .await
+            .get_absolute_path(inode);
+
+        self.path_filesystem
+            .getlk(req, path.as_ref().map(|path| path.as_ref()), fh, lock)
+            .await
+    }
+
+    #[cfg(feature = "file-lock")]
+    async fn setlk(
         &self,
         req: Request,
         inode: u64,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
-    ) -> Result<ReplyLock> {
+        lock: FileLock,
+        block: bool,
+    ) -> Result<()> {
         let path = self
             .inode_name_manager
             .read()
@@ -828,31 +904,24 @@ where
             .get_absolute_path(inode);
 
         self.path_filesystem
-            .getlk(
+            .setlk(
                 req,
                 path.as_ref().map(|path| path.as_ref()),
                 fh,
-                lock_owner,
-                start,
-                end,
-                r#type,
-                pid,
+                lock,
+                block,
             )
             .await
     }
 
-    #[allow(clippy::too_many_arguments)]
     #[cfg(feature = "file-lock")]
-    async fn setlk(
+    async fn flock(
         &self,
         req: Request,
         inode: u64,
         fh: u64,
         lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        operation: u32,
         block: bool,
     ) -> Result<()> {
         let path = self
@@ -862,15 +931,12 @@ where
             .get_absolute_path(inode);
 
         self.path_filesystem
-            .setlk(
+            .flock(
                 req,
                 path.as_ref().map(|path| path.as_ref()),
                 fh,
                 lock_owner,
-                start,
-                end,
-                r#type,
-                pid,
+                operation,
                 block,
             )
             .await
@@ -892,6 +958,7 @@ where
         name: &OsStr,
         mode: u32,
         flags: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyCreated> {
         let mut inode_name_manager = self.inode_name_manager.write().await;
         let parent_path = inode_name_manager
@@ -900,7 +967,14 @@ where
 
         match self
             .path_filesystem
-            .create(req, parent_path.as_ref(), name, mode, flags)
+            .create(
+                req,
+                parent_path.as_ref(),
+                name,
+                mode,
+                flags,
+                security_context,
+            )
             .await
         {
             Err(err) => {
@@ -1063,12 +1137,24 @@ where
         let entries = children.entries;
         futures_util::pin_mut!(entries);
 
-        let entries_size = entries.size_hint().1.unwrap_or(0);
-        let mut entry_list = Vec::with_capacity(entries_size);
-
-        while let Some(entry) = entries.next().await {
-            let entry = entry?;
+        let entries = entries.try_collect::<Vec<_>>().await?;
 
+        // allocate the inodes of the new names at once, a large directory may have many
+        let new_name_count = entries
+            .iter()
+            .filter(|entry| {
+                entry.name != OsStr::new(".")
+                    && entry.name != OsStr::new("..")
+                    && !inode_name_manager.contains_name(&Name::new(parent, entry.name.clone()))
+            })
+            .count();
+        let mut new_inodes = inode_name_manager
+            .inode_generator
+            .allocate_batch(new_name_count as _);
+
+        let mut entry_list = Vec::with_capacity(entries.len());
+
+        for entry in entries {
             let inode = if entry.name == OsStr::new(".") {
                 parent
             } else if entry.name == OsStr::new("..") {
@@ -1083,9 +1169,15 @@ where
             } else {
                 let name = Name::new(parent, entry.name.clone());
 
-                inode_name_manager
-                    .get_name_inode(&name)
-                    .unwrap_or_else(|| inode_name_manager.insert_name(name))
+                match inode_name_manager.get_name_inode(&name) {
+                    Some(inode) => inode,
+                    None => {
+                        let inode = new_inodes.next().expect("new name inode is allocated");
+                        inode_name_manager.insert_name_with_inode(name, inode);
+
+                        inode
+                    }
+                }
             };
 
             entry_list.push(Ok(DirectoryEntryPlus {
@@ -1100,7 +1192,13 @@ where
             }));
         }
 
+        // the duplicated names in the entries are counted more than once
+        for inode in new_inodes {
+            inode_name_manager.inode_generator.release_inode(inode);
+        }
+
         Ok(ReplyDirectoryPlus {
+            parent: None,
             entries: stream::iter(entry_list),
         })
     }
@@ -1123,6 +1221,15 @@ where
             .get_absolute_path(new_parent)
             .ok_or_else(Errno::new_not_exist)?;
 
+        let exchange = RenameFlags::from(flags).contains(RenameFlags::EXCHANGE);
+        check_rename_loop(
+            &origin_parent_path,
+            name,
+            &new_parent_path,
+            new_name,
+            exchange,
+        )?;
+
         // here is very complex so don't modify the inode_name_manager when error
         self.path_filesystem
             .rename2(
@@ -1204,4 +1311,131 @@ where
             )
             .await
     }
+
+    #[cfg(feature = "dax")]
+    #[allow(clippy::too_many_arguments)]
+    async fn setup_mapping(
+        &self,
+        req: Request,
+        inode: u64,
+        fh: u64,
+        foffset: u64,
+        len: u64,
+        flags: u64,
+        moffset: u64,
+    ) -> Result<()> {
+        let path = self
+            .inode_name_manager
+            .read()
+            .await
+            .get_absolute_path(inode);
+
+        self.path_filesystem
+            .setup_mapping(
+                req,
+                path.as_ref().map(|path| path.as_ref()),
+                fh,
+                foffset,
+                len,
+                flags,
+                moffset,
+            )
+            .await
+    }
+
+    #[cfg(feature = "dax")]
+    async fn remove_mapping(
+        &self,
+        req: Request,
+        inode: u64,
+        mappings: &[RemoveMapping],
+    ) -> Result<()> {
+        let path = self
+            .inode_name_manager
+            .read()
+            .await
+            .get_absolute_path(inode);
+
+        self.path_filesystem
+            .remove_mapping(req, path.as_ref().map(|path| path.as_ref()), mappings)
+            .await
+    }
+}
+
+/// check the rename doesn't move a directory into its own subtree, which makes a loop in the
+/// paths, when `exchange` is true, the new name is moved to the old place too.
+fn check_rename_loop(
+    origin_parent_path: &Path,
+    name: &OsStr,
+    new_parent_path: &Path,
+    new_name: &OsStr,
+    exchange: bool,
+) -> Result<()> {
+    if new_parent_path.starts_with(origin_parent_path.join(name)) {
+        return Err(libc::EINVAL.into());
+    }
+
+    if exchange && origin_parent_path.starts_with(new_parent_path.join(new_name)) {
+        return Err(libc::EINVAL.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_loop(
+        origin_parent: &str,
+        name: &str,
+        new_parent: &str,
+        new_name: &str,
+        exchange: bool,
+    ) -> Result<()> {
+        check_rename_loop(
+            Path::new(origin_parent),
+            OsStr::new(name),
+            Path::new(new_parent),
+            OsStr::new(new_name),
+            exchange,
+        )
+    }
+
+    #[test]
+    fn rename_into_own_subtree() {
+        let einval = Err(Errno::from(libc::EINVAL));
+
+        assert_eq!(rename_loop("/", "a", "/a", "b", false), einval);
+        assert_eq!(rename_loop("/", "a", "/a/b/c", "d", false), einval);
+        assert_eq!(rename_loop("/x", "a", "/x/a/b", "a", false), einval);
+    }
+
+    #[test]
+    fn rename_to_sibling_or_same_parent() {
+        assert_eq!(rename_loop("/", "a", "/b", "a", false), Ok(()));
+        assert_eq!(rename_loop("/x/a", "b", "/x/c", "b", false), Ok(()));
+        // the paths are compared by components, "/ab" is not in the subtree of "/a"
+        assert_eq!(rename_loop("/", "a", "/ab", "a", false), Ok(()));
+
+        assert_eq!(rename_loop("/", "a", "/", "b", false), Ok(()));
+        assert_eq!(rename_loop("/x", "a", "/x", "a", false), Ok(()));
+        assert_eq!(rename_loop("/x", "a", "/x", "b", true), Ok(()));
+
+        // moving a file to an ancestor is not a loop
+        assert_eq!(rename_loop("/a/b", "c", "/", "c", false), Ok(()));
+    }
+
+    #[test]
+    fn exchange_into_own_subtree() {
+        let einval = Err(Errno::from(libc::EINVAL));
+
+        // the new name is moved into the old place, which is in its own subtree
+        assert_eq!(rename_loop("/a/b", "c", "/", "a", false), Ok(()));
+        assert_eq!(rename_loop("/a/b", "c", "/", "a", true), einval);
+        assert_eq!(rename_loop("/a", "c", "/", "a", true), einval);
+
+        assert_eq!(rename_loop("/", "a", "/a", "b", true), einval);
+        assert_eq!(rename_loop("/a/b", "c", "/", "b", true), Ok(()));
+    }
 }
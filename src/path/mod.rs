@@ -5,7 +5,12 @@
 //! inode or do the path<->inode map on yourself, use [`Filesystem`][crate::raw::Filesystem].
 
 pub use path_filesystem::PathFilesystem;
-pub use session::Session;
+#[cfg(all(
+    any(feature = "async-io-runtime", feature = "tokio-runtime"),
+    feature = "unprivileged"
+))]
+pub use session::mount_with_unprivileged;
+pub use session::{mount, Session};
 
 pub use crate::raw::Request;
 
@@ -22,6 +27,13 @@ pub mod prelude {
     pub use super::Request;
     pub use super::Session;
     pub use crate::notify::Notify;
+    pub use crate::raw::flags::GetattrFlags;
+    pub use crate::raw::flags::ReleaseFlags;
+    pub use crate::raw::FsCapabilities;
     pub use crate::FileType;
+    pub use crate::SecurityContext;
     pub use crate::SetAttr;
+    pub use crate::FUSE_ROOT_ID;
+    #[cfg(feature = "file-lock")]
+    pub use crate::{FileLock, LockKind};
 }
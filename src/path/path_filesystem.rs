@@ -6,11 +6,22 @@ use futures_util::stream::Stream;
 use super::reply::*;
 use super::Request;
 use crate::notify::Notify;
-use crate::{Result, SetAttr};
+use crate::raw::flags::{GetattrFlags, ReleaseFlags};
+use crate::raw::FsCapabilities;
+#[cfg(feature = "file-lock")]
+use crate::FileLock;
+#[cfg(feature = "dax")]
+use crate::RemoveMapping;
+use crate::{Result, SecurityContext, SetAttr};
 
 #[allow(unused_variables)]
 #[trait_make::make(Send)]
 /// Path based filesystem trait.
+///
+/// # Notes:
+///
+/// the paths and names are the raw bytes from kernel, they may be not valid UTF-8, don't convert
+/// them with the lossy conversion such as `to_string_lossy`, or the names can't be found again.
 pub trait PathFilesystem {
     /// initialize filesystem. Called before any other filesystem method.
     async fn init(&self, req: Request) -> Result<ReplyInit>;
@@ -21,7 +32,24 @@ pub trait PathFilesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn destroy(&self, req: Request);
 
+    /// get the operations the filesystem supports, it is called once after the filesystem is
+    /// initialized and cached by the session, default is [`FsCapabilities::all`].
+    ///
+    /// # Notes:
+    ///
+    /// the unsupported requests are replied with `ENOSYS` directly, the filesystem won't see
+    /// them. The operations are the fuse opcodes of the requests, see
+    /// [`Filesystem::capabilities`][crate::raw::Filesystem::capabilities].
+    fn capabilities(&self) -> FsCapabilities {
+        FsCapabilities::all()
+    }
+
     /// look up a directory entry by name and get its attributes.
+    ///
+    /// # Notes:
+    ///
+    /// the path filesystem can't be exported by NFS, the path of an inode is dropped when the
+    /// inode is forgotten, so an inode in the NFS file handle can't be resolved anymore.
     async fn lookup(&self, req: Request, parent: &OsStr, name: &OsStr) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -38,20 +66,35 @@ pub trait PathFilesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn forget(&self, req: Request, parent: &OsStr, nlookup: u64) {}
 
-    /// get file attributes. If `fh` is None, means `fh` is not set. If `path` is None, means the
-    /// path may be deleted.
+    /// get file attributes. If `fh` is None, means `fh` is not set, see [`GetattrFlags::has_fh`].
+    /// If `path` is None, means the path may be deleted.
     async fn getattr(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: Option<u64>,
-        flags: u32,
+        flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
         Err(libc::ENOSYS.into())
     }
 
     /// set file attributes. If `fh` is None, means `fh` is not set. If `path` is None, means the
     /// path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is set, the kernel holds the inode lock while waiting the reply, so the
+    /// truncate is serialized with the writes of the same inode from this mount. With
+    /// writeback cache disabled no write of the inode is in flight at the same time; with
+    /// [`write_back`][crate::MountOptions::write_back] enabled, the kernel flushes and waits the
+    /// cached writes before sending the truncate. The filesystem still needs its own lock to keep
+    /// the truncate and the reply attr atomic, because every request is handled concurrently,
+    /// and the content may be changed by other ways, like another mount or the backend storage.
+    ///
+    /// the reply attr is the whole attributes after the change, not only the changed fields,
+    /// kernel replaces its cached attributes with it. As POSIX requires, any change should also
+    /// bump the `ctime` to the current time when `ctime` isn't set, even only `atime` or `mtime`
+    /// is changed.
     async fn setattr(
         &self,
         req: Request,
@@ -63,24 +106,32 @@ pub trait PathFilesystem {
     }
 
     /// read symbolic link.
+    ///
+    /// # Notes:
+    ///
+    /// if the path is not a symbolic link, the filesystem should return `EINVAL`, which is what
+    /// `readlink(2)` reports to the caller in that case.
     async fn readlink(&self, req: Request, path: &OsStr) -> Result<ReplyData> {
         Err(libc::ENOSYS.into())
     }
 
-    /// create a symbolic link.
+    /// create a symbolic link. `security_context` is the security context of the creating process,
+    /// see [`SecurityContext`].
     async fn symlink(
         &self,
         req: Request,
         parent: &OsStr,
         name: &OsStr,
         link_path: &OsStr,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
     /// create file node. Create a regular file, character device, block device, fifo or socket
     /// node. When creating file, most cases user only need to implement
-    /// [`create`][PathFilesystem::create].
+    /// [`create`][PathFilesystem::create]. `security_context` is the security context of the
+    /// creating process, see [`SecurityContext`].
     async fn mknod(
         &self,
         req: Request,
@@ -88,11 +139,13 @@ pub trait PathFilesystem {
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
-    /// create a directory.
+    /// create a directory. `security_context` is the security context of the creating process,
+    /// see [`SecurityContext`].
     async fn mkdir(
         &self,
         req: Request,
@@ -100,6 +153,7 @@ pub trait PathFilesystem {
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -115,6 +169,11 @@ pub trait PathFilesystem {
     }
 
     /// rename a file or directory.
+    ///
+    /// # Notes:
+    ///
+    /// the `.` and `..` names are rejected with `EINVAL` before calling it, so is renaming a
+    /// directory into its own subtree.
     async fn rename(
         &self,
         req: Request,
@@ -151,6 +210,10 @@ pub trait PathFilesystem {
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
+    ///
+    /// when the file is a stream which isn't ready yet, such as a fifo like device, and `flags`
+    /// has `O_NONBLOCK`, return `EAGAIN` by `Errno::from(libc::EAGAIN)`, the `open(2)` fails with
+    /// it and the caller can retry later, the blocking open can wait the stream ready instead.
     async fn open(&self, req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
@@ -161,6 +224,11 @@ pub trait PathFilesystem {
     /// read system call will reflect the return value of this operation. `fh` will contain the
     /// value set by the open method, or will be undefined if the open method didn't set any value.
     /// when `path` is None, it means the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// the data longer than `size` bytes will be truncated, so don't read more than `size` bytes
+    /// from the backend, [`ReplyData::with_exact_len`] can help to check it.
     async fn read(
         &self,
         req: Request,
@@ -172,13 +240,24 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// write data. Write should return exactly the number of bytes requested except on error. An
-    /// exception to this is when the file has been opened in `direct_io` mode, in which case the
-    /// return value of the write system call will reflect the return value of this operation. `fh`
-    /// will contain the value set by the open method, or will be undefined if the open method
-    /// didn't set any value. When `path` is None, it means the path may be deleted. When
-    /// `write_flags` contains [`FUSE_WRITE_CACHE`](crate::raw::flags::FUSE_WRITE_CACHE), means the
-    /// write operation is a delay write.
+    /// write data. Write may return less than the number of bytes requested, like a filesystem
+    /// which is short of quota, the `write(2)` returns the written count and the caller retries
+    /// the rest, see [`ReplyWrite::short`]. `fh` will contain the value set by the open method,
+    /// or will be undefined if the open method didn't set any value. When `path` is None, it means
+    /// the path may be deleted. When `write_flags` contains
+    /// [`FUSE_WRITE_CACHE`](crate::raw::flags::FUSE_WRITE_CACHE), means the write operation is a
+    /// delay write.
+    ///
+    /// # Notes:
+    ///
+    /// the written count must not be greater than the length of `data`, or kernel fails the
+    /// write with `EIO`.
+    ///
+    /// when the file is opened with `O_APPEND`, the data must be written at the end of the file
+    /// whatever the `offset` is, kernel sends the end of the file it knows which may be stale
+    /// when the file is changed by others, so the filesystem should record the open flags
+    /// against the `fh` and append. With the writeback cache, kernel handles `O_APPEND` itself
+    /// and the filesystem should ignore it.
     #[allow(clippy::too_many_arguments)]
     async fn write(
         &self,
@@ -193,6 +272,30 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
+    /// write data which is owned by the filesystem, the session calls it for the write requests,
+    /// the default implementation is calling [`write`][PathFilesystem::write].
+    ///
+    /// # Notes:
+    ///
+    /// see [`Filesystem::write_bytes`][crate::raw::Filesystem::write_bytes].
+    #[allow(clippy::too_many_arguments)]
+    async fn write_bytes(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: u32,
+        flags: u32,
+    ) -> Result<ReplyWrite>
+    where
+        Self: Sync,
+    {
+        self.write(req, path, fh, offset, &data, write_flags, flags)
+            .await
+    }
+
     /// get filesystem statistics.
     async fn statfs(&self, req: Request, path: &OsStr) -> Result<ReplyStatFs> {
         Err(libc::ENOSYS.into())
@@ -203,8 +306,17 @@ pub trait PathFilesystem {
     /// there will be exactly one release call. The filesystem may reply with an error, but error
     /// values are not returned to `close()` or `munmap()` which triggered the release. `fh` will
     /// contain the value set by the open method, or will be undefined if the open method didn't
-    /// set any value. `flags` will contain the same flags as for open. `flush` means flush the
-    /// data or not when closing file. when `path` is None, it means the path may be deleted.
+    /// set any value. `flags` will contain the same `O_*` flags as for open, so the filesystem can
+    /// match the release with the open. `release_flags` tells to flush the data or not when
+    /// closing file, and to unlock the `flock(2)` locks of the `lock_owner` or not, see
+    /// [`ReleaseFlags`]. when `path` is None, it means the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// [`flush`][PathFilesystem::flush] may be called many times for one open file, once for each
+    /// `close()` of a duplicated file descriptor, but release is called only once when the last
+    /// reference is gone. So if the filesystem wants to persist data when the file is closed, it
+    /// should do it in release, not in flush.
     async fn release(
         &self,
         req: Request,
@@ -212,7 +324,7 @@ pub trait PathFilesystem {
         fh: u64,
         flags: u32,
         lock_owner: u64,
-        flush: bool,
+        release_flags: ReleaseFlags,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -244,6 +356,12 @@ pub trait PathFilesystem {
 
     /// get an extended attribute. If size is too small, use [`ReplyXAttr::Size`] to return correct
     /// size. If size is enough, use [`ReplyXAttr::Data`] to send it, or return error.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is 0, the kernel asks the size of the value, reply [`ReplyXAttr::Size`] or
+    /// [`ReplyXAttr::Data`], only the size is sent. Otherwise the value larger than `size` is
+    /// replied `ERANGE` instead of being sent to the kernel.
     async fn getxattr(
         &self,
         req: Request,
@@ -256,6 +374,13 @@ pub trait PathFilesystem {
 
     /// list extended attribute names. If size is too small, use [`ReplyXAttr::Size`] to return
     /// correct size. If size is enough, use [`ReplyXAttr::Data`] to send it, or return error.
+    /// [`ReplyXAttr::names`] builds the null terminated list of the names.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is 0, the kernel asks the size of the list, reply [`ReplyXAttr::Size`] or
+    /// [`ReplyXAttr::Data`], only the size is sent. Otherwise the list larger than `size` is
+    /// replied `ERANGE` instead of being sent to the kernel.
     async fn listxattr(&self, req: Request, path: &OsStr, size: u32) -> Result<ReplyXAttr> {
         Err(libc::ENOSYS.into())
     }
@@ -296,6 +421,17 @@ pub trait PathFilesystem {
     /// I/O and not store anything in `fh`.  A file system need not implement this method if it
     /// sets [`MountOptions::no_open_dir_support`][crate::MountOptions::no_open_dir_support] and if
     /// the kernel supports `FUSE_NO_OPENDIR_SUPPORT`.
+    ///
+    /// # Notes:
+    ///
+    /// reply [`FOPEN_CACHE_DIR`][crate::raw::flags::FOPEN_CACHE_DIR] to let kernel cache the
+    /// directory contents, the later [`readdir`][PathFilesystem::readdir] and
+    /// [`readdirplus`][PathFilesystem::readdirplus] of the directory may be served from the cache
+    /// without calling the filesystem, the entry offsets must be stable. Kernel drops the cache
+    /// when the directory is changed through the mount or its mtime is changed, the filesystem
+    /// must invalidate it with [`Notify::invalid_inode`][crate::notify::Notify::invalid_inode]
+    /// on the directory when it is changed behind kernel. The cache is kept across opendir only
+    /// when the reply also has [`FOPEN_KEEP_CACHE`][crate::raw::flags::FOPEN_KEEP_CACHE].
     async fn opendir(&self, req: Request, path: &OsStr, flags: u32) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
@@ -308,6 +444,12 @@ pub trait PathFilesystem {
     /// read directory. `offset` is used to track the offset of the directory entries. `fh` will
     /// contain the value set by the [`opendir`][PathFilesystem::opendir] method, or will be
     /// undefined if the [`opendir`][PathFilesystem::opendir] method didn't set any value.
+    ///
+    /// # Notes:
+    ///
+    /// if `readdir` is not implemented, the session will fallback to
+    /// [`readdirplus`][PathFilesystem::readdirplus] and discard the attrs, so implementing
+    /// `readdirplus` only is enough, but it costs the attrs generation of every entry.
     async fn readdir<'a>(
         &'a self,
         req: Request,
@@ -335,44 +477,70 @@ pub trait PathFilesystem {
     }
 
     #[cfg(feature = "file-lock")]
-    /// test for a POSIX file lock.
+    /// test for a POSIX file lock, reply the lock which conflicts with `lock`, or reply `lock`
+    /// with [`LockKind::Unlock`][crate::LockKind::Unlock] if no lock conflicts.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
     async fn getlk(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        lock: FileLock,
     ) -> Result<ReplyLock>;
 
     #[cfg(feature = "file-lock")]
-    /// acquire, modify or release a POSIX file lock.
+    /// acquire, modify or release a POSIX file lock. When `block` is true, it is a `SETLKW`
+    /// request and the filesystem should wait until the lock is acquired.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
+    ///
+    /// the session never drops a running request future, when the waiting process is
+    /// interrupted, the kernel sends an [`interrupt`][PathFilesystem::interrupt] with the
+    /// `req.unique` of the `SETLKW` request instead. A filesystem which waits for the lock should
+    /// record the waiter by `req.unique` in its lock queue, and when the interrupt arrives, remove
+    /// the waiter from the queue and make this method return `EINTR`. Otherwise the waiter stays
+    /// in the queue and may acquire the lock after the process has gone.
     async fn setlk(
         &self,
         req: Request,
         path: Option<&OsStr>,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        lock: FileLock,
         block: bool,
     ) -> Result<()>;
 
+    #[cfg(feature = "file-lock")]
+    /// acquire or release a BSD file lock, which is used by `flock(2)`. `operation` is one of
+    /// `libc::LOCK_SH`, `libc::LOCK_EX` and `libc::LOCK_UN`, when `block` is false, the
+    /// filesystem should return `EAGAIN` instead of waiting if the lock is held by others.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature and
+    /// [`MountOptions::flock`][crate::MountOptions::flock] is enabled, otherwise the kernel
+    /// handles `flock` locally.
+    ///
+    /// the lock belongs to the open file description, which is identified by `lock_owner`.
+    /// When the last reference of the open file description is closed,
+    /// [`release`][PathFilesystem::release] is called with the same `lock_owner` and
+    /// [`ReleaseFlags::flock_unlock`] set, the filesystem should release the lock held by it.
+    async fn flock(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        lock_owner: u64,
+        operation: u32,
+        block: bool,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
     /// check file access permissions. This will be called for the `access()` system call. If the
     /// `default_permissions` mount option is given, this method is not be called. This method is
     /// not called under Linux kernel versions 2.4.x.
@@ -395,7 +563,13 @@ pub trait PathFilesystem {
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
-    /// more details.
+    /// more details. `security_context` is the security context of the creating process, see
+    /// [`SecurityContext`].
+    ///
+    /// `flags` always has `O_CREAT`, kernel calls create after the lookup found nothing, but the
+    /// file may be created by others between them. When the file exists and `flags` has
+    /// `O_EXCL`, return `EEXIST`, otherwise open the existing file like `open(2)` does, such as
+    /// truncating it when `flags` has `O_TRUNC`, the `mode` is ignored.
     async fn create(
         &self,
         req: Request,
@@ -403,21 +577,32 @@ pub trait PathFilesystem {
         name: &OsStr,
         mode: u32,
         flags: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyCreated> {
         Err(libc::ENOSYS.into())
     }
 
     /// handle interrupt. When a operation is interrupted, an interrupt request will send to fuse
     /// server with the unique id of the operation.
+    ///
+    /// # Notes:
+    ///
+    /// the interrupted operation is not cancelled by the session, it is still running and must
+    /// still be replied, usually with `EINTR`. If the operation has been replied or isn't known
+    /// yet, the interrupt can be ignored.
     async fn interrupt(&self, req: Request, unique: u64) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// map block index within file to block index within device.
+    /// map block index within file to block index within device, it is used by the `FIBMAP`
+    /// ioctl. A filesystem which stores the files in a block device can implement it by
+    /// returning the device block of the file block `idx`.
     ///
     /// # Notes:
     ///
-    /// This may not works because currently this crate doesn't support fuseblk mode yet.
+    /// kernel only sends bmap for the fuseblk mounts, which this crate doesn't support yet, on
+    /// the other mounts `FIBMAP` gets block 0 without calling it. To report the holes of sparse
+    /// files, implement [`lseek`][PathFilesystem::lseek] with `SEEK_DATA` and `SEEK_HOLE` instead.
     async fn bmap(
         &self,
         req: Request,
@@ -496,6 +681,14 @@ pub trait PathFilesystem {
 
     /// read directory entries, but with their attribute, like [`readdir`][PathFilesystem::readdir]
     /// + [`lookup`][PathFilesystem::lookup] at the same time.
+    ///
+    /// # Notes:
+    ///
+    /// the attributes prime the kernel inode cache, such as `ls -l` won't call
+    /// [`getattr`][PathFilesystem::getattr] for the entries within the
+    /// [`attr_ttl`][DirectoryEntryPlus::attr_ttl]. If the attribute of an entry is uncertain, such
+    /// as a file being written by others, use [`DirectoryEntryPlus::without_attr_cache`] so kernel
+    /// fetches it by getattr.
     async fn readdirplus<'a>(
         &'a self,
         req: Request,
@@ -507,7 +700,24 @@ pub trait PathFilesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory with flags.
+    /// rename a file or directory with flags. The `flags` can be parsed by
+    /// [`RenameFlags`][crate::raw::flags::RenameFlags].
+    ///
+    /// # Notes:
+    ///
+    /// when `flags` has [`RenameFlags::WHITEOUT`][crate::raw::flags::RenameFlags::WHITEOUT], the
+    /// filesystem should create a whiteout object, a character device with device number 0/0, at
+    /// the old name atomically with the rename. It is used by overlay filesystems to hide the
+    /// lower entry.
+    ///
+    /// a filesystem must reply `EINVAL` for the flags it doesn't support, rather than ignore them
+    /// and do a plain rename, which breaks the atomicity the caller relies on;
+    /// [`RenameFlags::unsupported`][crate::raw::flags::RenameFlags::unsupported] helps to check
+    /// it. If the default `ENOSYS` is returned, kernel won't send rename2 anymore and all the
+    /// `renameat2(2)` with flags fail with `EINVAL`.
+    ///
+    /// the `.` and `..` names and the subtree loops are rejected before calling it, the same as
+    /// [`rename`][PathFilesystem::rename].
     async fn rename2(
         &self,
         req: Request,
@@ -521,6 +731,15 @@ pub trait PathFilesystem {
     }
 
     /// find next data or hole after the specified offset.
+    ///
+    /// # Notes:
+    ///
+    /// kernel only sends lseek with `SEEK_DATA` and `SEEK_HOLE`, other `whence` are handled by
+    /// kernel. If the default `ENOSYS` is returned, kernel won't send lseek anymore and fallback to
+    /// its generic implementation. For a non-seekable file, such as a stream, set
+    /// [`FOPEN_NONSEEKABLE`][crate::raw::flags::FOPEN_NONSEEKABLE] in the
+    /// [`open`][PathFilesystem::open] reply, then `lseek(2)` on it will fail with `ESPIPE` and the
+    /// filesystem won't receive lseek.
     async fn lseek(
         &self,
         req: Request,
@@ -537,6 +756,12 @@ pub trait PathFilesystem {
     /// then to kernel, finally send back to FUSE server. By implement this method, data will only
     /// copy in FUSE server internal.  when `from_path` or `to_path` is None, it means the path may
     /// be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// `flags` is the flags of `copy_file_range(2)`, not the write flags or open flags, no flag
+    /// is defined yet so it is 0 for now, the filesystem should reply `EINVAL` if it is not 0.
+    /// The copied count may be less than `length`, but must not be greater than it.
     #[allow(clippy::too_many_arguments)]
     async fn copy_file_range(
         &self,
@@ -552,4 +777,38 @@ pub trait PathFilesystem {
     ) -> Result<ReplyCopyFileRange> {
         Err(libc::ENOSYS.into())
     }
+
+    #[cfg(feature = "dax")]
+    /// map `len` bytes of the file at `foffset` into the DAX window at `moffset`. when `path` is
+    /// None, it means the path may be deleted.
+    ///
+    /// # Notes:
+    ///
+    /// see [`Filesystem::setup_mapping`][crate::raw::Filesystem::setup_mapping].
+    #[allow(clippy::too_many_arguments)]
+    async fn setup_mapping(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        fh: u64,
+        foffset: u64,
+        len: u64,
+        flags: u64,
+        moffset: u64,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "dax")]
+    /// remove the `mappings` of the DAX window which are set up by
+    /// [`setup_mapping`][PathFilesystem::setup_mapping]. when `path` is None, it means the path
+    /// may be deleted.
+    async fn remove_mapping(
+        &self,
+        req: Request,
+        path: Option<&OsStr>,
+        mappings: &[RemoveMapping],
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
 }
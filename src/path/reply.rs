@@ -36,6 +36,13 @@ pub struct FileAttr {
     /// Number of hard links
     pub nlink: u32,
     /// User id
+    ///
+    /// # Notes:
+    ///
+    /// the uid and gid in attr are the owner shown by `stat(2)`,
+    /// [`MountOptions::uid`][crate::MountOptions::uid] and
+    /// [`MountOptions::gid`][crate::MountOptions::gid] don't change them. The mount uid and gid
+    /// are the mount owner, without `allow_other` only the mount owner can access the filesystem.
     pub uid: u32,
     /// Group id
     pub gid: u32,
@@ -44,6 +51,13 @@ pub struct FileAttr {
     #[cfg(target_os = "macos")]
     /// Flags (macOS only, see chflags(2))
     pub flags: u32,
+    /// Preferred I/O block size of the file, it is the `st_blksize` shown by `stat(2)`
+    ///
+    /// # Notes:
+    ///
+    /// it is a per-file value, not the filesystem block size reported by
+    /// [`statfs`][crate::path::PathFilesystem::statfs], the two can differ. Kernel rounds it down
+    /// to a power of two, if it is 0, kernel uses the block size of the mount.
     pub blksize: u32,
 }
 
@@ -81,8 +95,11 @@ pub struct ReplyInit {
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// entry reply.
 pub struct ReplyEntry {
-    /// the attribute TTL.
-    pub ttl: Duration,
+    /// the entry TTL, kernel caches the name of the entry within it without calling lookup.
+    pub entry_ttl: Duration,
+    /// the attribute TTL, kernel uses the `attr` to answer the `stat(2)` within it without
+    /// calling getattr, it can be different from the `entry_ttl`.
+    pub attr_ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
 }
@@ -148,10 +165,21 @@ pub struct DirectoryEntryPlus {
     pub attr: FileAttr,
     /// the entry TTL.
     pub entry_ttl: Duration,
-    /// the attribute TTL.
+    /// the attribute TTL, kernel uses the `attr` to answer the `stat(2)` within it without
+    /// calling getattr. Set it to zero when the `attr` may be stale.
     pub attr_ttl: Duration,
 }
 
+impl DirectoryEntryPlus {
+    /// set the `attr_ttl` to zero, so kernel only primes the entry and calls getattr to fetch the
+    /// attribute when it is needed.
+    pub fn without_attr_cache(mut self) -> Self {
+        self.attr_ttl = Duration::ZERO;
+
+        self
+    }
+}
+
 /// the readdirplus reply.
 pub struct ReplyDirectoryPlus<S: Stream<Item = Result<DirectoryEntryPlus>>> {
     pub entries: S,
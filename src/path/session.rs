@@ -6,6 +6,43 @@ use crate::path::path_filesystem::PathFilesystem;
 use crate::raw;
 use crate::MountOptions;
 
+/// mount the path based filesystem with root permission, it is a shortcut of
+/// `Session::new(mount_options).mount(fs, mount_path)`, await the returned
+/// [`MountHandle`][raw::MountHandle] to wait the filesystem is unmounted.
+#[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
+pub async fn mount<FS, P>(
+    fs: FS,
+    mount_path: P,
+    mount_options: MountOptions,
+) -> io::Result<raw::MountHandle>
+where
+    FS: PathFilesystem + Send + Sync + 'static,
+    P: AsRef<Path>,
+{
+    Session::new(mount_options).mount(fs, mount_path).await
+}
+
+/// mount the path based filesystem without root permission, it is a shortcut of
+/// `Session::new(mount_options).mount_with_unprivileged(fs, mount_path)`, await the returned
+/// [`MountHandle`][raw::MountHandle] to wait the filesystem is unmounted.
+#[cfg(all(
+    any(feature = "async-io-runtime", feature = "tokio-runtime"),
+    feature = "unprivileged"
+))]
+pub async fn mount_with_unprivileged<FS, P>(
+    fs: FS,
+    mount_path: P,
+    mount_options: MountOptions,
+) -> io::Result<raw::MountHandle>
+where
+    FS: PathFilesystem + Send + Sync + 'static,
+    P: AsRef<Path>,
+{
+    Session::new(mount_options)
+        .mount_with_unprivileged(fs, mount_path)
+        .await
+}
+
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 #[derive(Debug)]
 /// fuse filesystem session, path based.
@@ -22,6 +59,10 @@ impl Session {
 
     #[cfg(feature = "unprivileged")]
     /// mount the filesystem without root permission.
+    ///
+    /// It returns after the `FUSE_INIT` request is handled, if [`PathFilesystem::init`] returns an
+    /// error, the mount path is unmounted and the init error is returned. Mounting on a path
+    /// inside an active mount of this process is rejected with `InvalidInput`, it would deadlock.
     pub async fn mount_with_unprivileged<P, FS>(
         self,
         fs: FS,
@@ -39,6 +80,10 @@ impl Session {
     }
 
     /// mount the filesystem with root permission.
+    ///
+    /// It returns after the `FUSE_INIT` request is handled, if [`PathFilesystem::init`] returns an
+    /// error, the mount path is unmounted and the init error is returned. Mounting on a path
+    /// inside an active mount of this process is rejected with `InvalidInput`, it would deadlock.
     pub async fn mount<P, FS>(self, fs: FS, mount_path: P) -> io::Result<raw::MountHandle>
     where
         P: AsRef<Path>,
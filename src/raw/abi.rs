@@ -38,12 +38,19 @@ pub const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
 
 pub const DEFAULT_MAX_BACKGROUND: u16 = 12;
 
-pub const DEFAULT_CONGESTION_THRESHOLD: u16 = DEFAULT_MAX_BACKGROUND * 3 / 4;
-
 pub const DEFAULT_TIME_GRAN: u32 = 1;
 
 pub const DEFAULT_MAX_PAGES: u16 = u16::MAX;
 
+/// The max pages of a request when kernel doesn't support `FUSE_MAX_PAGES`. In Linux kernel the
+/// `FUSE_DEFAULT_MAX_PAGES_PER_REQ` is
+///
+/// ```c
+/// /** Default max number of pages that can be used in a single read request */
+/// #define FUSE_DEFAULT_MAX_PAGES_PER_REQ 32
+/// ```
+pub const FUSE_DEFAULT_MAX_PAGES_PER_REQ: u32 = 32;
+
 // TODO find valid value
 pub const DEFAULT_MAP_ALIGNMENT: u16 = 0;
 
@@ -172,6 +179,15 @@ pub const FUSE_VOL_RENAME: u32 = 1 << 30;
 #[cfg(target_os = "macos")]
 pub const FUSE_XTIMES: u32 = 1 << 31;
 
+#[cfg(target_os = "linux")]
+/// extended fuse_init_in request, the `flags2` is valid
+pub const FUSE_INIT_EXT: u32 = 1 << 30;
+
+// the flags2 of init request/reply, the bit 32 and later of the 64 bit init flags
+#[cfg(target_os = "linux")]
+/// add security context to create, mkdir, symlink, and mknod
+pub const FUSE_SECURITY_CTX: u32 = 1 << 0;
+
 // CUSE init request/reply flags
 // use unrestricted ioctl
 // pub const CUSE_UNRESTRICTED_IOCTL: u32 = 1 << 0;
@@ -179,12 +195,30 @@ pub const FUSE_XTIMES: u32 = 1 << 31;
 // Release flags
 pub const FUSE_RELEASE_FLUSH: u32 = 1 << 0;
 
-#[allow(dead_code)]
 pub const FUSE_RELEASE_FLOCK_UNLOCK: u32 = 1 << 1;
 
+// Setupmapping flags
+#[cfg(feature = "dax")]
+/// the mapping is writable
+pub const FUSE_SETUPMAPPING_FLAG_WRITE: u64 = 1 << 0;
+#[cfg(feature = "dax")]
+/// the mapping is readable
+pub const FUSE_SETUPMAPPING_FLAG_READ: u64 = 1 << 1;
+
 // Getattr flags
 pub const FUSE_GETATTR_FH: u32 = 1 << 0;
 
+// Open reply flags
+/// bypass the page cache for this open file, the shared `mmap(2)` of the file fails with
+/// `ENODEV`, the private `mmap(2)` still uses the page cache
+pub const FOPEN_DIRECT_IO: u32 = 1 << 0;
+/// don't invalidate the data cache on open
+pub const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+/// the file is not seekable
+pub const FOPEN_NONSEEKABLE: u32 = 1 << 2;
+/// allow caching the directory contents, it is only used by the opendir reply
+pub const FOPEN_CACHE_DIR: u32 = 1 << 3;
+
 #[allow(dead_code)]
 // Lock flags, this is BSD file lock
 pub const FUSE_LK_FLOCK: u32 = 1 << 0;
@@ -231,7 +265,7 @@ pub const FUSE_IOCTL_MAX_IOV: u32 = 256;
 /// request poll notify
 pub const FUSE_POLL_SCHEDULE_NOTIFY: u32 = 1 << 0;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_attr {
     pub ino: u64,
@@ -291,11 +325,91 @@ pub struct fuse_file_lock {
     pub pid: u32,
 }
 
-/// Invalid opcode error.
+/// Invalid opcode error, the opcode isn't supported by this build, or isn't defined by the fuse
+/// protocol.
 #[derive(Debug)]
 pub struct UnknownOpcodeError(pub u32);
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+impl UnknownOpcodeError {
+    /// the canonical name of the opcode, such as `FUSE_IOCTL`, if it is defined by the fuse
+    /// protocol, the opcode may be disabled by the features or only used on the other platforms.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self.0 {
+            1 => "FUSE_LOOKUP",
+            2 => "FUSE_FORGET",
+            3 => "FUSE_GETATTR",
+            4 => "FUSE_SETATTR",
+            5 => "FUSE_READLINK",
+            6 => "FUSE_SYMLINK",
+            8 => "FUSE_MKNOD",
+            9 => "FUSE_MKDIR",
+            10 => "FUSE_UNLINK",
+            11 => "FUSE_RMDIR",
+            12 => "FUSE_RENAME",
+            13 => "FUSE_LINK",
+            14 => "FUSE_OPEN",
+            15 => "FUSE_READ",
+            16 => "FUSE_WRITE",
+            17 => "FUSE_STATFS",
+            18 => "FUSE_RELEASE",
+            20 => "FUSE_FSYNC",
+            21 => "FUSE_SETXATTR",
+            22 => "FUSE_GETXATTR",
+            23 => "FUSE_LISTXATTR",
+            24 => "FUSE_REMOVEXATTR",
+            25 => "FUSE_FLUSH",
+            26 => "FUSE_INIT",
+            27 => "FUSE_OPENDIR",
+            28 => "FUSE_READDIR",
+            29 => "FUSE_RELEASEDIR",
+            30 => "FUSE_FSYNCDIR",
+            31 => "FUSE_GETLK",
+            32 => "FUSE_SETLK",
+            33 => "FUSE_SETLKW",
+            34 => "FUSE_ACCESS",
+            35 => "FUSE_CREATE",
+            36 => "FUSE_INTERRUPT",
+            37 => "FUSE_BMAP",
+            38 => "FUSE_DESTROY",
+            39 => "FUSE_IOCTL",
+            40 => "FUSE_POLL",
+            41 => "FUSE_NOTIFY_REPLY",
+            42 => "FUSE_BATCH_FORGET",
+            43 => "FUSE_FALLOCATE",
+            44 => "FUSE_READDIRPLUS",
+            45 => "FUSE_RENAME2",
+            46 => "FUSE_LSEEK",
+            47 => "FUSE_COPY_FILE_RANGE",
+            48 => "FUSE_SETUPMAPPING",
+            49 => "FUSE_REMOVEMAPPING",
+            50 => "FUSE_SYNCFS",
+            51 => "FUSE_TMPFILE",
+            52 => "FUSE_STATX",
+            61 => "FUSE_SETVOLNAME",
+            62 => "FUSE_GETXTIMES",
+            63 => "FUSE_EXCHANGE",
+            4096 => "CUSE_INIT",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+}
+
+impl Display for UnknownOpcodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            None => write!(f, "unknown opcode {}", self.0),
+            Some(name) => write!(f, "unsupported opcode {name}({})", self.0),
+        }
+    }
+}
+
+impl Error for UnknownOpcodeError {}
+
+/// the fuse opcode of a request, it is exported as [`Opcode`][crate::Opcode] for the hooks of
+/// [`MountOptions`][crate::MountOptions].
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub enum fuse_opcode {
     FUSE_LOOKUP = 1,
@@ -348,8 +462,14 @@ pub enum fuse_opcode {
     FUSE_RENAME2 = 45,
     FUSE_LSEEK = 46,
     FUSE_COPY_FILE_RANGE = 47,
-    // FUSE_SETUPMAPPING = 48,
-    // FUSE_REMOVEMAPPING = 49,
+    #[cfg(feature = "dax")]
+    FUSE_SETUPMAPPING = 48,
+    #[cfg(feature = "dax")]
+    FUSE_REMOVEMAPPING = 49,
+    // FUSE_SYNCFS = 50,
+    FUSE_TMPFILE = 51,
+    #[cfg(target_os = "linux")]
+    FUSE_STATX = 52,
     #[cfg(target_os = "macos")]
     FUSE_SETVOLNAME = 61,
     #[cfg(target_os = "macos")]
@@ -359,6 +479,7 @@ pub enum fuse_opcode {
     // CUSE_INIT = 4096,
 }
 
+/// the opcode is displayed as its canonical name, such as `FUSE_LOOKUP`.
 impl Display for fuse_opcode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         Debug::fmt(self, f)
@@ -418,8 +539,14 @@ impl TryFrom<u32> for fuse_opcode {
             45 => Ok(fuse_opcode::FUSE_RENAME2),
             46 => Ok(fuse_opcode::FUSE_LSEEK),
             47 => Ok(fuse_opcode::FUSE_COPY_FILE_RANGE),
-            // 48 => Ok(fuse_opcode::FUSE_SETUPMAPPING),
-            // 49 => Ok(fuse_opcode::FUSE_REMOVEMAPPING),
+            #[cfg(feature = "dax")]
+            48 => Ok(fuse_opcode::FUSE_SETUPMAPPING),
+            #[cfg(feature = "dax")]
+            49 => Ok(fuse_opcode::FUSE_REMOVEMAPPING),
+            // 50 => Ok(fuse_opcode::FUSE_SYNCFS),
+            51 => Ok(fuse_opcode::FUSE_TMPFILE),
+            #[cfg(target_os = "linux")]
+            52 => Ok(fuse_opcode::FUSE_STATX),
             #[cfg(target_os = "macos")]
             61 => Ok(fuse_opcode::FUSE_SETVOLNAME),
             #[cfg(target_os = "macos")]
@@ -491,7 +618,7 @@ impl TryFrom<u32> for fuse_notify_code {
 
 pub const FUSE_ENTRY_OUT_SIZE: usize = mem::size_of::<fuse_entry_out>();
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_entry_out {
     pub nodeid: u64,
@@ -515,7 +642,7 @@ pub const FUSE_FORGET_ONE_SIZE: usize = mem::size_of::<fuse_forget_one>();
 #[allow(non_camel_case_types)]
 pub struct fuse_forget_one {
     pub nodeid: u64,
-    pub(crate) _nlookup: u64,
+    pub(crate) nlookup: u64,
 }
 
 pub const FUSE_BATCH_FORGET_IN_SIZE: usize = mem::size_of::<fuse_batch_forget_in>();
@@ -790,7 +917,7 @@ pub struct fuse_lk_in {
     pub fh: u64,
     pub owner: u64,
     pub lk: fuse_file_lock,
-    pub(crate) _lk_flags: u32,
+    pub lk_flags: u32,
     _padding: u32,
 }
 
@@ -811,6 +938,8 @@ pub struct fuse_access_in {
     _padding: u32,
 }
 
+pub const FUSE_INIT_IN_SIZE: usize = mem::size_of::<fuse_init_in>();
+
 #[derive(Debug, Deserialize)]
 #[allow(non_camel_case_types)]
 pub struct fuse_init_in {
@@ -835,7 +964,8 @@ pub struct fuse_init_out {
     pub time_gran: u32,
     pub max_pages: u16,
     pub map_alignment: u16,
-    pub unused: [u32; 8],
+    pub flags2: u32,
+    pub unused: [u32; 7],
 }
 
 /*#[derive(Debug)]
@@ -960,7 +1090,10 @@ pub struct fuse_in_header {
     pub uid: u32,
     pub gid: u32,
     pub pid: u32,
-    _padding: u32,
+    /// the length of the request extensions in 8 bytes units, the extensions are at the end of
+    /// the request body.
+    pub total_extlen: u16,
+    _padding: u16,
 }
 
 pub const FUSE_OUT_HEADER_SIZE: usize = mem::size_of::<fuse_out_header>();
@@ -1090,3 +1223,181 @@ pub struct fuse_copy_file_range_in {
     pub len: u64,
     pub flags: u64,
 }
+
+#[cfg(feature = "dax")]
+pub const FUSE_SETUPMAPPING_IN_SIZE: usize = mem::size_of::<fuse_setupmapping_in>();
+
+#[cfg(feature = "dax")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_setupmapping_in {
+    /// an already open handle
+    pub fh: u64,
+    /// offset into the file to start the mapping
+    pub foffset: u64,
+    /// length of mapping required
+    pub len: u64,
+    /// flags, FUSE_SETUPMAPPING_FLAG_*
+    pub flags: u64,
+    /// offset in memory window
+    pub moffset: u64,
+}
+
+#[cfg(feature = "dax")]
+pub const FUSE_REMOVEMAPPING_IN_SIZE: usize = mem::size_of::<fuse_removemapping_in>();
+
+#[cfg(feature = "dax")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_removemapping_in {
+    /// number of fuse_removemapping_one follows
+    pub count: u32,
+}
+
+#[cfg(feature = "dax")]
+pub const FUSE_REMOVEMAPPING_ONE_SIZE: usize = mem::size_of::<fuse_removemapping_one>();
+
+#[cfg(feature = "dax")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_removemapping_one {
+    /// offset into the dax window start the unmapping
+    pub moffset: u64,
+    /// length of mapping required
+    pub len: u64,
+}
+
+// the sizes are the kernel ABI, the decoders rely on them
+#[cfg(feature = "dax")]
+const _: () = assert!(
+    FUSE_SETUPMAPPING_IN_SIZE == 40
+        && FUSE_REMOVEMAPPING_IN_SIZE == 4
+        && FUSE_REMOVEMAPPING_ONE_SIZE == 16
+);
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default, Serialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_sx_time {
+    pub tv_sec: i64,
+    pub tv_nsec: u32,
+    pub _reserved: i32,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Default, Serialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_statx {
+    pub mask: u32,
+    pub blksize: u32,
+    pub attributes: u64,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16,
+    pub _spare0: [u16; 1],
+    pub ino: u64,
+    pub size: u64,
+    pub blocks: u64,
+    pub attributes_mask: u64,
+    pub atime: fuse_sx_time,
+    pub btime: fuse_sx_time,
+    pub ctime: fuse_sx_time,
+    pub mtime: fuse_sx_time,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub _spare2: [u64; 14],
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_statx_in {
+    pub getattr_flags: u32,
+    _reserved: u32,
+    pub fh: u64,
+    pub sx_flags: u32,
+    pub sx_mask: u32,
+}
+
+#[cfg(target_os = "linux")]
+pub const FUSE_STATX_OUT_SIZE: usize = mem::size_of::<fuse_statx_out>();
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Serialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_statx_out {
+    pub attr_valid: u64,
+    pub attr_valid_nsec: u32,
+    pub flags: u32,
+    pub _spare: [u64; 2],
+    pub stat: fuse_statx,
+}
+
+/// the max number of the security contexts in a request, the extension whose type is not greater
+/// than it is a security context extension, and the type is the number of the contexts.
+#[cfg(target_os = "linux")]
+pub const FUSE_MAX_NR_SECCTX: u32 = 31;
+
+#[cfg(target_os = "linux")]
+pub const FUSE_EXT_HEADER_SIZE: usize = mem::size_of::<fuse_ext_header>();
+
+/// the header of a request extension, `size` includes the header itself.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_ext_header {
+    pub size: u32,
+    pub ext_type: u32,
+}
+
+#[cfg(target_os = "linux")]
+pub const FUSE_SECCTX_SIZE: usize = mem::size_of::<fuse_secctx>();
+
+/// a security context, followed by the null terminated name and the context value, `size` is
+/// the length of the value.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Deserialize)]
+#[allow(non_camel_case_types)]
+pub struct fuse_secctx {
+    pub size: u32,
+    _padding: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_opcode_name() {
+        // the supported opcodes have the same name as the variants
+        for n in 0..=4096 {
+            if let Ok(opcode) = fuse_opcode::try_from(n) {
+                assert_eq!(
+                    UnknownOpcodeError(n).name(),
+                    Some(format!("{opcode:?}").as_str())
+                );
+            }
+        }
+
+        assert_eq!(UnknownOpcodeError(39).name(), Some("FUSE_IOCTL"));
+        assert_eq!(UnknownOpcodeError(7).name(), None);
+        assert_eq!(UnknownOpcodeError(1000).name(), None);
+
+        assert_eq!(
+            UnknownOpcodeError(39).to_string(),
+            "unsupported opcode FUSE_IOCTL(39)"
+        );
+        assert_eq!(UnknownOpcodeError(7).to_string(), "unknown opcode 7");
+    }
+
+    #[cfg(feature = "dax")]
+    #[test]
+    fn dax_struct_sizes() {
+        assert_eq!(FUSE_SETUPMAPPING_IN_SIZE, 40);
+        assert_eq!(FUSE_REMOVEMAPPING_IN_SIZE, 4);
+        assert_eq!(FUSE_REMOVEMAPPING_ONE_SIZE, 16);
+    }
+}
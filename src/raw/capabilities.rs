@@ -0,0 +1,58 @@
+use std::fmt::{self, Debug, Formatter};
+
+use crate::Opcode;
+
+/// the operations a filesystem supports, it is returned by
+/// [`Filesystem::capabilities`][crate::raw::Filesystem::capabilities].
+///
+/// The operations are the fuse opcodes, the same as [`MountOptions::request_class`] uses.
+///
+/// [`MountOptions::request_class`]: crate::MountOptions::request_class
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FsCapabilities(u64);
+
+impl FsCapabilities {
+    /// all operations are supported.
+    pub const fn all() -> Self {
+        Self(u64::MAX)
+    }
+
+    /// no operation is supported.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// mark the operation of the `opcode` as supported.
+    pub const fn with(self, opcode: Opcode) -> Self {
+        Self(self.0 | Self::bit(opcode))
+    }
+
+    /// mark the operation of the `opcode` as unsupported.
+    pub const fn without(self, opcode: Opcode) -> Self {
+        Self(self.0 & !Self::bit(opcode))
+    }
+
+    /// check if the operation of the `opcode` is supported.
+    pub const fn supports(self, opcode: Opcode) -> bool {
+        self.0 & Self::bit(opcode) > 0
+    }
+
+    // all opcodes except `CUSE_INIT` are less than 64, and it is not a fuse request
+    const fn bit(opcode: Opcode) -> u64 {
+        1 << opcode as u32
+    }
+}
+
+impl Debug for FsCapabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FsCapabilities")
+            .field(&format_args!("{:#018x}", self.0))
+            .finish()
+    }
+}
+
+impl Default for FsCapabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
@@ -1,20 +1,15 @@
 #[cfg(target_os = "macos")]
 use std::env;
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(target_os = "macos")]
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io;
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(target_os = "macos")]
 use std::io::Write;
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::{Deref, DerefMut};
 use std::os::fd::AsFd;
 use std::os::fd::BorrowedFd;
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd",
-    target_os = "macos"
-))]
 use std::os::fd::OwnedFd;
 #[cfg(target_os = "macos")]
 use std::os::fd::{AsRawFd, FromRawFd};
@@ -25,16 +20,15 @@ use std::os::fd::{AsRawFd, FromRawFd};
 use std::os::unix::io::RawFd;
 use std::pin::pin;
 use std::sync::Arc;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use std::sync::OnceLock;
 #[cfg(any(
     all(target_os = "linux", feature = "unprivileged"),
     target_os = "macos"
 ))]
 use std::{ffi::OsString, path::Path};
 
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
 use async_io::Async;
 use async_lock::Mutex;
 use async_notify::Notify;
@@ -46,16 +40,13 @@ use async_process::Command;
 #[cfg(target_os = "macos")]
 use futures_util::{join, try_join};
 use futures_util::{select, FutureExt};
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+use nix::errno::Errno;
 #[cfg(any(
     all(target_os = "linux", feature = "unprivileged"),
     target_os = "macos"
 ))]
 use nix::sys::socket::{self, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockType};
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd",
-    target_os = "macos"
-))]
 use nix::sys::uio;
 #[cfg(any(
     all(target_os = "linux", feature = "unprivileged"),
@@ -78,27 +69,28 @@ pub struct FuseConnection {
 }
 
 impl FuseConnection {
+    /// open the `/dev/fuse`, the fd is registered in the async-io reactor, so the read and write
+    /// wait the readiness instead of blocking a thread.
     #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     pub fn new(unmount_notify: Arc<Notify>) -> io::Result<Self> {
-        #[cfg(target_os = "freebsd")]
-        {
-            let connection = NonBlockFuseConnection::new()?;
+        let connection = NonBlockFuseConnection::new()?;
 
-            Ok(Self {
-                unmount_notify,
-                mode: ConnectionMode::NonBlock(connection),
-            })
-        }
+        Ok(Self {
+            unmount_notify,
+            mode: ConnectionMode::NonBlock(connection),
+        })
+    }
 
-        #[cfg(target_os = "linux")]
-        {
-            let connection = BlockFuseConnection::new()?;
+    /// use the fd of an existing fuse connection, the fd is registered in the async-io reactor
+    /// like [`new`][FuseConnection::new].
+    #[cfg(target_os = "linux")]
+    pub fn from_fd(fd: OwnedFd, unmount_notify: Arc<Notify>) -> io::Result<Self> {
+        let connection = NonBlockFuseConnection::from_fd(fd)?;
 
-            Ok(Self {
-                unmount_notify,
-                mode: ConnectionMode::Block(connection),
-            })
-        }
+        Ok(Self {
+            unmount_notify,
+            mode: ConnectionMode::NonBlock(connection),
+        })
     }
 
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
@@ -151,14 +143,11 @@ impl FuseConnection {
         data_buf: T,
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         match &self.mode {
-            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            #[cfg(target_os = "macos")]
             ConnectionMode::Block(connection) => {
                 connection.read_vectored(header_buf, data_buf).await
             }
-            #[cfg(any(
-                all(target_os = "linux", feature = "unprivileged"),
-                target_os = "freebsd"
-            ))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             ConnectionMode::NonBlock(connection) => {
                 connection.read_vectored(header_buf, data_buf).await
             }
@@ -171,14 +160,11 @@ impl FuseConnection {
         body_extend_data: Option<U>,
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         match &self.mode {
-            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            #[cfg(target_os = "macos")]
             ConnectionMode::Block(connection) => {
                 connection.write_vectored(data, body_extend_data).await
             }
-            #[cfg(any(
-                all(target_os = "linux", feature = "unprivileged"),
-                target_os = "freebsd"
-            ))]
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
             ConnectionMode::NonBlock(connection) => {
                 connection.write_vectored(data, body_extend_data).await
             }
@@ -188,16 +174,13 @@ impl FuseConnection {
 
 #[derive(Debug)]
 enum ConnectionMode {
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[cfg(target_os = "macos")]
     Block(BlockFuseConnection),
-    #[cfg(any(
-        all(target_os = "linux", feature = "unprivileged"),
-        target_os = "freebsd"
-    ))]
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
     NonBlock(NonBlockFuseConnection),
 }
 
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(target_os = "macos")]
 #[derive(Debug)]
 struct BlockFuseConnection {
     file: File,
@@ -205,29 +188,17 @@ struct BlockFuseConnection {
     write: Mutex<()>,
 }
 
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(target_os = "macos")]
 impl BlockFuseConnection {
-    #[cfg(target_os = "linux")]
-    pub fn new() -> io::Result<Self> {
-        const DEV_FUSE: &str = "/dev/fuse";
-
-        let file = OpenOptions::new().write(true).read(true).open(DEV_FUSE)?;
-
-        Ok(Self {
-            file,
-            read: Mutex::new(()),
-            write: Mutex::new(()),
-        })
-    }
-
-    #[cfg(target_os = "macos")]
     async fn new_with_unprivileged(
         mount_options: MountOptions,
         mount_path: impl AsRef<Path>,
     ) -> io::Result<Self> {
-        use std::{thread, time::Duration};
+        use std::{process::Stdio, thread, time::Duration};
+
+        use tracing::warn;
 
-        use crate::find_macfuse_mount;
+        use crate::{find_macfuse_mount, mount_binary_error};
 
         let (sock0, sock1) = match socket::socketpair(
             AddressFamily::Unix,
@@ -254,22 +225,25 @@ impl BlockFuseConnection {
         };
 
         let mount_path = mount_path.as_ref().as_os_str().to_os_string();
-        async_global_executor::spawn(async move {
+        let mount_task = async_global_executor::spawn(async move {
             debug!("mount_thread start");
             let fd0 = sock0.as_raw_fd();
             let mut binding = Command::new(binary_path);
-            let mut child = binding
+            let child = binding
                 .env(ENV, fd0.to_string())
                 .env("_FUSE_CALL_BY_LIB", "1")
                 .env("_FUSE_COMMVERS", "2")
                 .env("_FUSE_DAEMON_PATH", exec_path)
-                .args(vec![options, mount_path])
+                .args(options)
+                .arg(mount_path)
+                .stderr(Stdio::piped())
                 .spawn()?;
-            if !child.status().await?.success() {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "fusermount run failed",
-                ));
+            let output = child.output().await?;
+            if !output.status.success() {
+                let err = mount_binary_error("mount_macfuse", output.status, &output.stderr);
+                warn!("{}", err);
+
+                return Err(err);
             }
             Ok(())
         });
@@ -314,10 +288,26 @@ impl BlockFuseConnection {
                 return Err(io::Error::new(io::ErrorKind::Other, "get fuse fd failed"));
             };
 
+            // the fd is received from the mount helper without FD_CLOEXEC
+            super::set_fd_cloexec(fd)?;
+
             Ok(fd)
         })
-        .await
-        .unwrap();
+        .await;
+
+        // the fd isn't received when mount_macfuse fails, its error tells the reason
+        let fd = match fd {
+            Ok(fd) => fd,
+            Err(err) => {
+                return match mount_task.await {
+                    Err(mount_err) => Err(mount_err),
+                    Ok(()) => Err(err),
+                };
+            }
+        };
+
+        // mount_macfuse will block until fuse init done, keep it running in background
+        mount_task.detach();
 
         // Safety: fd is valid
         let file = unsafe { File::from_raw_fd(fd) };
@@ -367,48 +357,65 @@ impl BlockFuseConnection {
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         let _guard = self.write.lock().await;
 
-        let res = {
-            let body_extend_data = body_extend_data.as_deref();
+        // the fd is blocking, the write never fails with EAGAIN, but it may be interrupted
+        loop {
+            let res = {
+                let body_extend_data = body_extend_data.as_deref();
 
-            match body_extend_data {
-                None => (&self.file).write_vectored(&[IoSlice::new(data.deref())]),
+                match body_extend_data {
+                    None => (&self.file).write_vectored(&[IoSlice::new(data.deref())]),
 
-                Some(body_extend_data) => (&self.file)
-                    .write_vectored(&[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)]),
-            }
-        };
+                    Some(body_extend_data) => (&self.file).write_vectored(&[
+                        IoSlice::new(data.deref()),
+                        IoSlice::new(body_extend_data),
+                    ]),
+                }
+            };
 
-        match res {
-            Err(err) => ((data, body_extend_data), Err(err)),
-            Ok(n) => ((data, body_extend_data), Ok(n)),
+            match res {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return ((data, body_extend_data), Err(err)),
+                Ok(n) => return ((data, body_extend_data), Ok(n)),
+            }
         }
     }
 }
 
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
 #[derive(Debug)]
 struct NonBlockFuseConnection {
-    fd: Async<OwnedFd>,
+    device: OwnedFd,
+    // the `/dev/fuse` fd can't be registered in the reactor before it is mounted, the kernel
+    // doesn't add the poll waiter for an unmounted fd, so it is registered at the first io
+    fd: OnceLock<Async<OwnedFd>>,
     read: Mutex<()>,
     write: Mutex<()>,
 }
 
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd"
-))]
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
 impl NonBlockFuseConnection {
-    #[cfg(target_os = "freebsd")]
     fn new() -> io::Result<Self> {
         const DEV_FUSE: &str = "/dev/fuse";
 
         let file = OpenOptions::new().write(true).read(true).open(DEV_FUSE)?;
 
         Ok(Self {
-            fd: Async::new(file.into())?,
+            device: file.into(),
+            fd: OnceLock::new(),
+            read: Mutex::new(()),
+            write: Mutex::new(()),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        super::set_fd_cloexec(fd.as_raw_fd())?;
+
+        Ok(Self {
+            device: fd,
+            fd: OnceLock::new(),
             read: Mutex::new(()),
             write: Mutex::new(()),
         })
@@ -485,6 +492,9 @@ impl NonBlockFuseConnection {
                 return Err(io::Error::new(io::ErrorKind::Other, "get fuse fd failed"));
             };
 
+            // the fd is received from the mount helper without FD_CLOEXEC
+            super::set_fd_cloexec(fd)?;
+
             Ok(fd)
         })
         .await?;
@@ -493,12 +503,24 @@ impl NonBlockFuseConnection {
         let fd = unsafe { OwnedFd::from_raw_fd(fd) };
 
         Ok(Self {
-            fd: Async::new(fd)?,
+            device: fd,
+            fd: OnceLock::new(),
             read: Mutex::new(()),
             write: Mutex::new(()),
         })
     }
 
+    fn registered_fd(&self) -> io::Result<&Async<OwnedFd>> {
+        if let Some(fd) = self.fd.get() {
+            return Ok(fd);
+        }
+
+        // if the read and write register at the same time, the loser is dropped and deregistered
+        let fd = Async::new(self.device.try_clone()?)?;
+
+        Ok(self.fd.get_or_init(|| fd))
+    }
+
     async fn read_vectored<T: DerefMut<Target = [u8]> + Send + 'static>(
         &self,
         mut header_buf: Vec<u8>,
@@ -506,8 +528,12 @@ impl NonBlockFuseConnection {
     ) -> CompleteIoResult<(Vec<u8>, T), usize> {
         let _guard = self.read.lock().await;
 
-        let res = self
-            .fd
+        let fd = match self.registered_fd() {
+            Err(err) => return ((header_buf, data_buf), Err(err)),
+            Ok(fd) => fd,
+        };
+
+        let res = fd
             .read_with(|fd| {
                 uio::readv(
                     fd,
@@ -530,40 +556,47 @@ impl NonBlockFuseConnection {
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         let _guard = self.write.lock().await;
 
-        let res = {
-            let body_extend_data = body_extend_data.as_deref();
+        let fd = match self.registered_fd() {
+            Err(err) => return ((data, body_extend_data), Err(err)),
+            Ok(fd) => fd,
+        };
 
-            match body_extend_data {
-                None => uio::writev(&self.fd, &[IoSlice::new(data.deref())]),
+        // write_with waits the fd writable when the write fails with EAGAIN
+        let res = fd
+            .write_with(|fd| loop {
+                let body_extend_data = body_extend_data.as_deref();
 
-                Some(body_extend_data) => uio::writev(
-                    &self.fd,
-                    &[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)],
-                ),
-            }
-        };
+                let res = match body_extend_data {
+                    None => uio::writev(fd, &[IoSlice::new(data.deref())]),
 
-        match res {
-            Err(err) => ((data, body_extend_data), Err(err.into())),
-            Ok(n) => ((data, body_extend_data), Ok(n)),
-        }
+                    Some(body_extend_data) => uio::writev(
+                        fd,
+                        &[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)],
+                    ),
+                };
+
+                match res {
+                    Err(Errno::EINTR) => continue,
+                    res => return res.map_err(Into::into),
+                }
+            })
+            .await;
+
+        ((data, body_extend_data), res)
     }
 }
 
 impl AsFd for FuseConnection {
     fn as_fd(&self) -> BorrowedFd<'_> {
         match &self.mode {
-            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            #[cfg(target_os = "macos")]
             ConnectionMode::Block(connection) => {
                 // Safety: we own the File
                 connection.file.as_fd()
             }
 
-            #[cfg(any(
-                all(target_os = "linux", feature = "unprivileged"),
-                target_os = "freebsd"
-            ))]
-            ConnectionMode::NonBlock(connection) => connection.fd.as_fd(),
+            #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+            ConnectionMode::NonBlock(connection) => connection.device.as_fd(),
         }
     }
 }
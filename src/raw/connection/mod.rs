@@ -1,4 +1,14 @@
+//! the fuse device connection.
+//!
+//! The fuse device fd always has `FD_CLOEXEC` set, so it won't be leaked to the child processes
+//! spawned by the filesystem.
+
 use std::io;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::os::unix::io::RawFd;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use nix::fcntl::{self, FcntlArg, FdFlag};
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 pub use async_io::FuseConnection;
@@ -11,3 +21,13 @@ mod async_io;
 mod tokio;
 
 pub(crate) type CompleteIoResult<T, U> = (T, io::Result<U>);
+
+/// set `FD_CLOEXEC` on the fd, `/dev/fuse` opened by [`std::fs::OpenOptions`] already has it,
+/// but the fd received by `recvmsg` or passed by
+/// [`Session::attach`][crate::raw::Session::attach] may not.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn set_fd_cloexec(fd: RawFd) -> io::Result<()> {
+    fcntl::fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+
+    Ok(())
+}
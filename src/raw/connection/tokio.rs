@@ -4,23 +4,12 @@ use std::env;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io;
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd",
-    target_os = "macos",
-))]
 use std::io::ErrorKind;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::io::Write;
 use std::io::{IoSlice, IoSliceMut};
 use std::ops::{Deref, DerefMut};
-#[cfg(any(
-    all(target_os = "linux", feature = "unprivileged"),
-    target_os = "freebsd",
-    target_os = "macos",
-))]
-use std::os::fd::OwnedFd;
-use std::os::fd::{AsFd, BorrowedFd};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 #[cfg(any(target_os = "freebsd", target_os = "macos"))]
 use std::os::unix::fs::OpenOptionsExt;
 #[cfg(any(
@@ -116,6 +105,17 @@ impl FuseConnection {
         }
     }
 
+    /// use the fd of an existing fuse connection, it is used by the blocking read and write.
+    #[cfg(target_os = "linux")]
+    pub fn from_fd(fd: OwnedFd, unmount_notify: Arc<Notify>) -> io::Result<Self> {
+        let connection = BlockFuseConnection::from_fd(fd)?;
+
+        Ok(Self {
+            unmount_notify,
+            mode: ConnectionMode::Block(connection),
+        })
+    }
+
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
     pub async fn new_with_unprivileged(
         mount_options: MountOptions,
@@ -235,16 +235,37 @@ impl BlockFuseConnection {
         })
     }
 
+    #[cfg(target_os = "linux")]
+    fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        use nix::fcntl::{self, FcntlArg, OFlag};
+
+        super::set_fd_cloexec(fd.as_raw_fd())?;
+
+        // the fd may be non-blocking if it was opened by the mount helper, the blocking read
+        // would fail with EAGAIN
+        let flags = fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?;
+        let flags = OFlag::from_bits_truncate(flags) - OFlag::O_NONBLOCK;
+        fcntl::fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+
+        Ok(Self {
+            file: fd.into(),
+            read: Mutex::new(()),
+            write: Mutex::new(()),
+        })
+    }
+
     #[cfg(target_os = "macos")]
     async fn new_with_unprivileged(
         mount_options: MountOptions,
         mount_path: impl AsRef<Path>,
     ) -> io::Result<Self> {
-        use std::{thread, time::Duration};
+        use std::{process::Stdio, thread, time::Duration};
 
         use tokio::time::sleep;
 
-        use crate::find_macfuse_mount;
+        use crate::{find_macfuse_mount, mount_binary_error};
 
         let (sock0, sock1) = match socket::socketpair(
             AddressFamily::Unix,
@@ -272,7 +293,7 @@ impl BlockFuseConnection {
 
         let mount_path = mount_path.as_ref().as_os_str().to_os_string();
         // macfuse_mound will block until fuse init done, so we can not join it in the current function
-        tokio::spawn(async move {
+        let mount_task = tokio::spawn(async move {
             debug!("mount_thread start");
             let fd0 = sock0.as_raw_fd();
             let mut binding = Command::new(binary_path);
@@ -281,16 +302,18 @@ impl BlockFuseConnection {
                 .env("_FUSE_CALL_BY_LIB", "1")
                 .env("_FUSE_COMMVERS", "2")
                 .env("_FUSE_DAEMON_PATH", exec_path)
-                .args(vec![options, mount_path]);
-            let status = child.spawn()?.wait().await?;
+                .args(options)
+                .arg(mount_path)
+                .stderr(Stdio::piped());
+            let output = child.spawn()?.wait_with_output().await?;
 
-            if status.success() {
+            if output.status.success() {
                 Ok(())
             } else {
-                Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "fusermount run failed",
-                ))
+                let err = mount_binary_error("mount_macfuse", output.status, &output.stderr);
+                warn!("{}", err);
+
+                Err(err)
             }
         });
 
@@ -334,10 +357,24 @@ impl BlockFuseConnection {
                 return Err(io::Error::new(ErrorKind::Other, "get fuse fd failed"));
             };
 
+            // the fd is received from the mount helper without FD_CLOEXEC
+            super::set_fd_cloexec(fd)?;
+
             Ok(fd)
         })
         .await
-        .unwrap()?;
+        .unwrap();
+
+        // the fd isn't received when mount_macfuse fails, its error tells the reason
+        let fd = match fd {
+            Ok(fd) => fd,
+            Err(err) => {
+                return match mount_task.await {
+                    Ok(Err(mount_err)) => Err(mount_err),
+                    _ => Err(err),
+                };
+            }
+        };
 
         let file = unsafe { File::from_raw_fd(fd) };
         Ok(Self {
@@ -386,20 +423,26 @@ impl BlockFuseConnection {
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         let _guard = self.write.lock().await;
 
-        let res = {
-            let body_extend_data = body_extend_data.as_deref();
+        // the fd is blocking, the write never fails with EAGAIN, but it may be interrupted
+        loop {
+            let res = {
+                let body_extend_data = body_extend_data.as_deref();
 
-            match body_extend_data {
-                None => (&self.file).write_vectored(&[IoSlice::new(data.deref())]),
+                match body_extend_data {
+                    None => (&self.file).write_vectored(&[IoSlice::new(data.deref())]),
 
-                Some(body_extend_data) => (&self.file)
-                    .write_vectored(&[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)]),
-            }
-        };
+                    Some(body_extend_data) => (&self.file).write_vectored(&[
+                        IoSlice::new(data.deref()),
+                        IoSlice::new(body_extend_data),
+                    ]),
+                }
+            };
 
-        match res {
-            Err(err) => ((data, body_extend_data), Err(err)),
-            Ok(n) => ((data, body_extend_data), Ok(n)),
+            match res {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return ((data, body_extend_data), Err(err)),
+                Ok(n) => return ((data, body_extend_data), Ok(n)),
+            }
         }
     }
 }
@@ -517,6 +560,9 @@ impl NonBlockFuseConnection {
                 return Err(io::Error::new(ErrorKind::Other, "get fuse fd failed"));
             };
 
+            // the fd is received from the mount helper without FD_CLOEXEC
+            super::set_fd_cloexec(fd)?;
+
             Ok(fd)
         })
         .await
@@ -589,22 +635,32 @@ impl NonBlockFuseConnection {
     ) -> CompleteIoResult<(T, Option<U>), usize> {
         let _guard = self.write.lock().await;
 
-        let res = {
-            let body_extend_data = body_extend_data.as_deref();
+        loop {
+            let mut write_guard = match self.fd.writable().await {
+                Err(err) => return ((data, body_extend_data), Err(err)),
+                Ok(write_guard) => write_guard,
+            };
+
+            let res = write_guard.try_io(|fd| {
+                let body_extend_data = body_extend_data.as_deref();
 
-            match body_extend_data {
-                None => uio::writev(&self.fd, &[IoSlice::new(data.deref())]),
+                match body_extend_data {
+                    None => uio::writev(fd, &[IoSlice::new(data.deref())]),
 
-                Some(body_extend_data) => uio::writev(
-                    &self.fd,
-                    &[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)],
-                ),
-            }
-        };
+                    Some(body_extend_data) => uio::writev(
+                        fd,
+                        &[IoSlice::new(data.deref()), IoSlice::new(body_extend_data)],
+                    ),
+                }
+                .map_err(io::Error::from)
+            });
 
-        match res {
-            Err(err) => ((data, body_extend_data), Err(err.into())),
-            Ok(n) => ((data, body_extend_data), Ok(n)),
+            match res {
+                // EAGAIN, wait the fd writable again
+                Err(_) => continue,
+                Ok(Err(err)) if err.kind() == ErrorKind::Interrupted => continue,
+                Ok(res) => return ((data, body_extend_data), res),
+            }
         }
     }
 }
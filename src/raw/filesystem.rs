@@ -1,12 +1,21 @@
 use std::ffi::OsStr;
+use std::time::Duration;
 
 use bytes::Bytes;
 use futures_util::stream::Stream;
 
 use crate::notify::Notify;
+use crate::raw::flags::{GetattrFlags, ReleaseFlags};
 use crate::raw::reply::*;
 use crate::raw::request::Request;
-use crate::{Inode, Result, SetAttr};
+use crate::raw::FsCapabilities;
+#[cfg(feature = "file-lock")]
+use crate::FileLock;
+#[cfg(feature = "dax")]
+use crate::RemoveMapping;
+#[cfg(target_os = "linux")]
+use crate::Timestamp;
+use crate::{Inode, Result, SecurityContext, SetAttr, FUSE_ROOT_ID};
 
 #[allow(unused_variables)]
 #[trait_make::make(Send)]
@@ -21,11 +30,49 @@ pub trait Filesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn destroy(&self, req: Request);
 
+    /// get the operations the filesystem supports, it is called once after the filesystem is
+    /// initialized and cached by the session, default is [`FsCapabilities::all`].
+    ///
+    /// # Notes:
+    ///
+    /// the unsupported requests are replied with `ENOSYS` directly, the filesystem won't see
+    /// them. It is an introspection API for the wrapper layers, the forget, batch forget,
+    /// interrupt, notify reply, release, releasedir and destroy requests are always handled.
+    fn capabilities(&self) -> FsCapabilities {
+        FsCapabilities::all()
+    }
+
     /// look up a directory entry by name and get its attributes.
+    ///
+    /// # Notes:
+    ///
+    /// when the filesystem is exported by NFS, the kernel may look up `.` on an inode it only
+    /// knows by the number in a file handle, even the inode has been forgotten. The filesystem
+    /// should resolve any inode it has ever returned with the same generation, or return
+    /// `ESTALE` if the inode is gone. The `..` lookup is sent to
+    /// [`lookup_parent`][Filesystem::lookup_parent].
+    ///
+    /// replying an entry with inode 0 is a negative entry, kernel caches the name doesn't exist
+    /// for the `entry_ttl` and returns `ENOENT`, the `attr` is ignored. Other requests which
+    /// create an entry must not reply inode 0, they are replied with `EIO`.
     async fn lookup(&self, req: Request, parent: Inode, name: &OsStr) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
+    /// look up the parent directory of a directory, it is called when the kernel looks up `..`,
+    /// the default implementation is calling [`lookup`][Filesystem::lookup] with name `..`.
+    ///
+    /// # Notes:
+    ///
+    /// when the filesystem is exported by NFS, the kernel uses it to reconnect a directory which
+    /// is decoded from a file handle, the `inode` may not be looked up before.
+    async fn lookup_parent(&self, req: Request, inode: Inode) -> Result<ReplyEntry>
+    where
+        Self: Sync,
+    {
+        self.lookup(req, inode, OsStr::new("..")).await
+    }
+
     /// forget an inode. The nlookup parameter indicates the number of lookups previously
     /// performed on this inode. If the filesystem implements inode lifetimes, it is recommended
     /// that inodes acquire a single reference on each lookup, and lose nlookup references on each
@@ -37,18 +84,49 @@ pub trait Filesystem {
     /// <https://sourceforge.net/p/fuse/mailman/message/31995737/>
     async fn forget(&self, req: Request, inode: Inode, nlookup: u64) {}
 
-    /// get file attributes. If `fh` is None, means `fh` is not set.
+    /// get file attributes. If `fh` is None, means `fh` is not set, see [`GetattrFlags::has_fh`].
+    ///
+    /// # Notes:
+    ///
+    /// when the filesystem is exported by NFS, the `inode` may be forgotten before, see
+    /// [`lookup`][Filesystem::lookup].
+    ///
+    /// kernel sends getattr on the root [`FUSE_ROOT_ID`] before any lookup, the default
+    /// implementation replies [`FileAttr::new_root`] for the root, so a filesystem which doesn't
+    /// implement getattr still can be mounted, other inodes are replied with `ENOSYS`.
     async fn getattr(
         &self,
         req: Request,
         inode: Inode,
         fh: Option<u64>,
-        flags: u32,
+        flags: GetattrFlags,
     ) -> Result<ReplyAttr> {
+        if inode == FUSE_ROOT_ID {
+            return Ok(ReplyAttr {
+                ttl: Duration::from_secs(1),
+                attr: FileAttr::new_root(),
+            });
+        }
+
         Err(libc::ENOSYS.into())
     }
 
     /// set file attributes. If `fh` is None, means `fh` is not set.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is set, the kernel holds the inode lock while waiting the reply, so the
+    /// truncate is serialized with the writes of the same inode from this mount. With
+    /// writeback cache disabled no write of the inode is in flight at the same time; with
+    /// [`write_back`][crate::MountOptions::write_back] enabled, the kernel flushes and waits the
+    /// cached writes before sending the truncate. The filesystem still needs its own lock to keep
+    /// the truncate and the reply attr atomic, because every request is handled concurrently,
+    /// and the content may be changed by other ways, like another mount or the backend storage.
+    ///
+    /// the reply attr is the whole attributes after the change, not only the changed fields,
+    /// kernel replaces its cached attributes with it. As POSIX requires, any change should also
+    /// bump the `ctime` to the current time when `ctime` isn't set, even only `atime` or `mtime`
+    /// is changed.
     async fn setattr(
         &self,
         req: Request,
@@ -60,24 +138,32 @@ pub trait Filesystem {
     }
 
     /// read symbolic link.
+    ///
+    /// # Notes:
+    ///
+    /// if the inode is not a symbolic link, the filesystem should return `EINVAL`, which is what
+    /// `readlink(2)` reports to the caller in that case.
     async fn readlink(&self, req: Request, inode: Inode) -> Result<ReplyData> {
         Err(libc::ENOSYS.into())
     }
 
-    /// create a symbolic link.
+    /// create a symbolic link. `security_context` is the security context of the creating process,
+    /// see [`SecurityContext`].
     async fn symlink(
         &self,
         req: Request,
         parent: Inode,
         name: &OsStr,
         link: &OsStr,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
     /// create file node. Create a regular file, character device, block device, fifo or socket
     /// node. When creating file, most cases user only need to implement
-    /// [`create`][Filesystem::create].
+    /// [`create`][Filesystem::create]. `security_context` is the security context of the creating
+    /// process, see [`SecurityContext`].
     async fn mknod(
         &self,
         req: Request,
@@ -85,11 +171,13 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         rdev: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
 
-    /// create a directory.
+    /// create a directory. `security_context` is the security context of the creating process,
+    /// see [`SecurityContext`].
     async fn mkdir(
         &self,
         req: Request,
@@ -97,6 +185,7 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         umask: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyEntry> {
         Err(libc::ENOSYS.into())
     }
@@ -112,6 +201,11 @@ pub trait Filesystem {
     }
 
     /// rename a file or directory.
+    ///
+    /// # Notes:
+    ///
+    /// the `.` and `..` names are rejected with `EINVAL` before calling it. The filesystem must
+    /// reply `EINVAL` when renaming a directory into its own subtree, which makes a loop.
     async fn rename(
         &self,
         req: Request,
@@ -148,6 +242,10 @@ pub trait Filesystem {
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
     /// more details.
+    ///
+    /// when the file is a stream which isn't ready yet, such as a fifo like device, and `flags`
+    /// has `O_NONBLOCK`, return `EAGAIN` by `Errno::from(libc::EAGAIN)`, the `open(2)` fails with
+    /// it and the caller can retry later, the blocking open can wait the stream ready instead.
     async fn open(&self, req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
@@ -157,6 +255,11 @@ pub trait Filesystem {
     /// when the file has been opened in `direct_io` mode, in which case the return value of the
     /// read system call will reflect the return value of this operation. `fh` will contain the
     /// value set by the open method, or will be undefined if the open method didn't set any value.
+    ///
+    /// # Notes:
+    ///
+    /// the data longer than `size` bytes will be truncated, so don't read more than `size` bytes
+    /// from the backend, [`ReplyData::with_exact_len`] can help to check it.
     async fn read(
         &self,
         req: Request,
@@ -168,13 +271,23 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// write data. Write should return exactly the number of bytes requested except on error. An
-    /// exception to this is when the file has been opened in `direct_io` mode, in which case the
-    /// return value of the write system call will reflect the return value of this operation. `fh`
-    /// will contain the value set by the open method, or will be undefined if the open method
-    /// didn't set any value. When `write_flags` contains
+    /// write data. Write may return less than the number of bytes requested, like a filesystem
+    /// which is short of quota, the `write(2)` returns the written count and the caller retries
+    /// the rest, see [`ReplyWrite::short`]. `fh` will contain the value set by the open method,
+    /// or will be undefined if the open method didn't set any value. When `write_flags` contains
     /// [`FUSE_WRITE_CACHE`](crate::raw::flags::FUSE_WRITE_CACHE), means the write operation is a
     /// delay write.
+    ///
+    /// # Notes:
+    ///
+    /// the written count must not be greater than the length of `data`, or kernel fails the
+    /// write with `EIO`.
+    ///
+    /// when the file is opened with `O_APPEND`, the data must be written at the end of the file
+    /// whatever the `offset` is, kernel sends the end of the file it knows which may be stale
+    /// when the file is changed by others, so the filesystem should record the open flags
+    /// against the `fh` and append. With the writeback cache, kernel handles `O_APPEND` itself
+    /// and the filesystem should ignore it.
     #[allow(clippy::too_many_arguments)]
     async fn write(
         &self,
@@ -189,6 +302,35 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
+    /// write data which is owned by the filesystem, the session calls it for the write requests,
+    /// the default implementation is calling [`write`][Filesystem::write].
+    ///
+    /// # Notes:
+    ///
+    /// kernel sends the whole data of a `write(2)` in one request, up to the `max_write`, so the
+    /// data can't be streamed, but the filesystem which keeps the data, like an in-memory
+    /// filesystem or a filesystem which sends it to another task, can take the `data` without
+    /// copying it again, [`Bytes::slice`] splits it into chunks without copying. The data of a
+    /// large write is a part of the request buffer and isn't copied by the session, the data of
+    /// a small write is copied out of the reused request buffer once.
+    #[allow(clippy::too_many_arguments)]
+    async fn write_bytes(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        offset: u64,
+        data: Bytes,
+        write_flags: u32,
+        flags: u32,
+    ) -> Result<ReplyWrite>
+    where
+        Self: Sync,
+    {
+        self.write(req, inode, fh, offset, &data, write_flags, flags)
+            .await
+    }
+
     /// get filesystem statistics.
     async fn statfs(&self, req: Request, inode: Inode) -> Result<ReplyStatFs> {
         Err(libc::ENOSYS.into())
@@ -199,8 +341,17 @@ pub trait Filesystem {
     /// there will be exactly one release call. The filesystem may reply with an error, but error
     /// values are not returned to `close()` or `munmap()` which triggered the release. `fh` will
     /// contain the value set by the open method, or will be undefined if the open method didn't
-    /// set any value. `flags` will contain the same flags as for open. `flush` means flush the
-    /// data or not when closing file.
+    /// set any value. `flags` will contain the same `O_*` flags as for open, so the filesystem can
+    /// match the release with the open. `release_flags` tells to flush the data or not when
+    /// closing file, and to unlock the `flock(2)` locks of the `lock_owner` or not, see
+    /// [`ReleaseFlags`].
+    ///
+    /// # Notes:
+    ///
+    /// [`flush`][Filesystem::flush] may be called many times for one open file, once for each
+    /// `close()` of a duplicated file descriptor, but release is called only once when the last
+    /// reference is gone. So if the filesystem wants to persist data when the file is closed, it
+    /// should do it in release, not in flush.
     async fn release(
         &self,
         req: Request,
@@ -208,7 +359,7 @@ pub trait Filesystem {
         fh: u64,
         flags: u32,
         lock_owner: u64,
-        flush: bool,
+        release_flags: ReleaseFlags,
     ) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
@@ -235,6 +386,12 @@ pub trait Filesystem {
     /// Get an extended attribute. If `size` is too small, return `Err<ERANGE>`.
     /// Otherwise, use [`ReplyXAttr::Data`] to send the attribute data, or
     /// return an error.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is 0, the kernel asks the size of the value, reply [`ReplyXAttr::Size`] or
+    /// [`ReplyXAttr::Data`], only the size is sent. Otherwise the value larger than `size` is
+    /// replied `ERANGE` instead of being sent to the kernel.
     async fn getxattr(
         &self,
         req: Request,
@@ -249,6 +406,13 @@ pub trait Filesystem {
     ///
     /// If `size` is too small, return `Err<ERANGE>`.  Otherwise, use
     /// [`ReplyXAttr::Data`] to send the attribute list, or return an error.
+    /// [`ReplyXAttr::names`] builds the null terminated list of the names.
+    ///
+    /// # Notes:
+    ///
+    /// when `size` is 0, the kernel asks the size of the list, reply [`ReplyXAttr::Size`] or
+    /// [`ReplyXAttr::Data`], only the size is sent. Otherwise the list larger than `size` is
+    /// replied `ERANGE` instead of being sent to the kernel.
     async fn listxattr(&self, req: Request, inode: Inode, size: u32) -> Result<ReplyXAttr> {
         Err(libc::ENOSYS.into())
     }
@@ -281,6 +445,17 @@ pub trait Filesystem {
     /// I/O and not store anything in `fh`.  A file system need not implement this method if it
     /// sets [`MountOptions::no_open_dir_support`][crate::MountOptions::no_open_dir_support] and
     /// if the kernel supports `FUSE_NO_OPENDIR_SUPPORT`.
+    ///
+    /// # Notes:
+    ///
+    /// reply [`FOPEN_CACHE_DIR`][crate::raw::flags::FOPEN_CACHE_DIR] to let kernel cache the
+    /// directory contents, the later [`readdir`][Filesystem::readdir] and
+    /// [`readdirplus`][Filesystem::readdirplus] of the directory may be served from the cache
+    /// without calling the filesystem, the entry offsets must be stable. Kernel drops the cache
+    /// when the directory is changed through the mount or its mtime is changed, the filesystem
+    /// must invalidate it with [`Notify::invalid_inode`][crate::notify::Notify::invalid_inode]
+    /// on the directory when it is changed behind kernel. The cache is kept across opendir only
+    /// when the reply also has [`FOPEN_KEEP_CACHE`][crate::raw::flags::FOPEN_KEEP_CACHE].
     async fn opendir(&self, req: Request, inode: Inode, flags: u32) -> Result<ReplyOpen> {
         Err(libc::ENOSYS.into())
     }
@@ -293,6 +468,19 @@ pub trait Filesystem {
     /// read directory. `offset` is used to track the offset of the directory entries. `fh` will
     /// contain the value set by the [`opendir`][Filesystem::opendir] method, or will be
     /// undefined if the [`opendir`][Filesystem::opendir] method didn't set any value.
+    ///
+    /// # Notes:
+    ///
+    /// if `readdir` is not implemented, the default `ENOSYS` makes the session fallback to
+    /// [`readdirplus`][Filesystem::readdirplus] and discard the attrs, so implementing
+    /// `readdirplus` only is enough. The adaptation costs the attrs generation of every entry, a
+    /// filesystem which can list entries cheaply should implement `readdir` too.
+    ///
+    /// The fallback calls `readdirplus` with the `lock_owner` 0 and kernel never sees the
+    /// entries, so it won't [`forget`][Filesystem::forget] them. It is a hard requirement that a
+    /// `readdirplus` which backs the fallback doesn't take the lookup references of the entries,
+    /// a filesystem which counts them, like the lookups of `readdirplus` entries, must implement
+    /// `readdir`, otherwise the references are leaked. The first fallback logs a warning.
     async fn readdir<'a>(
         &'a self,
         req: Request,
@@ -320,43 +508,64 @@ pub trait Filesystem {
     }
 
     #[cfg(feature = "file-lock")]
-    /// test for a POSIX file lock.
+    /// test for a POSIX file lock, reply the lock which conflicts with `lock`, or reply `lock`
+    /// with [`LockKind::Unlock`][crate::LockKind::Unlock] if no lock conflicts.
     ///
     /// # Notes:
     ///
     /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
-    async fn getlk(
+    async fn getlk(&self, req: Request, inode: Inode, fh: u64, lock: FileLock)
+        -> Result<ReplyLock>;
+
+    #[cfg(feature = "file-lock")]
+    /// acquire, modify or release a POSIX file lock. When `block` is true, it is a `SETLKW`
+    /// request and the filesystem should wait until the lock is acquired.
+    ///
+    /// # Notes:
+    ///
+    /// this is supported on enable **`file-lock`** feature.
+    ///
+    /// the session never drops a running request future, when the waiting process is
+    /// interrupted, the kernel sends an [`interrupt`][Filesystem::interrupt] with the
+    /// `req.unique` of the `SETLKW` request instead. A filesystem which waits for the lock should
+    /// record the waiter by `req.unique` in its lock queue, and when the interrupt arrives, remove
+    /// the waiter from the queue and make this method return `EINTR`. Otherwise the waiter stays
+    /// in the queue and may acquire the lock after the process has gone.
+    async fn setlk(
         &self,
         req: Request,
         inode: Inode,
         fh: u64,
-        lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
-    ) -> Result<ReplyLock>;
+        lock: FileLock,
+        block: bool,
+    ) -> Result<()>;
 
     #[cfg(feature = "file-lock")]
-    /// acquire, modify or release a POSIX file lock.
+    /// acquire or release a BSD file lock, which is used by `flock(2)`. `operation` is one of
+    /// `libc::LOCK_SH`, `libc::LOCK_EX` and `libc::LOCK_UN`, when `block` is false, the
+    /// filesystem should return `EAGAIN` instead of waiting if the lock is held by others.
     ///
     /// # Notes:
     ///
-    /// this is supported on enable **`file-lock`** feature.
-    #[allow(clippy::too_many_arguments)]
-    async fn setlk(
+    /// this is supported on enable **`file-lock`** feature and
+    /// [`MountOptions::flock`][crate::MountOptions::flock] is enabled, otherwise the kernel
+    /// handles `flock` locally.
+    ///
+    /// the lock belongs to the open file description, which is identified by `lock_owner`.
+    /// When the last reference of the open file description is closed,
+    /// [`release`][Filesystem::release] is called with the same `lock_owner` and
+    /// [`ReleaseFlags::flock_unlock`] set, the filesystem should release the lock held by it.
+    async fn flock(
         &self,
         req: Request,
         inode: Inode,
         fh: u64,
         lock_owner: u64,
-        start: u64,
-        end: u64,
-        r#type: u32,
-        pid: u32,
+        operation: u32,
         block: bool,
-    ) -> Result<()>;
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
 
     /// check file access permissions. This will be called for the `access()` system call. If the
     /// `default_permissions` mount option is given, this method is not be called. This method is
@@ -380,7 +589,13 @@ pub trait Filesystem {
     ///
     /// See `fuse_file_info` structure in
     /// [fuse_common.h](https://libfuse.github.io/doxygen/include_2fuse__common_8h_source.html) for
-    /// more details.
+    /// more details. `security_context` is the security context of the creating process, see
+    /// [`SecurityContext`].
+    ///
+    /// `flags` always has `O_CREAT`, kernel calls create after the lookup found nothing, but the
+    /// file may be created by others between them. When the file exists and `flags` has
+    /// `O_EXCL`, return `EEXIST`, otherwise open the existing file like `open(2)` does, such as
+    /// truncating it when `flags` has `O_TRUNC`, the `mode` is ignored.
     async fn create(
         &self,
         req: Request,
@@ -388,21 +603,32 @@ pub trait Filesystem {
         name: &OsStr,
         mode: u32,
         flags: u32,
+        security_context: Option<SecurityContext>,
     ) -> Result<ReplyCreated> {
         Err(libc::ENOSYS.into())
     }
 
     /// handle interrupt. When a operation is interrupted, an interrupt request will send to fuse
     /// server with the unique id of the operation.
+    ///
+    /// # Notes:
+    ///
+    /// the interrupted operation is not cancelled by the session, it is still running and must
+    /// still be replied, usually with `EINTR`. If the operation has been replied or isn't known
+    /// yet, the interrupt can be ignored.
     async fn interrupt(&self, req: Request, unique: u64) -> Result<()> {
         Err(libc::ENOSYS.into())
     }
 
-    /// map block index within file to block index within device.
+    /// map block index within file to block index within device, it is used by the `FIBMAP`
+    /// ioctl. A filesystem which stores the files in a block device can implement it by
+    /// returning the device block of the file block `idx`.
     ///
     /// # Notes:
     ///
-    /// This may not works because currently this crate doesn't support fuseblk mode yet.
+    /// kernel only sends bmap for the fuseblk mounts, which this crate doesn't support yet, on
+    /// the other mounts `FIBMAP` gets block 0 without calling it. To report the holes of sparse
+    /// files, implement [`lseek`][Filesystem::lseek] with `SEEK_DATA` and `SEEK_HOLE` instead.
     async fn bmap(
         &self,
         req: Request,
@@ -481,6 +707,17 @@ pub trait Filesystem {
 
     /// read directory entries, but with their attribute, like [`readdir`][Filesystem::readdir]
     /// + [`lookup`][Filesystem::lookup] at the same time.
+    ///
+    /// # Notes:
+    ///
+    /// the attributes prime the kernel inode cache, such as `ls -l` won't call
+    /// [`getattr`][Filesystem::getattr] for the entries within the
+    /// [`attr_ttl`][DirectoryEntryPlus::attr_ttl]. If the attribute of an entry is uncertain, such
+    /// as a file being written by others, use [`DirectoryEntryPlus::without_attr_cache`] so kernel
+    /// fetches it by getattr.
+    ///
+    /// If [`readdir`][Filesystem::readdir] is not implemented, `readdirplus` also serves the
+    /// plain readdir, see its notes about the lookup references.
     async fn readdirplus<'a>(
         &'a self,
         req: Request,
@@ -492,7 +729,24 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
-    /// rename a file or directory with flags.
+    /// rename a file or directory with flags. The `flags` can be parsed by
+    /// [`RenameFlags`][crate::raw::flags::RenameFlags].
+    ///
+    /// # Notes:
+    ///
+    /// when `flags` has [`RenameFlags::WHITEOUT`][crate::raw::flags::RenameFlags::WHITEOUT], the
+    /// filesystem should create a whiteout object, a character device with device number 0/0, at
+    /// the old name atomically with the rename. It is used by overlay filesystems to hide the
+    /// lower entry.
+    ///
+    /// a filesystem must reply `EINVAL` for the flags it doesn't support, rather than ignore them
+    /// and do a plain rename, which breaks the atomicity the caller relies on;
+    /// [`RenameFlags::unsupported`][crate::raw::flags::RenameFlags::unsupported] helps to check
+    /// it. If the default `ENOSYS` is returned, kernel won't send rename2 anymore and all the
+    /// `renameat2(2)` with flags fail with `EINVAL`.
+    ///
+    /// the filesystem must reject the subtree loops with `EINVAL`, the same as
+    /// [`rename`][Filesystem::rename].
     async fn rename2(
         &self,
         req: Request,
@@ -506,6 +760,15 @@ pub trait Filesystem {
     }
 
     /// find next data or hole after the specified offset.
+    ///
+    /// # Notes:
+    ///
+    /// kernel only sends lseek with `SEEK_DATA` and `SEEK_HOLE`, other `whence` are handled by
+    /// kernel. If the default `ENOSYS` is returned, kernel won't send lseek anymore and fallback to
+    /// its generic implementation. For a non-seekable file, such as a stream, set
+    /// [`FOPEN_NONSEEKABLE`][crate::raw::flags::FOPEN_NONSEEKABLE] in the
+    /// [`open`][Filesystem::open] reply, then `lseek(2)` on it will fail with `ESPIPE` and the
+    /// filesystem won't receive lseek.
     async fn lseek(
         &self,
         req: Request,
@@ -521,6 +784,12 @@ pub trait Filesystem {
     /// reduce data copy: in normal, data will copy from FUSE server to kernel, then to user-space,
     /// then to kernel, finally send back to FUSE server. By implement this method, data will only
     /// copy in FUSE server internal.
+    ///
+    /// # Notes:
+    ///
+    /// `flags` is the flags of `copy_file_range(2)`, not the write flags or open flags, no flag
+    /// is defined yet so it is 0 for now, the filesystem should reply `EINVAL` if it is not 0.
+    /// The copied count may be less than `length`, but must not be greater than it.
     #[allow(clippy::too_many_arguments)]
     async fn copy_file_range(
         &self,
@@ -537,5 +806,94 @@ pub trait Filesystem {
         Err(libc::ENOSYS.into())
     }
 
+    #[cfg(feature = "dax")]
+    /// map `len` bytes of the file at `foffset` into the DAX window at `moffset`, `flags` has
+    /// [`FUSE_SETUPMAPPING_FLAG_READ`][crate::raw::flags::FUSE_SETUPMAPPING_FLAG_READ] and
+    /// [`FUSE_SETUPMAPPING_FLAG_WRITE`][crate::raw::flags::FUSE_SETUPMAPPING_FLAG_WRITE].
+    ///
+    /// # Notes:
+    ///
+    /// it is only sent by virtiofs with DAX enabled, the window is a shared memory region of the
+    /// virtual device, the `/dev/fuse` mount never sends it.
+    #[allow(clippy::too_many_arguments)]
+    async fn setup_mapping(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: u64,
+        foffset: u64,
+        len: u64,
+        flags: u64,
+        moffset: u64,
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    #[cfg(feature = "dax")]
+    /// remove the `mappings` of the DAX window which are set up by
+    /// [`setup_mapping`][Filesystem::setup_mapping].
+    async fn remove_mapping(
+        &self,
+        req: Request,
+        inode: Inode,
+        mappings: &[RemoveMapping],
+    ) -> Result<()> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// create an unnamed file in the `parent` directory and open it, it is sent by `open(2)` with
+    /// `O_TMPFILE`. The file has no name until it is linked by `linkat(2)`, which sends
+    /// [`link`][Filesystem::link] with the inode. If the file is never linked, it should be
+    /// removed when it is [`release`][Filesystem::release]d.
+    ///
+    /// # Notes:
+    ///
+    /// the `nlink` of the reply attr should be 1, kernel drops it to 0 for the unnamed file. If
+    /// the default `ENOSYS` is returned, kernel won't send tmpfile anymore and `open(2)` fails
+    /// with `EOPNOTSUPP`. `security_context` is the security context of the creating process, see
+    /// [`SecurityContext`].
+    async fn tmpfile(
+        &self,
+        req: Request,
+        parent: Inode,
+        mode: u32,
+        flags: u32,
+        security_context: Option<SecurityContext>,
+    ) -> Result<ReplyCreated> {
+        Err(libc::ENOSYS.into())
+    }
+
+    /// get the extended file attributes, it is sent by `statx(2)` when the fields which are not
+    /// in the basic stats, like `STATX_BTIME`, are requested. `mask` is the requested fields and
+    /// `flags` is the `AT_STATX_*` sync flags. If `fh` is None, means `fh` is not set.
+    ///
+    /// # Notes:
+    ///
+    /// the default implementation calls [`getattr`][Filesystem::getattr] and only reports the
+    /// basic stats, so the `btime` is unavailable. If `ENOSYS` is returned, kernel won't send
+    /// statx anymore.
+    #[cfg(target_os = "linux")]
+    async fn statx(
+        &self,
+        req: Request,
+        inode: Inode,
+        fh: Option<u64>,
+        getattr_flags: GetattrFlags,
+        flags: u32,
+        mask: u32,
+    ) -> Result<ReplyStatx>
+    where
+        Self: Sync,
+    {
+        let attr = self.getattr(req, inode, fh, getattr_flags).await?;
+
+        Ok(ReplyStatx {
+            ttl: attr.ttl,
+            attr: attr.attr,
+            btime: Timestamp::new(0, 0),
+            mask: libc::STATX_BASIC_STATS,
+        })
+    }
+
     // TODO setupmapping and removemapping
 }
@@ -1,5 +1,9 @@
 //! request flags.
 
+pub use crate::raw::abi::FOPEN_CACHE_DIR;
+pub use crate::raw::abi::FOPEN_DIRECT_IO;
+pub use crate::raw::abi::FOPEN_KEEP_CACHE;
+pub use crate::raw::abi::FOPEN_NONSEEKABLE;
 pub use crate::raw::abi::FUSE_IOCTL_32BIT;
 pub use crate::raw::abi::FUSE_IOCTL_COMPAT;
 pub use crate::raw::abi::FUSE_IOCTL_DIR;
@@ -8,5 +12,155 @@ pub use crate::raw::abi::FUSE_IOCTL_RETRY;
 pub use crate::raw::abi::FUSE_IOCTL_UNRESTRICTED;
 pub use crate::raw::abi::FUSE_POLL_SCHEDULE_NOTIFY;
 pub use crate::raw::abi::FUSE_READ_LOCKOWNER;
+#[cfg(feature = "dax")]
+pub use crate::raw::abi::FUSE_SETUPMAPPING_FLAG_READ;
+#[cfg(feature = "dax")]
+pub use crate::raw::abi::FUSE_SETUPMAPPING_FLAG_WRITE;
 pub use crate::raw::abi::FUSE_WRITE_CACHE;
 pub use crate::raw::abi::FUSE_WRITE_LOCKOWNER;
+
+use std::ops::BitOr;
+
+use crate::raw::abi::{FUSE_GETATTR_FH, FUSE_RELEASE_FLOCK_UNLOCK, FUSE_RELEASE_FLUSH};
+
+/// the getattr request flags.
+///
+/// # Notes:
+///
+/// currently kernel only sends `FUSE_GETATTR_FH`, but other flags may be added by future kernel.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct GetattrFlags(u32);
+
+impl GetattrFlags {
+    /// the getattr request carries a file handle, which means the getattr is called on an opened
+    /// file, such as `fstat(2)`.
+    pub const fn has_fh(self) -> bool {
+        self.0 & FUSE_GETATTR_FH > 0
+    }
+
+    /// get the raw flags.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for GetattrFlags {
+    fn from(flags: u32) -> Self {
+        Self(flags)
+    }
+}
+
+impl From<GetattrFlags> for u32 {
+    fn from(flags: GetattrFlags) -> Self {
+        flags.0
+    }
+}
+
+/// the release request flags, they are not the `O_*` open flags.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ReleaseFlags(u32);
+
+impl ReleaseFlags {
+    /// flush the data before releasing the file.
+    pub const fn flush(self) -> bool {
+        self.0 & FUSE_RELEASE_FLUSH > 0
+    }
+
+    /// the file holds `flock(2)` locks of the `lock_owner`, the filesystem should unlock them,
+    /// because kernel doesn't send the unlock request when the last reference is closed.
+    pub const fn flock_unlock(self) -> bool {
+        self.0 & FUSE_RELEASE_FLOCK_UNLOCK > 0
+    }
+
+    /// get the raw flags.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for ReleaseFlags {
+    fn from(flags: u32) -> Self {
+        Self(flags)
+    }
+}
+
+impl From<ReleaseFlags> for u32 {
+    fn from(flags: ReleaseFlags) -> Self {
+        flags.0
+    }
+}
+
+/// the rename2 request flags, see `renameat2(2)`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RenameFlags(u32);
+
+impl RenameFlags {
+    /// don't overwrite the new name of rename, return `EEXIST` if it exists.
+    pub const NOREPLACE: Self = Self(1 << 0);
+
+    /// atomically exchange the old name and the new name, both of them must exist.
+    pub const EXCHANGE: Self = Self(1 << 1);
+
+    /// create a whiteout object at the old name while renaming, it is used by overlay
+    /// filesystems. The whiteout object is a character device with device number 0/0.
+    pub const WHITEOUT: Self = Self(1 << 2);
+
+    /// check if all of the `other` flags are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// check if no flag is set, it means a plain rename.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// get the flags which are set but not in the `supported` flags, if it is not empty, the
+    /// filesystem should reply `EINVAL`.
+    pub const fn unsupported(self, supported: Self) -> Self {
+        Self(self.0 & !supported.0)
+    }
+
+    /// get the raw flags.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for RenameFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl From<u32> for RenameFlags {
+    fn from(flags: u32) -> Self {
+        Self(flags)
+    }
+}
+
+impl From<RenameFlags> for u32 {
+    fn from(flags: RenameFlags) -> Self {
+        flags.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn getattr_flags_has_fh() {
+        assert!(!GetattrFlags::default().has_fh());
+        assert!(GetattrFlags::from(FUSE_GETATTR_FH).has_fh());
+
+        // the unknown flags of the future kernel are kept but don't mean fh
+        let flags = GetattrFlags::from(1 << 5);
+        assert!(!flags.has_fh());
+        assert!(GetattrFlags::from(FUSE_GETATTR_FH | 1 << 5).has_fh());
+        assert_eq!(flags.bits(), 1 << 5);
+        assert_eq!(u32::from(flags), 1 << 5);
+    }
+}
@@ -7,29 +7,121 @@
 //! choose.
 
 use bytes::Bytes;
+pub use capabilities::FsCapabilities;
 pub use filesystem::Filesystem;
+#[cfg(feature = "small-footprint")]
+use futures_channel::mpsc::{channel, Receiver, Sender};
+#[cfg(not(feature = "small-footprint"))]
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures_util::future::Either;
 pub use request::Request;
+#[cfg(all(
+    any(feature = "async-io-runtime", feature = "tokio-runtime"),
+    any(
+        all(target_os = "linux", feature = "unprivileged"),
+        all(target_os = "freebsd", feature = "unprivileged"),
+        target_os = "macos"
+    )
+))]
+pub use session::mount_with_unprivileged;
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
-pub use session::{MountHandle, Session};
+pub use session::{mount, MountHandle, Session, SessionStats};
 
-pub(crate) type FuseData = Either<Vec<u8>, (Vec<u8>, Bytes)>;
+/// the reply data which will be written to `/dev/fuse`.
+///
+/// The right variant is the header and the payload, they are written by one `writev` call so the
+/// payload, such as the read data, doesn't need to be copied after the header.
+pub(crate) type FuseData = Either<ReplyBuffer, (ReplyBuffer, Bytes)>;
+
+/// the message which is sent to the reply task.
+#[derive(Debug)]
+pub(crate) enum Response {
+    /// a reply or a notification, it is written by one write.
+    Reply(FuseData),
+    /// the notify batch, the notifications are one after another in the buffer, the fuse device
+    /// takes one notification in one write, so they are written one by one.
+    NotifyBatch(ReplyBuffer),
+}
+
+/// the buffer of the reply header and the small reply body.
+///
+/// With the `small-footprint` feature, the buffer is stored inline when the reply is not larger
+/// than [`SMALL_REPLY_SIZE`], so the metadata replies don't need a separate buffer allocation.
+#[cfg(not(feature = "small-footprint"))]
+pub(crate) type ReplyBuffer = Vec<u8>;
+#[cfg(feature = "small-footprint")]
+pub(crate) type ReplyBuffer = smallvec::SmallVec<[u8; SMALL_REPLY_SIZE]>;
+
+/// convert the buffer to the [`Bytes`] without copying it, unless it is stored inline.
+#[cfg(not(feature = "small-footprint"))]
+pub(crate) fn reply_buffer_into_bytes(buffer: ReplyBuffer) -> Bytes {
+    Bytes::from(buffer)
+}
+#[cfg(feature = "small-footprint")]
+pub(crate) fn reply_buffer_into_bytes(buffer: ReplyBuffer) -> Bytes {
+    Bytes::from(buffer.into_vec())
+}
+
+/// the largest small reply, it is the create reply with `fuse_entry_out` and `fuse_open_out`.
+#[cfg(feature = "small-footprint")]
+pub(crate) const SMALL_REPLY_SIZE: usize =
+    abi::FUSE_OUT_HEADER_SIZE + abi::FUSE_ENTRY_OUT_SIZE + abi::FUSE_OPEN_OUT_SIZE;
+
+/// the channel sender which sends the replies and notifies to the reply task.
+#[cfg(not(feature = "small-footprint"))]
+pub(crate) type ResponseSender = UnboundedSender<Response>;
+#[cfg(feature = "small-footprint")]
+pub(crate) type ResponseSender = Sender<Response>;
+
+#[cfg(not(feature = "small-footprint"))]
+pub(crate) type ResponseReceiver = UnboundedReceiver<Response>;
+#[cfg(feature = "small-footprint")]
+pub(crate) type ResponseReceiver = Receiver<Response>;
+
+/// the buffer size of the bounded response channel with the `small-footprint` feature. The
+/// channel can hold `RESPONSE_CHANNEL_SIZE` replies and one more for each sender, a sender is
+/// held by each in flight request, so the pending replies are limited by the in flight requests.
+#[cfg(feature = "small-footprint")]
+pub(crate) const RESPONSE_CHANNEL_SIZE: usize = 64;
+
+pub(crate) fn response_channel() -> (ResponseSender, ResponseReceiver) {
+    #[cfg(not(feature = "small-footprint"))]
+    let channel = unbounded();
+
+    #[cfg(feature = "small-footprint")]
+    let channel = channel(RESPONSE_CHANNEL_SIZE);
+
+    channel
+}
 
 pub(crate) mod abi;
+mod capabilities;
 mod connection;
 mod filesystem;
 pub mod flags;
+// the protocol is public for the fuzz targets only
+#[cfg(fuzzing)]
+pub mod protocol;
+#[cfg(not(fuzzing))]
+pub(crate) mod protocol;
 pub mod reply;
 mod request;
 pub(crate) mod session;
 
 pub mod prelude {
+    pub use super::flags::GetattrFlags;
+    pub use super::flags::ReleaseFlags;
     pub use super::reply::FileAttr;
     pub use super::reply::*;
     pub use super::Filesystem;
+    pub use super::FsCapabilities;
     pub use super::Request;
     pub use super::Session;
     pub use crate::notify::Notify;
     pub use crate::FileType;
+    pub use crate::SecurityContext;
     pub use crate::SetAttr;
+    pub use crate::FUSE_ROOT_ID;
+    #[cfg(feature = "file-lock")]
+    pub use crate::{FileLock, LockKind};
 }
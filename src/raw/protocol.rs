@@ -0,0 +1,993 @@
+//! the runtime agnostic fuse protocol encoding and decoding.
+//!
+//! It only works on the bytes, doesn't do any IO and doesn't need the async runtime, the session
+//! reads the requests from the fuse connection and calls into it, so the decoders can be fuzzed
+//! with the arbitrary bytes.
+
+use std::any;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::helper::{get_bincode_config, get_first_null_position, get_padding_size, is_valid_name};
+use crate::raw::abi::*;
+#[cfg(feature = "dax")]
+use crate::RemoveMapping;
+use crate::{Errno, FileType, SecurityContext};
+
+/// the error of decoding a request.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    /// the request is too short to contain the struct.
+    Truncated(&'static str),
+    /// the name in the request isn't terminated by null.
+    NoNull,
+    /// the name in the request is empty, `.`, `..` or contains `/`.
+    InvalidName(OsString),
+    /// the request extensions are invalid.
+    InvalidExtension,
+}
+
+impl DecodeError {
+    /// the errno which the request should be replied with.
+    pub fn errno(&self) -> Errno {
+        libc::EINVAL.into()
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated(name) => write!(f, "request is too short for {name}"),
+            DecodeError::NoNull => f.write_str("name doesn't have null"),
+            DecodeError::InvalidName(name) => write!(f, "name {name:?} is invalid"),
+            DecodeError::InvalidExtension => f.write_str("request extensions are invalid"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// the decoded create request.
+#[derive(Debug)]
+pub struct CreateIn {
+    pub create_in: fuse_create_in,
+    pub name: OsString,
+    pub security_context: Option<SecurityContext>,
+}
+
+/// the decoded symlink request, the `link_name` is the target of the link, it isn't a single
+/// path component.
+#[derive(Debug)]
+pub struct SymlinkIn {
+    pub name: OsString,
+    pub link_name: OsString,
+    pub security_context: Option<SecurityContext>,
+}
+
+/// the decoded mknod request.
+#[derive(Debug)]
+pub struct MknodIn {
+    pub mknod_in: fuse_mknod_in,
+    pub name: OsString,
+    pub security_context: Option<SecurityContext>,
+}
+
+/// the decoded mkdir request.
+#[derive(Debug)]
+pub struct MkdirIn {
+    pub mkdir_in: fuse_mkdir_in,
+    pub name: OsString,
+    pub security_context: Option<SecurityContext>,
+}
+
+/// the decoded link request.
+#[derive(Debug)]
+pub struct LinkIn {
+    pub link_in: fuse_link_in,
+    pub name: OsString,
+}
+
+/// the decoded write request, the `data` is borrowed from the request buffer.
+#[derive(Debug)]
+pub struct WriteIn<'a> {
+    pub write_in: fuse_write_in,
+    pub data: &'a [u8],
+}
+
+/// the decoded setxattr request, the `value` is borrowed from the request buffer.
+#[derive(Debug)]
+pub struct SetxattrIn<'a> {
+    pub setxattr_in: fuse_setxattr_in,
+    pub name: OsString,
+    pub value: &'a [u8],
+}
+
+/// the decoded getxattr request.
+#[derive(Debug)]
+pub struct GetxattrIn {
+    pub getxattr_in: fuse_getxattr_in,
+    pub name: OsString,
+}
+
+/// the decoded notify reply of a retrieve, the `data` is borrowed from the request buffer.
+#[derive(Debug)]
+pub struct NotifyReplyIn<'a> {
+    pub notify_retrieve_in: fuse_notify_retrieve_in,
+    pub data: &'a [u8],
+}
+
+/// the decoded rename or rename2 request, the `flags` of rename is always 0.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RenameIn {
+    pub new_dir: u64,
+    pub name: OsString,
+    pub new_name: OsString,
+    pub flags: u32,
+}
+
+/// decode the `fuse_in_header`, the rest of `data` is returned with it.
+pub fn decode_in_header(data: &[u8]) -> Result<(fuse_in_header, &[u8]), DecodeError> {
+    decode_struct(data, FUSE_IN_HEADER_SIZE, "fuse_in_header")
+}
+
+/// decode the fixed size struct at the start of the request body, the rest of the body is
+/// ignored.
+pub fn decode_body<T: DeserializeOwned>(data: &[u8]) -> Result<T, DecodeError> {
+    get_bincode_config()
+        .deserialize(data)
+        .map_err(|_| DecodeError::Truncated(struct_name::<T>()))
+}
+
+/// decode the request body which is only a null terminated name, such as lookup, unlink, rmdir
+/// and removexattr. The name is not checked as a path component, the kernel has resolved it.
+pub fn decode_name_body(data: &[u8]) -> Result<OsString, DecodeError> {
+    let (name, _) = decode_raw_name(data)?;
+
+    Ok(name)
+}
+
+/// decode the symlink request body, it is the name and the target of the link.
+pub fn decode_symlink(in_header: &fuse_in_header, data: &[u8]) -> Result<SymlinkIn, DecodeError> {
+    let security_context = decode_security_context(in_header, data)?;
+    let (name, data) = decode_raw_name(data)?;
+    let (link_name, _) = decode_raw_name(data)?;
+
+    if !is_valid_name(&name) {
+        return Err(DecodeError::InvalidName(name));
+    }
+
+    Ok(SymlinkIn {
+        name,
+        link_name,
+        security_context,
+    })
+}
+
+/// decode the mknod request body.
+pub fn decode_mknod(in_header: &fuse_in_header, data: &[u8]) -> Result<MknodIn, DecodeError> {
+    let security_context = decode_security_context(in_header, data)?;
+    let (mknod_in, data) = decode_struct(data, FUSE_MKNOD_IN_SIZE, "fuse_mknod_in")?;
+    let (name, _) = decode_name(data)?;
+
+    Ok(MknodIn {
+        mknod_in,
+        name,
+        security_context,
+    })
+}
+
+/// decode the mkdir request body.
+pub fn decode_mkdir(in_header: &fuse_in_header, data: &[u8]) -> Result<MkdirIn, DecodeError> {
+    let security_context = decode_security_context(in_header, data)?;
+    let (mkdir_in, data) = decode_struct(data, FUSE_MKDIR_IN_SIZE, "fuse_mkdir_in")?;
+    let (name, _) = decode_name(data)?;
+
+    Ok(MkdirIn {
+        mkdir_in,
+        name,
+        security_context,
+    })
+}
+
+/// decode the link request body.
+pub fn decode_link(data: &[u8]) -> Result<LinkIn, DecodeError> {
+    let (link_in, data) = decode_struct(data, FUSE_LINK_IN_SIZE, "fuse_link_in")?;
+    let (name, _) = decode_name(data)?;
+
+    Ok(LinkIn { link_in, name })
+}
+
+/// decode the write request body, the data must be as long as the `size` of `fuse_write_in`.
+pub fn decode_write(data: &[u8]) -> Result<WriteIn<'_>, DecodeError> {
+    let (write_in, data) =
+        decode_struct::<fuse_write_in>(data, FUSE_WRITE_IN_SIZE, "fuse_write_in")?;
+
+    if write_in.size as usize != data.len() {
+        return Err(DecodeError::Truncated("write data"));
+    }
+
+    Ok(WriteIn { write_in, data })
+}
+
+/// decode the setxattr request body, the value must be as long as the `size` of
+/// `fuse_setxattr_in`.
+pub fn decode_setxattr(data: &[u8]) -> Result<SetxattrIn<'_>, DecodeError> {
+    let (setxattr_in, data) =
+        decode_struct::<fuse_setxattr_in>(data, FUSE_SETXATTR_IN_SIZE, "fuse_setxattr_in")?;
+    let (name, value) = decode_raw_name(data)?;
+
+    if setxattr_in.size as usize != value.len() {
+        return Err(DecodeError::Truncated("xattr value"));
+    }
+
+    Ok(SetxattrIn {
+        setxattr_in,
+        name,
+        value,
+    })
+}
+
+/// decode the getxattr request body.
+pub fn decode_getxattr(data: &[u8]) -> Result<GetxattrIn, DecodeError> {
+    let (getxattr_in, data) = decode_struct(data, FUSE_GETXATTR_IN_SIZE, "fuse_getxattr_in")?;
+    let (name, _) = decode_raw_name(data)?;
+
+    Ok(GetxattrIn { getxattr_in, name })
+}
+
+/// decode the batch forget request body, it must contain `count` of `fuse_batch_forget_in`
+/// forgets.
+pub fn decode_batch_forget(data: &[u8]) -> Result<Vec<fuse_forget_one>, DecodeError> {
+    let (batch_forget_in, mut data) = decode_struct::<fuse_batch_forget_in>(
+        data,
+        FUSE_BATCH_FORGET_IN_SIZE,
+        "fuse_batch_forget_in",
+    )?;
+
+    let mut forgets = vec![];
+    while data.len() >= FUSE_FORGET_ONE_SIZE {
+        let (forget_one, rest) = decode_struct(data, FUSE_FORGET_ONE_SIZE, "fuse_forget_one")?;
+        data = rest;
+
+        forgets.push(forget_one);
+    }
+
+    if forgets.len() != batch_forget_in.count as usize {
+        return Err(DecodeError::Truncated("fuse_forget_one"));
+    }
+
+    Ok(forgets)
+}
+
+/// decode the notify reply body, it must contain the `size` of `fuse_notify_retrieve_in` data,
+/// the data is truncated to it.
+pub fn decode_notify_reply(data: &[u8]) -> Result<NotifyReplyIn<'_>, DecodeError> {
+    let (notify_retrieve_in, data) = decode_struct::<fuse_notify_retrieve_in>(
+        data,
+        FUSE_NOTIFY_RETRIEVE_IN_SIZE,
+        "fuse_notify_retrieve_in",
+    )?;
+
+    let data = data
+        .get(..notify_retrieve_in.size as usize)
+        .ok_or(DecodeError::Truncated("notify reply data"))?;
+
+    Ok(NotifyReplyIn {
+        notify_retrieve_in,
+        data,
+    })
+}
+
+/// decode the create request body.
+pub fn decode_create(in_header: &fuse_in_header, data: &[u8]) -> Result<CreateIn, DecodeError> {
+    let security_context = decode_security_context(in_header, data)?;
+    let (create_in, data) = decode_struct(data, FUSE_CREATE_IN_SIZE, "fuse_create_in")?;
+    let (name, _) = decode_name(data)?;
+
+    Ok(CreateIn {
+        create_in,
+        name,
+        security_context,
+    })
+}
+
+/// decode the rename request body.
+pub fn decode_rename(data: &[u8]) -> Result<RenameIn, DecodeError> {
+    let (rename_in, data) =
+        decode_struct::<fuse_rename_in>(data, FUSE_RENAME_IN_SIZE, "fuse_rename_in")?;
+    let (name, data) = decode_name(data)?;
+    let (new_name, _) = decode_name(data)?;
+
+    Ok(RenameIn {
+        new_dir: rename_in.newdir,
+        name,
+        new_name,
+        flags: 0,
+    })
+}
+
+/// decode the rename2 request body.
+pub fn decode_rename2(data: &[u8]) -> Result<RenameIn, DecodeError> {
+    let (rename2_in, data) =
+        decode_struct::<fuse_rename2_in>(data, FUSE_RENAME2_IN_SIZE, "fuse_rename2_in")?;
+    let (name, data) = decode_name(data)?;
+    let (new_name, _) = decode_name(data)?;
+
+    Ok(RenameIn {
+        new_dir: rename2_in.newdir,
+        name,
+        new_name,
+        flags: rename2_in.flags,
+    })
+}
+
+/// decode the removemapping request body, the `fuse_removemapping_in` is followed by `count`
+/// mappings.
+#[cfg(feature = "dax")]
+pub fn decode_remove_mapping(data: &[u8]) -> Result<Vec<RemoveMapping>, DecodeError> {
+    let (remove_mapping_in, mut data) = decode_struct::<fuse_removemapping_in>(
+        data,
+        FUSE_REMOVEMAPPING_IN_SIZE,
+        "fuse_removemapping_in",
+    )?;
+
+    // check the count before allocating, the request must contain all the mappings
+    let count = remove_mapping_in.count as usize;
+    if data.len() / FUSE_REMOVEMAPPING_ONE_SIZE < count {
+        return Err(DecodeError::Truncated("fuse_removemapping_one"));
+    }
+
+    let mut mappings = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (one, rest) = decode_struct::<fuse_removemapping_one>(
+            data,
+            FUSE_REMOVEMAPPING_ONE_SIZE,
+            "fuse_removemapping_one",
+        )?;
+        data = rest;
+
+        mappings.push(RemoveMapping {
+            moffset: one.moffset,
+            len: one.len,
+        });
+    }
+
+    Ok(mappings)
+}
+
+/// decode the security context from the request extensions which are at the end of the request
+/// body, it is `None` if the request has no security context.
+#[cfg(target_os = "linux")]
+pub fn decode_security_context(
+    in_header: &fuse_in_header,
+    data: &[u8],
+) -> Result<Option<SecurityContext>, DecodeError> {
+    let ext_len = in_header.total_extlen as usize * 8;
+    if ext_len == 0 {
+        return Ok(None);
+    }
+
+    let mut ext_data = data
+        .len()
+        .checked_sub(ext_len)
+        .map(|start| &data[start..])
+        .ok_or(DecodeError::InvalidExtension)?;
+
+    while ext_data.len() >= FUSE_EXT_HEADER_SIZE {
+        let ext_header = get_bincode_config()
+            .deserialize::<fuse_ext_header>(ext_data)
+            .map_err(|_| DecodeError::InvalidExtension)?;
+
+        let size = ext_header.size as usize;
+        if size < FUSE_EXT_HEADER_SIZE || size > ext_data.len() {
+            return Err(DecodeError::InvalidExtension);
+        }
+
+        // the type of the security context extension is the number of contexts, only the first
+        // context is used
+        if ext_header.ext_type > 0 && ext_header.ext_type <= FUSE_MAX_NR_SECCTX {
+            let secctx_data = &ext_data[FUSE_EXT_HEADER_SIZE..size];
+            let secctx = get_bincode_config()
+                .deserialize::<fuse_secctx>(secctx_data)
+                .map_err(|_| DecodeError::InvalidExtension)?;
+            let secctx_data = &secctx_data[FUSE_SECCTX_SIZE.min(secctx_data.len())..];
+
+            let name_end =
+                get_first_null_position(secctx_data).ok_or(DecodeError::InvalidExtension)?;
+            let value = secctx_data
+                .get(name_end + 1..name_end + 1 + secctx.size as usize)
+                .ok_or(DecodeError::InvalidExtension)?;
+
+            return Ok(Some(SecurityContext {
+                name: OsString::from_vec(secctx_data[..name_end].to_vec()),
+                value: value.to_vec(),
+            }));
+        }
+
+        ext_data = &ext_data[size..];
+    }
+
+    Ok(None)
+}
+
+/// the security context is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn decode_security_context(
+    _in_header: &fuse_in_header,
+    _data: &[u8],
+) -> Result<Option<SecurityContext>, DecodeError> {
+    Ok(None)
+}
+
+/// encode the `fuse_out_header` of a reply, `body_len` is the length of the reply body after the
+/// header.
+pub fn encode_out_header(out: &mut impl Write, unique: u64, error: i32, body_len: usize) {
+    let out_header = fuse_out_header {
+        len: (FUSE_OUT_HEADER_SIZE + body_len) as u32,
+        error,
+        unique,
+    };
+
+    encode_struct(out, &out_header);
+}
+
+/// decode the `len` of the `fuse_out_header` at the start of `data`, it is the length of the
+/// whole reply or notification. It is `None` if `data` is shorter than the header.
+pub fn decode_out_header_len(data: &[u8]) -> Option<usize> {
+    let len = data.get(..4)?;
+
+    Some(u32::from_le_bytes(len.try_into().expect("len is 4 bytes")) as usize)
+}
+
+/// encode a fixed size struct of the reply body.
+pub fn encode_struct(out: &mut impl Write, value: &impl Serialize) {
+    get_bincode_config()
+        .serialize_into(out, value)
+        .expect("won't happened");
+}
+
+/// encode a readdir entry, the `fuse_dirent` is followed by the name and the padding to 8 bytes.
+pub fn encode_dirent(out: &mut impl Write, ino: u64, off: u64, kind: FileType, name: &OsStr) {
+    encode_struct(out, &new_dirent(ino, off, kind, name));
+    encode_dirent_name(out, name, FUSE_DIRENT_SIZE);
+}
+
+/// encode a readdirplus entry, the `fuse_direntplus` is followed by the name and the padding to 8
+/// bytes.
+pub fn encode_direntplus(
+    out: &mut impl Write,
+    entry_out: fuse_entry_out,
+    ino: u64,
+    off: u64,
+    kind: FileType,
+    name: &OsStr,
+) {
+    let direntplus = fuse_direntplus {
+        entry_out,
+        dirent: new_dirent(ino, off, kind, name),
+    };
+
+    encode_struct(out, &direntplus);
+    encode_dirent_name(out, name, FUSE_DIRENTPLUS_SIZE);
+}
+
+fn new_dirent(ino: u64, off: u64, kind: FileType, name: &OsStr) -> fuse_dirent {
+    fuse_dirent {
+        ino,
+        off,
+        namelen: name.len() as u32,
+        // learn from fuse-rs and golang bazil.org fuse DirentType
+        r#type: kind.dirent_type().into(),
+    }
+}
+
+/// encode the name after the dirent struct of `struct_size`, and pad the entry to 8 bytes.
+fn encode_dirent_name(out: &mut impl Write, name: &OsStr, struct_size: usize) {
+    let padding_size = get_padding_size(struct_size + name.len());
+
+    out.write_all(name.as_bytes()).expect("won't happened");
+    out.write_all(&[0; mem::size_of::<u64>()][..padding_size])
+        .expect("won't happened");
+}
+
+fn decode_struct<'a, T: DeserializeOwned>(
+    data: &'a [u8],
+    size: usize,
+    name: &'static str,
+) -> Result<(T, &'a [u8]), DecodeError> {
+    if data.len() < size {
+        return Err(DecodeError::Truncated(name));
+    }
+
+    let value = get_bincode_config()
+        .deserialize(data)
+        .map_err(|_| DecodeError::Truncated(name))?;
+
+    Ok((value, &data[size..]))
+}
+
+/// decode a null terminated name which must be a single path component, the rest of `data` after
+/// the null is returned with it.
+fn decode_name(data: &[u8]) -> Result<(OsString, &[u8]), DecodeError> {
+    let (name, data) = decode_raw_name(data)?;
+
+    if !is_valid_name(&name) {
+        return Err(DecodeError::InvalidName(name));
+    }
+
+    Ok((name, data))
+}
+
+/// decode a null terminated name, the rest of `data` after the null is returned with it.
+fn decode_raw_name(data: &[u8]) -> Result<(OsString, &[u8]), DecodeError> {
+    let index = get_first_null_position(data).ok_or(DecodeError::NoNull)?;
+    let name = OsString::from_vec(data[..index].to_vec());
+
+    Ok((name, &data[index + 1..]))
+}
+
+/// the struct name without the module path, such as `fuse_read_in`.
+fn struct_name<T>() -> &'static str {
+    let name = any::type_name::<T>();
+
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_header(opcode: u32, unique: u64) -> fuse_in_header {
+        let mut data = Vec::with_capacity(FUSE_IN_HEADER_SIZE);
+        data.extend_from_slice(&(FUSE_IN_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&opcode.to_le_bytes());
+        data.extend_from_slice(&unique.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&1000u32.to_le_bytes());
+        data.extend_from_slice(&1001u32.to_le_bytes());
+        data.extend_from_slice(&42u32.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+
+        let (in_header, rest) = decode_in_header(&data).unwrap();
+        assert!(rest.is_empty());
+
+        in_header
+    }
+
+    /// the fixed struct, which is padded with zero to `size`, followed by the names.
+    fn body(fields: &[&[u8]], size: usize, names: &[&[u8]]) -> Vec<u8> {
+        let mut data = fields.concat();
+        data.resize(size, 0);
+        for name in names {
+            data.extend_from_slice(name);
+        }
+
+        data
+    }
+
+    #[test]
+    fn in_header_fields() {
+        let in_header = in_header(fuse_opcode::FUSE_MKNOD as u32, 7);
+
+        assert_eq!(in_header.opcode, fuse_opcode::FUSE_MKNOD as u32);
+        assert_eq!(in_header.unique, 7);
+        assert_eq!(in_header.nodeid, 1);
+        assert_eq!(in_header.uid, 1000);
+        assert_eq!(in_header.gid, 1001);
+        assert_eq!(in_header.pid, 42);
+        assert_eq!(in_header.total_extlen, 0);
+
+        assert!(matches!(
+            decode_in_header(&[0; FUSE_IN_HEADER_SIZE - 1]),
+            Err(DecodeError::Truncated("fuse_in_header"))
+        ));
+    }
+
+    #[test]
+    fn mknod_and_mkdir() {
+        let in_header = in_header(fuse_opcode::FUSE_MKNOD as u32, 1);
+        let data = body(
+            &[&0o100644u32.to_le_bytes(), &5u32.to_le_bytes()],
+            FUSE_MKNOD_IN_SIZE,
+            &[b"file\0"],
+        );
+        let mknod = decode_mknod(&in_header, &data).unwrap();
+
+        assert_eq!(mknod.mknod_in.mode, 0o100644);
+        assert_eq!(mknod.mknod_in.rdev, 5);
+        assert_eq!(mknod.name, "file");
+        assert!(mknod.security_context.is_none());
+
+        let data = body(&[&0o755u32.to_le_bytes()], FUSE_MKDIR_IN_SIZE, &[b"dir\0"]);
+        let mkdir = decode_mkdir(&in_header, &data).unwrap();
+
+        assert_eq!(mkdir.mkdir_in.mode, 0o755);
+        assert_eq!(mkdir.name, "dir");
+
+        assert!(matches!(
+            decode_mknod(&in_header, &[0; FUSE_MKNOD_IN_SIZE - 1]),
+            Err(DecodeError::Truncated("fuse_mknod_in"))
+        ));
+        let data = body(&[], FUSE_MKDIR_IN_SIZE, &[b"dir"]);
+        assert!(matches!(
+            decode_mkdir(&in_header, &data),
+            Err(DecodeError::NoNull)
+        ));
+        let data = body(&[], FUSE_MKDIR_IN_SIZE, &[b"a/b\0"]);
+        assert!(matches!(
+            decode_mkdir(&in_header, &data),
+            Err(DecodeError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn symlink() {
+        let in_header = in_header(fuse_opcode::FUSE_SYMLINK as u32, 1);
+        let symlink = decode_symlink(&in_header, b"link\0../target/file\0").unwrap();
+
+        assert_eq!(symlink.name, "link");
+        // the target of the link is a path, not a single component
+        assert_eq!(symlink.link_name, "../target/file");
+
+        assert!(matches!(
+            decode_symlink(&in_header, b"..\0target\0"),
+            Err(DecodeError::InvalidName(_))
+        ));
+        assert!(matches!(
+            decode_symlink(&in_header, b"link\0target"),
+            Err(DecodeError::NoNull)
+        ));
+    }
+
+    #[test]
+    fn link_and_name_body() {
+        let data = body(&[&9u64.to_le_bytes()], FUSE_LINK_IN_SIZE, &[b"hard\0"]);
+        let link = decode_link(&data).unwrap();
+
+        assert_eq!(link.link_in.oldnodeid, 9);
+        assert_eq!(link.name, "hard");
+
+        assert_eq!(decode_name_body(b"name\0rest").unwrap(), "name");
+        assert!(matches!(
+            decode_name_body(b"name"),
+            Err(DecodeError::NoNull)
+        ));
+    }
+
+    #[test]
+    fn write() {
+        let mut data = body(
+            &[
+                &3u64.to_le_bytes(),
+                &4096u64.to_le_bytes(),
+                &5u32.to_le_bytes(),
+            ],
+            FUSE_WRITE_IN_SIZE,
+            &[b"hello"],
+        );
+        let write = decode_write(&data).unwrap();
+
+        assert_eq!(write.write_in.fh, 3);
+        assert_eq!(write.write_in.offset, 4096);
+        assert_eq!(write.data, b"hello");
+
+        data.pop();
+        assert!(matches!(
+            decode_write(&data),
+            Err(DecodeError::Truncated("write data"))
+        ));
+    }
+
+    #[test]
+    fn setxattr_and_getxattr() {
+        let data = body(
+            &[&3u32.to_le_bytes(), &1u32.to_le_bytes()],
+            FUSE_SETXATTR_IN_SIZE,
+            &[b"user.a\0", b"abc"],
+        );
+        let setxattr = decode_setxattr(&data).unwrap();
+
+        assert_eq!(setxattr.setxattr_in.size, 3);
+        assert_eq!(setxattr.setxattr_in.flags, 1);
+        assert_eq!(setxattr.name, "user.a");
+        assert_eq!(setxattr.value, b"abc");
+
+        assert!(matches!(
+            decode_setxattr(&data[..data.len() - 1]),
+            Err(DecodeError::Truncated("xattr value"))
+        ));
+
+        let data = body(
+            &[&64u32.to_le_bytes()],
+            FUSE_GETXATTR_IN_SIZE,
+            &[b"user.a\0"],
+        );
+        let getxattr = decode_getxattr(&data).unwrap();
+
+        assert_eq!(getxattr.getxattr_in.size, 64);
+        assert_eq!(getxattr.name, "user.a");
+    }
+
+    #[test]
+    fn rename_and_rename2() {
+        let data = body(&[&2u64.to_le_bytes()], FUSE_RENAME_IN_SIZE, &[b"a\0b\0"]);
+
+        assert_eq!(
+            decode_rename(&data).unwrap(),
+            RenameIn {
+                new_dir: 2,
+                name: "a".into(),
+                new_name: "b".into(),
+                flags: 0,
+            }
+        );
+
+        let data = body(
+            &[&2u64.to_le_bytes(), &1u32.to_le_bytes()],
+            FUSE_RENAME2_IN_SIZE,
+            &[b"a\0b\0"],
+        );
+
+        assert_eq!(
+            decode_rename2(&data).unwrap(),
+            RenameIn {
+                new_dir: 2,
+                name: "a".into(),
+                new_name: "b".into(),
+                flags: 1,
+            }
+        );
+
+        let data = body(&[], FUSE_RENAME2_IN_SIZE, &[b"a\0..\0"]);
+        assert!(matches!(
+            decode_rename2(&data),
+            Err(DecodeError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn create() {
+        let in_header = in_header(fuse_opcode::FUSE_CREATE as u32, 1);
+        let data = body(
+            &[&2u32.to_le_bytes(), &0o644u32.to_le_bytes()],
+            FUSE_CREATE_IN_SIZE,
+            &[b"new\0"],
+        );
+        let create = decode_create(&in_header, &data).unwrap();
+
+        assert_eq!(create.create_in.flags, 2);
+        assert_eq!(create.create_in.mode, 0o644);
+        assert_eq!(create.name, "new");
+    }
+
+    #[test]
+    fn batch_forget() {
+        let forget_one = |nodeid: u64| body(&[&nodeid.to_le_bytes(), &1u64.to_le_bytes()], 16, &[]);
+        let mut data = body(&[&2u32.to_le_bytes()], FUSE_BATCH_FORGET_IN_SIZE, &[]);
+        data.extend_from_slice(&forget_one(5));
+        data.extend_from_slice(&forget_one(6));
+
+        let forgets = decode_batch_forget(&data).unwrap();
+        assert_eq!(
+            forgets
+                .iter()
+                .map(|forget| forget.nodeid)
+                .collect::<Vec<_>>(),
+            [5, 6]
+        );
+
+        // the count doesn't match the forgets in the request
+        assert!(matches!(
+            decode_batch_forget(&data[..data.len() - 1]),
+            Err(DecodeError::Truncated("fuse_forget_one"))
+        ));
+    }
+
+    #[cfg(feature = "dax")]
+    #[test]
+    fn setup_mapping() {
+        let fields: [&[u8]; 5] = [
+            &1u64.to_le_bytes(),
+            &2u64.to_le_bytes(),
+            &3u64.to_le_bytes(),
+            &FUSE_SETUPMAPPING_FLAG_WRITE.to_le_bytes(),
+            &4u64.to_le_bytes(),
+        ];
+        let data = body(&fields, FUSE_SETUPMAPPING_IN_SIZE, &[]);
+
+        let setup_mapping_in = decode_body::<fuse_setupmapping_in>(&data).unwrap();
+        assert_eq!(setup_mapping_in.fh, 1);
+        assert_eq!(setup_mapping_in.foffset, 2);
+        assert_eq!(setup_mapping_in.len, 3);
+        assert_eq!(setup_mapping_in.flags, FUSE_SETUPMAPPING_FLAG_WRITE);
+        assert_eq!(setup_mapping_in.moffset, 4);
+    }
+
+    #[cfg(feature = "dax")]
+    #[test]
+    fn remove_mapping() {
+        let mappings = [
+            RemoveMapping {
+                moffset: 0x1000,
+                len: 0x2000,
+            },
+            RemoveMapping {
+                moffset: 0x20_0000,
+                len: 0x20_0000,
+            },
+        ];
+
+        let mut data = body(&[&2u32.to_le_bytes()], FUSE_REMOVEMAPPING_IN_SIZE, &[]);
+        for mapping in &mappings {
+            data.extend_from_slice(&body(
+                &[&mapping.moffset.to_le_bytes(), &mapping.len.to_le_bytes()],
+                FUSE_REMOVEMAPPING_ONE_SIZE,
+                &[],
+            ));
+        }
+
+        assert_eq!(decode_remove_mapping(&data).unwrap(), mappings);
+
+        // the count doesn't match the mappings in the request
+        assert!(matches!(
+            decode_remove_mapping(&data[..data.len() - 1]),
+            Err(DecodeError::Truncated("fuse_removemapping_one"))
+        ));
+        assert!(matches!(
+            decode_remove_mapping(&data[..2]),
+            Err(DecodeError::Truncated("fuse_removemapping_in"))
+        ));
+    }
+
+    #[test]
+    fn notify_reply() {
+        let data = body(
+            &[
+                &0u64.to_le_bytes(),
+                &0u64.to_le_bytes(),
+                &3u32.to_le_bytes(),
+            ],
+            FUSE_NOTIFY_RETRIEVE_IN_SIZE,
+            &[b"abcdef"],
+        );
+        let notify_reply = decode_notify_reply(&data).unwrap();
+
+        // the data is truncated to the size
+        assert_eq!(notify_reply.notify_retrieve_in.size, 3);
+        assert_eq!(notify_reply.data, b"abc");
+
+        assert!(matches!(
+            decode_notify_reply(&data[..FUSE_NOTIFY_RETRIEVE_IN_SIZE + 2]),
+            Err(DecodeError::Truncated("notify reply data"))
+        ));
+    }
+
+    #[test]
+    fn body_struct_name() {
+        assert!(matches!(
+            decode_body::<fuse_read_in>(&[0; 4]),
+            Err(DecodeError::Truncated("fuse_read_in"))
+        ));
+    }
+
+    #[test]
+    fn out_header_layout() {
+        let mut data = vec![];
+        encode_out_header(&mut data, 0x0102_0304_0506_0708, -libc::ENOENT, 16);
+
+        let mut expect = vec![];
+        expect.extend_from_slice(&(FUSE_OUT_HEADER_SIZE as u32 + 16).to_le_bytes());
+        expect.extend_from_slice(&(-libc::ENOENT).to_le_bytes());
+        expect.extend_from_slice(&0x0102_0304_0506_0708u64.to_le_bytes());
+
+        assert_eq!(data, expect);
+    }
+
+    #[test]
+    fn out_header_len() {
+        let mut data = vec![];
+        encode_out_header(&mut data, 1, 0, 16);
+
+        assert_eq!(
+            decode_out_header_len(&data),
+            Some(FUSE_OUT_HEADER_SIZE + 16)
+        );
+        assert_eq!(decode_out_header_len(&data[..3]), None);
+    }
+
+    /// the reply layout before the dirent encoding moved here: the header, then each
+    /// `fuse_dirent`, the name and the zero padding to 8 bytes.
+    fn old_dirent_reply(unique: u64, entries: &[(u64, u64, FileType, &str)]) -> Vec<u8> {
+        let mut body = vec![];
+        for (ino, off, kind, name) in entries {
+            encode_struct(
+                &mut body,
+                &fuse_dirent {
+                    ino: *ino,
+                    off: *off,
+                    namelen: name.len() as u32,
+                    r#type: kind.dirent_type().into(),
+                },
+            );
+            body.extend_from_slice(name.as_bytes());
+            body.resize(
+                body.len() + get_padding_size(FUSE_DIRENT_SIZE + name.len()),
+                0,
+            );
+        }
+
+        let mut data = vec![];
+        encode_out_header(&mut data, unique, 0, body.len());
+        data.extend_from_slice(&body);
+
+        data
+    }
+
+    const ENTRIES: &[(u64, u64, FileType, &str)] = &[
+        (1, 1, FileType::Directory, "."),
+        (2, 2, FileType::RegularFile, "8 bytes!"),
+        (3, 3, FileType::Symlink, "link"),
+        (4, 4, FileType::NamedPipe, "文件"),
+    ];
+
+    #[test]
+    fn dirent_layout() {
+        let mut data = vec![0; FUSE_OUT_HEADER_SIZE];
+        for (ino, off, kind, name) in ENTRIES {
+            encode_dirent(&mut data, *ino, *off, *kind, OsStr::new(name));
+            assert_eq!((data.len() - FUSE_OUT_HEADER_SIZE) % 8, 0);
+        }
+        let body_len = data.len() - FUSE_OUT_HEADER_SIZE;
+        encode_out_header(&mut &mut data[..FUSE_OUT_HEADER_SIZE], 7, 0, body_len);
+
+        assert_eq!(data, old_dirent_reply(7, ENTRIES));
+
+        // ino, off, namelen, type, then the name "." and 7 bytes padding
+        let first = &data[FUSE_OUT_HEADER_SIZE..FUSE_OUT_HEADER_SIZE + FUSE_DIRENT_SIZE + 8];
+        assert_eq!(&first[..8], &1u64.to_le_bytes());
+        assert_eq!(&first[16..20], &1u32.to_le_bytes());
+        assert_eq!(&first[20..24], &u32::from(libc::DT_DIR).to_le_bytes());
+        assert_eq!(&first[24..], b".\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn direntplus_layout() {
+        let entry_out = || fuse_entry_out {
+            nodeid: 2,
+            generation: 3,
+            entry_valid: 4,
+            attr_valid: 5,
+            entry_valid_nsec: 6,
+            attr_valid_nsec: 7,
+            attr: fuse_attr::default(),
+        };
+
+        let mut data = vec![];
+        for (ino, off, kind, name) in ENTRIES {
+            encode_direntplus(&mut data, entry_out(), *ino, *off, *kind, OsStr::new(name));
+            assert_eq!(data.len() % 8, 0);
+        }
+
+        // each entry is the entry_out followed by the readdir entry
+        let dirents = old_dirent_reply(0, ENTRIES);
+        let mut dirents = &dirents[FUSE_OUT_HEADER_SIZE..];
+        let mut expect = vec![];
+        for (_, _, _, name) in ENTRIES {
+            let len =
+                FUSE_DIRENT_SIZE + name.len() + get_padding_size(FUSE_DIRENT_SIZE + name.len());
+            encode_struct(&mut expect, &entry_out());
+            expect.extend_from_slice(&dirents[..len]);
+            dirents = &dirents[len..];
+        }
+
+        assert_eq!(data, expect);
+    }
+}
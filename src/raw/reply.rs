@@ -1,21 +1,36 @@
 //! reply structures.
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
 use std::num::NonZeroU32;
-use std::time::Duration;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::time::{Duration, SystemTime};
 
 use bytes::Bytes;
 use futures_util::stream::Stream;
 
 use crate::helper::mode_from_kind_and_perm;
+#[cfg(feature = "file-lock")]
+use crate::raw::abi::fuse_lk_out;
 use crate::raw::abi::{
     fuse_attr, fuse_attr_out, fuse_bmap_out, fuse_entry_out, fuse_kstatfs, fuse_lseek_out,
     fuse_open_out, fuse_poll_out, fuse_statfs_out, fuse_write_out,
 };
+#[cfg(target_os = "linux")]
+use crate::raw::abi::{fuse_statx, fuse_statx_out, fuse_sx_time};
 #[cfg(feature = "file-lock")]
-use crate::raw::abi::{fuse_file_lock, fuse_lk_out};
-use crate::{FileType, Result, Timestamp};
+use crate::FileLock;
+use crate::{FileType, Inode, Result, Timestamp, FUSE_ROOT_ID};
 
 /// file attributes
+///
+/// # Notes:
+///
+/// there is no `st_dev` in the attributes, kernel reports the device of the fuse connection as
+/// the `st_dev` of all the files in the mount, it can't be set per file. The tools which identify
+/// a file by `(st_dev, st_ino)`, like `du`, `tar` and `find`, take the files with the same inode
+/// as one file, so a filesystem emulating many sub devices, like a view of many bind mounts, must
+/// give every file a unique inode across all of them.
 #[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct FileAttr {
     /// Inode number
@@ -40,17 +55,98 @@ pub struct FileAttr {
     /// Number of hard links
     pub nlink: u32,
     /// User id
+    ///
+    /// # Notes:
+    ///
+    /// the uid and gid in attr are the owner shown by `stat(2)`,
+    /// [`MountOptions::uid`][crate::MountOptions::uid] and
+    /// [`MountOptions::gid`][crate::MountOptions::gid] don't change them. The mount uid and gid
+    /// are the mount owner, without `allow_other` only the mount owner can access the filesystem.
     pub uid: u32,
     /// Group id
     pub gid: u32,
-    /// Rdev
+    /// Rdev, the device number which a character or block device file represents, it is the
+    /// `st_rdev` shown by `stat(2)`
+    ///
+    /// # Notes:
+    ///
+    /// it is only for the device files created by `mknod`, it isn't the `st_dev` of the file,
+    /// which is the device of the fuse connection, set it to 0 for the other kinds of files.
     pub rdev: u32,
     #[cfg(target_os = "macos")]
     /// Flags (macOS only, see chflags(2))
     pub flags: u32,
+    /// Preferred I/O block size of the file, it is the `st_blksize` shown by `stat(2)`
+    ///
+    /// # Notes:
+    ///
+    /// it is a per-file value, not the filesystem block size reported by
+    /// [`statfs`][crate::raw::Filesystem::statfs], the two can differ. Kernel rounds it down to
+    /// a power of two, if it is 0, kernel uses the block size of the mount.
     pub blksize: u32,
 }
 
+impl FileAttr {
+    /// build the [`FileAttr`] of an empty root directory, its inode is
+    /// [`FUSE_ROOT_ID`], the mode is `0o755`, the owner is the user running
+    /// the filesystem and all the times are the current time.
+    ///
+    /// It is the attributes which the default [`getattr`][crate::raw::Filesystem::getattr]
+    /// replies for the root.
+    pub fn new_root() -> Self {
+        let now = SystemTime::now().into();
+
+        Self {
+            ino: FUSE_ROOT_ID,
+            size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            #[cfg(target_os = "macos")]
+            crtime: now,
+            kind: FileType::Directory,
+            perm: 0o755,
+            nlink: 2,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            #[cfg(target_os = "macos")]
+            flags: 0,
+            blksize: 0,
+        }
+    }
+
+    /// build a [`FileAttr`] from the [`Metadata`] of a real file, the inode number is set to
+    /// `ino` instead of the real file's inode number.
+    ///
+    /// This is useful for the passthrough filesystem which stacks on real files.
+    pub fn from_metadata(ino: Inode, meta: &Metadata) -> Self {
+        Self {
+            ino,
+            size: meta.size(),
+            blocks: meta.blocks(),
+            atime: Timestamp::new(meta.atime(), meta.atime_nsec() as _),
+            mtime: Timestamp::new(meta.mtime(), meta.mtime_nsec() as _),
+            ctime: Timestamp::new(meta.ctime(), meta.ctime_nsec() as _),
+            #[cfg(target_os = "macos")]
+            crtime: meta
+                .created()
+                .map(Into::into)
+                .unwrap_or_else(|_| Timestamp::new(0, 0)),
+            kind: meta.file_type().into(),
+            perm: (meta.mode() & 0o7777) as _,
+            nlink: meta.nlink() as _,
+            uid: meta.uid(),
+            gid: meta.gid(),
+            rdev: meta.rdev() as _,
+            #[cfg(target_os = "macos")]
+            flags: std::os::macos::fs::MetadataExt::st_flags(meta),
+            blksize: meta.blksize() as _,
+        }
+    }
+}
+
 impl From<FileAttr> for fuse_attr {
     fn from(attr: FileAttr) -> Self {
         fuse_attr {
@@ -86,18 +182,29 @@ impl From<FileAttr> for fuse_attr {
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// init reply
 pub struct ReplyInit {
-    /// the max write size
+    /// the max write size. The negotiated max write size is the minimum of this value,
+    /// [`MountOptions::max_write`][crate::MountOptions::max_write] and the max size kernel can
+    /// send, a larger write will be split into multiple write requests by kernel.
     pub max_write: NonZeroU32,
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// entry reply.
 pub struct ReplyEntry {
-    /// the attribute TTL.
-    pub ttl: Duration,
+    /// the entry TTL, kernel caches the name of the entry within it without calling lookup.
+    pub entry_ttl: Duration,
+    /// the attribute TTL, kernel uses the `attr` to answer the `stat(2)` within it without
+    /// calling getattr, it can be different from the `entry_ttl`.
+    pub attr_ttl: Duration,
     /// the attribute.
     pub attr: FileAttr,
-    /// the generation.
+    /// the generation, the inode and generation identify a file, like the NFS file handle.
+    ///
+    /// # Notes:
+    ///
+    /// the generation must be the same in all the replies of a live inode, like lookup, create
+    /// and readdirplus, and only changes when the inode is reused after it is forgotten. The
+    /// getattr reply has no generation, so it can't be checked there.
     pub generation: u64,
 }
 
@@ -108,10 +215,10 @@ impl From<ReplyEntry> for fuse_entry_out {
         fuse_entry_out {
             nodeid: attr.ino,
             generation: entry.generation,
-            entry_valid: entry.ttl.as_secs(),
-            attr_valid: entry.ttl.as_secs(),
-            entry_valid_nsec: entry.ttl.subsec_nanos(),
-            attr_valid_nsec: entry.ttl.subsec_nanos(),
+            entry_valid: entry.entry_ttl.as_secs(),
+            attr_valid: entry.attr_ttl.as_secs(),
+            entry_valid_nsec: entry.entry_ttl.subsec_nanos(),
+            attr_valid_nsec: entry.attr_ttl.subsec_nanos(),
             attr: attr.into(),
         }
     }
@@ -144,6 +251,37 @@ pub struct ReplyData {
     pub data: Bytes,
 }
 
+impl ReplyData {
+    /// create a data reply for a read request which requests `size` bytes. If the data is longer
+    /// than `size`, `EIO` will be returned, it helps to find the bug which reads more data than
+    /// requested.
+    pub fn with_exact_len(data: impl Into<Bytes>, size: u32) -> Result<Self> {
+        let data = data.into();
+
+        if data.len() > size as usize {
+            return Err(libc::EIO.into());
+        }
+
+        Ok(Self { data })
+    }
+
+    /// create a data reply for a readlink request, the `target` is replied as is, kernel adds
+    /// the null terminator itself.
+    ///
+    /// # Notes:
+    ///
+    /// the `FUSE_CACHE_SYMLINKS` is enabled when kernel supports it, kernel caches the target in
+    /// the page cache of the link, and the later `readlink(2)` won't call
+    /// [`readlink`][crate::raw::Filesystem::readlink] until the inode is evicted or invalidated
+    /// by [`Notify::invalid_inode`][crate::notify::Notify::invalid_inode], so a computed target
+    /// should not change while the link is cached.
+    pub fn symlink(target: impl AsRef<OsStr>) -> Self {
+        Self {
+            data: Bytes::copy_from_slice(target.as_ref().as_bytes()),
+        }
+    }
+}
+
 impl From<Bytes> for ReplyData {
     fn from(data: Bytes) -> Self {
         Self { data }
@@ -159,7 +297,15 @@ pub struct ReplyOpen {
     ///
     /// if set fh 0, means use stateless IO.
     pub fh: u64,
-    /// the flags.
+    /// the flags, such as [`FOPEN_NONSEEKABLE`][crate::raw::flags::FOPEN_NONSEEKABLE], the
+    /// opendir reply can set [`FOPEN_CACHE_DIR`][crate::raw::flags::FOPEN_CACHE_DIR].
+    ///
+    /// # Notes:
+    ///
+    /// when the data of the file isn't stable in the page cache, like it is generated on read,
+    /// set [`FOPEN_DIRECT_IO`][crate::raw::flags::FOPEN_DIRECT_IO] to make the reads and writes of
+    /// this handle bypass the page cache, it also disables the shared `mmap(2)` of the handle,
+    /// which fails with `ENODEV`, so the mapping never exposes the stale or zero pages.
     pub flags: u32,
 }
 
@@ -180,6 +326,15 @@ pub struct ReplyWrite {
     pub written: u32,
 }
 
+impl ReplyWrite {
+    /// create a short write reply which reports only `written` bytes of the data are written, it
+    /// must not be greater than the length of write data. The `write(2)` returns the `written`
+    /// and the caller retries the remaining data.
+    pub const fn short(written: u32) -> Self {
+        Self { written }
+    }
+}
+
 impl From<ReplyWrite> for fuse_write_out {
     fn from(written: ReplyWrite) -> Self {
         fuse_write_out {
@@ -191,6 +346,12 @@ impl From<ReplyWrite> for fuse_write_out {
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// statfs reply.
+///
+/// # Notes:
+///
+/// the fuse protocol doesn't carry the filesystem id, the `f_fsid` returned by `statfs(2)` is
+/// decided by kernel, the filesystem can't set it. The `namelen` is reported as `f_namemax` of
+/// `statvfs(3)`.
 pub struct ReplyStatFs {
     /// the number of blocks in the filesystem.
     pub blocks: u64,
@@ -202,14 +363,32 @@ pub struct ReplyStatFs {
     pub files: u64,
     /// the number of free inodes.
     pub ffree: u64,
-    /// the block size.
+    /// the filesystem block size, it is the `f_bsize` shown by `statvfs(3)`.
+    ///
+    /// # Notes:
+    ///
+    /// it is the block size of the whole filesystem, the per-file preferred I/O size is
+    /// [`FileAttr::blksize`], they are set independently.
     pub bsize: u32,
     /// the maximum length of file name.
     pub namelen: u32,
-    /// the fragment size.
+    /// the fragment size, `blocks`, `bfree` and `bavail` are counted in this unit. If it is 0,
+    /// `bsize` is used.
     pub frsize: u32,
 }
 
+impl ReplyStatFs {
+    /// the fragment size which `blocks`, `bfree` and `bavail` are counted in, it is `frsize`, or
+    /// `bsize` when `frsize` is 0.
+    pub fn fragment_size(&self) -> u32 {
+        if self.frsize == 0 {
+            self.bsize
+        } else {
+            self.frsize
+        }
+    }
+}
+
 impl From<ReplyStatFs> for fuse_statfs_out {
     fn from(stat_fs: ReplyStatFs) -> Self {
         fuse_statfs_out {
@@ -221,7 +400,7 @@ impl From<ReplyStatFs> for fuse_statfs_out {
                 ffree: stat_fs.ffree,
                 bsize: stat_fs.bsize,
                 namelen: stat_fs.namelen,
-                frsize: stat_fs.frsize,
+                frsize: stat_fs.fragment_size(),
                 _padding: 0,
                 spare: [0; 6],
             },
@@ -236,6 +415,29 @@ pub enum ReplyXAttr {
     Data(Bytes),
 }
 
+impl ReplyXAttr {
+    /// create a listxattr reply of the xattr `names`, each name is terminated by null. The size
+    /// probe and the `ERANGE` check use the length of the whole list, the empty `names` is an
+    /// empty list.
+    ///
+    /// # Notes:
+    ///
+    /// the names should not contain null, otherwise kernel takes it as more names.
+    pub fn names<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        let mut data = vec![];
+        for name in names {
+            data.extend_from_slice(name.as_ref().as_bytes());
+            data.push(0);
+        }
+
+        Self::Data(data.into())
+    }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// directory entry.
 pub struct DirectoryEntry {
@@ -251,6 +453,10 @@ pub struct DirectoryEntry {
 
 /// readdir reply.
 pub struct ReplyDirectory<S: Stream<Item = Result<DirectoryEntry>>> {
+    /// the parent inode of the directory, when
+    /// [`MountOptions::synthesize_dot_entries`][crate::MountOptions::synthesize_dot_entries] is
+    /// enabled and it is set, the "." and ".." entries are prepended before `entries`.
+    pub parent: Option<Inode>,
     pub entries: S,
 }
 
@@ -259,39 +465,33 @@ impl<S: Stream<Item = Result<DirectoryEntry>> + std::fmt::Debug> std::fmt::Debug
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ReplyDirectory")
+            .field("parent", &self.parent)
             .field("entries", &self.entries)
             .finish()
     }
 }
 
 #[cfg(feature = "file-lock")]
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 /// file lock reply.
 pub struct ReplyLock {
-    /// starting offset for lock.
-    pub start: u64,
-    /// end offset for lock.
-    pub end: u64,
-    /// type of lock, such as: [`F_RDLCK`], [`F_WRLCK`] and [`F_UNLCK`]
-    ///
-    /// [`F_RDLCK`]: libc::F_RDLCK
-    /// [`F_WRLCK`]: libc::F_WRLCK
-    /// [`F_UNLCK`]: libc::F_UNLCK
-    pub r#type: u32,
-    /// PID of process blocking our lock
-    pub pid: u32,
+    /// the lock which conflicts with the requested lock, or the requested lock with
+    /// [`LockKind::Unlock`][crate::LockKind::Unlock] if no lock conflicts.
+    pub lock: FileLock,
+}
+
+#[cfg(feature = "file-lock")]
+impl From<FileLock> for ReplyLock {
+    fn from(lock: FileLock) -> Self {
+        Self { lock }
+    }
 }
 
 #[cfg(feature = "file-lock")]
 impl From<ReplyLock> for fuse_lk_out {
-    fn from(lock: ReplyLock) -> Self {
+    fn from(reply: ReplyLock) -> Self {
         fuse_lk_out {
-            lk: fuse_file_lock {
-                start: lock.start,
-                end: lock.end,
-                r#type: lock.r#type,
-                pid: lock.pid,
-            },
+            lk: reply.lock.to_raw(),
         }
     }
 }
@@ -303,7 +503,7 @@ pub struct ReplyCreated {
     pub ttl: Duration,
     /// the attribute of file.
     pub attr: FileAttr,
-    /// the generation of file.
+    /// the generation of file, see [`ReplyEntry::generation`].
     pub generation: u64,
     /// the file handle.
     pub fh: u64,
@@ -377,7 +577,7 @@ impl From<ReplyPoll> for fuse_poll_out {
 pub struct DirectoryEntryPlus {
     /// the entry inode.
     pub inode: u64,
-    /// the entry generation.
+    /// the entry generation, see [`ReplyEntry::generation`].
     pub generation: u64,
     /// the entry kind.
     pub kind: FileType,
@@ -389,12 +589,27 @@ pub struct DirectoryEntryPlus {
     pub attr: FileAttr,
     /// the entry TTL.
     pub entry_ttl: Duration,
-    /// the attribute TTL.
+    /// the attribute TTL, kernel uses the `attr` to answer the `stat(2)` within it without
+    /// calling getattr. Set it to zero when the `attr` may be stale.
     pub attr_ttl: Duration,
 }
 
+impl DirectoryEntryPlus {
+    /// set the `attr_ttl` to zero, so kernel only primes the entry and calls getattr to fetch the
+    /// attribute when it is needed.
+    pub fn without_attr_cache(mut self) -> Self {
+        self.attr_ttl = Duration::ZERO;
+
+        self
+    }
+}
+
 /// the readdirplus reply.
 pub struct ReplyDirectoryPlus<S: Stream<Item = Result<DirectoryEntryPlus>>> {
+    /// the parent inode of the directory, when
+    /// [`MountOptions::synthesize_dot_entries`][crate::MountOptions::synthesize_dot_entries] is
+    /// enabled and it is set, the "." and ".." entries are prepended before `entries`.
+    pub parent: Option<Inode>,
     pub entries: S,
 }
 
@@ -403,6 +618,7 @@ impl<S: Stream<Item = Result<DirectoryEntryPlus>> + std::fmt::Debug> std::fmt::D
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ReplyDirectoryPlus")
+            .field("parent", &self.parent)
             .field("entries", &self.entries)
             .finish()
     }
@@ -423,6 +639,67 @@ impl From<ReplyLSeek> for fuse_lseek_out {
     }
 }
 
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+/// statx reply.
+pub struct ReplyStatx {
+    /// the attribute TTL.
+    pub ttl: Duration,
+    /// the attribute.
+    pub attr: FileAttr,
+    /// the creation time, it is valid when `mask` has `STATX_BTIME`.
+    pub btime: Timestamp,
+    /// the mask of the valid fields, like `libc::STATX_BASIC_STATS` and `libc::STATX_BTIME`.
+    /// Kernel only updates its attr cache when all basic stats are valid.
+    pub mask: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl From<Timestamp> for fuse_sx_time {
+    fn from(ts: Timestamp) -> Self {
+        fuse_sx_time {
+            tv_sec: ts.sec,
+            tv_nsec: ts.nsec,
+            _reserved: 0,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<ReplyStatx> for fuse_statx_out {
+    fn from(statx: ReplyStatx) -> Self {
+        let attr = statx.attr;
+        let rdev = attr.rdev as libc::dev_t;
+
+        fuse_statx_out {
+            attr_valid: statx.ttl.as_secs(),
+            attr_valid_nsec: statx.ttl.subsec_nanos(),
+            flags: 0,
+            _spare: [0; 2],
+            stat: fuse_statx {
+                mask: statx.mask,
+                blksize: attr.blksize,
+                nlink: attr.nlink,
+                uid: attr.uid,
+                gid: attr.gid,
+                // the file type and permission bits always fit in 16 bits
+                mode: mode_from_kind_and_perm(attr.kind, attr.perm) as u16,
+                ino: attr.ino,
+                size: attr.size,
+                blocks: attr.blocks,
+                atime: attr.atime.into(),
+                btime: statx.btime.into(),
+                ctime: attr.ctime.into(),
+                mtime: attr.mtime.into(),
+                // Safety: major and minor only do the bit operations
+                rdev_major: unsafe { libc::major(rdev) },
+                rdev_minor: unsafe { libc::minor(rdev) },
+                ..Default::default()
+            },
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 /// copy_file_range reply.
 pub struct ReplyCopyFileRange {
@@ -438,3 +715,57 @@ impl From<ReplyCopyFileRange> for fuse_write_out {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xattr_names() {
+        assert_eq!(
+            ReplyXAttr::names(Vec::<&OsStr>::new()),
+            ReplyXAttr::Data(Bytes::new())
+        );
+        assert_eq!(
+            ReplyXAttr::names(["user.a"]),
+            ReplyXAttr::Data(Bytes::from_static(b"user.a\0"))
+        );
+        assert_eq!(
+            ReplyXAttr::names([OsString::from("user.a"), OsString::from("security.b")]),
+            ReplyXAttr::Data(Bytes::from_static(b"user.a\0security.b\0"))
+        );
+    }
+
+    #[test]
+    fn file_attr_from_metadata() {
+        use std::fs::{self, Permissions};
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"hello").unwrap();
+        fs::set_permissions(file.path(), Permissions::from_mode(0o640)).unwrap();
+
+        let meta = fs::metadata(file.path()).unwrap();
+        let attr = FileAttr::from_metadata(100, &meta);
+
+        assert_eq!(attr.ino, 100);
+        assert_ne!(meta.ino(), 100);
+        assert_eq!(attr.size, 5);
+        assert_eq!(attr.kind, FileType::RegularFile);
+        assert_eq!(attr.perm, 0o640);
+        assert_eq!(attr.nlink, 1);
+        assert_eq!(attr.uid, meta.uid());
+        assert_eq!(attr.gid, meta.gid());
+        assert_eq!(
+            attr.mtime,
+            Timestamp::new(meta.mtime(), meta.mtime_nsec() as _)
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let attr = FileAttr::from_metadata(101, &fs::metadata(dir.path()).unwrap());
+
+        assert_eq!(attr.ino, 101);
+        assert_eq!(attr.kind, FileType::Directory);
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(target_os = "linux")]
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+
 use crate::raw::abi::fuse_in_header;
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -10,9 +13,53 @@ pub struct Request {
     /// the gid of this request.
     pub gid: u32,
     /// the pid of this request.
+    ///
+    /// # Notes:
+    ///
+    /// on Linux, the pid is translated into the pid namespace of the process which opened the
+    /// fuse device, not the pid namespace of the caller, if the caller is in a child pid
+    /// namespace, like a container, it sees itself as a different pid. If the caller is not
+    /// visible in the pid namespace of the fuse device opener, the pid is 0. Use
+    /// [`namespace_pids`][Request::namespace_pids] to get the pid seen by the caller.
     pub pid: u32,
 }
 
+impl Request {
+    /// get the pids of the caller in all the nested pid namespaces it belongs to, from the pid
+    /// namespace of `/proc` to the caller's own pid namespace, so the last one is the pid seen
+    /// by the caller itself. It is read from the `NSpid` line of `/proc/<pid>/status`.
+    ///
+    /// # Notes:
+    ///
+    /// this is best-effort, the caller may exit and the pid may be reused by another process
+    /// after the request is sent. The `/proc` should be mounted by the pid namespace of the
+    /// process which opened the fuse device, or the `pid` refers to another process.
+    #[cfg(target_os = "linux")]
+    pub fn namespace_pids(&self) -> IoResult<Vec<u32>> {
+        if self.pid == 0 {
+            return Err(IoError::new(
+                ErrorKind::NotFound,
+                "the caller is not visible in the pid namespace",
+            ));
+        }
+
+        let status = std::fs::read_to_string(format!("/proc/{}/status", self.pid))?;
+
+        let ns_pids = status
+            .lines()
+            .find_map(|line| line.strip_prefix("NSpid:"))
+            .ok_or_else(|| IoError::new(ErrorKind::Unsupported, "NSpid is not supported"))?;
+
+        ns_pids
+            .split_whitespace()
+            .map(|pid| {
+                pid.parse()
+                    .map_err(|err| IoError::new(ErrorKind::InvalidData, err))
+            })
+            .collect()
+    }
+}
+
 impl From<&fuse_in_header> for Request {
     fn from(header: &fuse_in_header) -> Self {
         Self {
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use std::ffi::OsStr;
 use std::ffi::OsString;
@@ -6,21 +7,26 @@ use std::future::Future;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
 use std::io::Result as IoResult;
-use std::num::NonZeroU32;
-use std::os::fd::AsFd;
-use std::os::unix::ffi::OsStrExt;
+use std::mem;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::ops::{ControlFlow, Deref};
+use std::os::fd::{AsFd, OwnedFd};
 use std::os::unix::ffi::OsStringExt;
 use std::os::unix::io::AsRawFd;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::pin::{pin, Pin};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_fs::read_dir;
 #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
 use async_global_executor::{self as task, Task as JoinHandle};
+#[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+use async_lock::{Semaphore, SemaphoreGuardArc as Permit};
 #[cfg(all(
     target_os = "linux",
     not(feature = "tokio-runtime"),
@@ -28,13 +34,12 @@ use async_global_executor::{self as task, Task as JoinHandle};
     feature = "unprivileged"
 ))]
 use async_process::Command;
-use bincode::Options;
 use bytes::Bytes;
-use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures_util::future::{Either, FutureExt};
+use futures_channel::oneshot;
+use futures_util::future::{self, Either, Fuse, FutureExt, Shared};
 use futures_util::select;
 use futures_util::sink::{Sink, SinkExt};
-use futures_util::stream::StreamExt;
+use futures_util::stream::{self, StreamExt};
 use nix::mount;
 #[cfg(any(target_os = "freebsd", target_os = "macos"))]
 use nix::mount::MntFlags;
@@ -46,24 +51,193 @@ use nix::mount::MntFlags;
 ))]
 use tokio::process::Command;
 #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+use tokio::sync::{OwnedSemaphorePermit as Permit, Semaphore};
+#[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
 use tokio::task::JoinHandle;
 #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
 use tokio::{fs::read_dir, task};
-use tracing::{debug, debug_span, error, instrument, warn, Instrument, Span};
+use tracing::{debug, debug_span, error, info, instrument, warn, Instrument, Span};
 
 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
 use crate::find_fusermount3;
 use crate::helper::*;
+use crate::mount_options::DEFAULT_MAX_CONCURRENT_NORMAL_REQUESTS;
 use crate::notify::Notify;
 use crate::raw::abi::*;
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 use crate::raw::connection::FuseConnection;
 use crate::raw::filesystem::Filesystem;
-use crate::raw::reply::ReplyXAttr;
+use crate::raw::flags::{GetattrFlags, ReleaseFlags};
+use crate::raw::protocol::{
+    self, CreateIn, DecodeError, GetxattrIn, LinkIn, MkdirIn, MknodIn, NotifyReplyIn, RenameIn,
+    SetxattrIn, SymlinkIn, WriteIn,
+};
+use crate::raw::reply::{DirectoryEntry, FileAttr, ReplyXAttr};
 use crate::raw::request::Request;
-use crate::raw::FuseData;
-use crate::MountOptions;
-use crate::{Errno, SetAttr};
+use crate::raw::{
+    reply_buffer_into_bytes, response_channel, FuseData, ReplyBuffer, Response, ResponseReceiver,
+    ResponseSender,
+};
+#[cfg(feature = "file-lock")]
+use crate::FileLock;
+use crate::{Errno, FileType, SetAttr, FUSE_ROOT_ID};
+use crate::{MountOptions, Priority};
+
+/// the write which is at least `1 / ZERO_COPY_WRITE_DIVISOR` of the request buffer takes the
+/// buffer instead of copying its data, so the buffer kept by the filesystem is at most 4 times of
+/// the data.
+const ZERO_COPY_WRITE_DIVISOR: usize = 4;
+
+/// mount the filesystem with root permission, it is a shortcut of
+/// `Session::new(mount_options).mount(fs, mount_path)`, await the returned [`MountHandle`] to wait
+/// the filesystem is unmounted. Use [`Session`] for the advanced usages, such as
+/// [`Session::with_shared`].
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use std::num::NonZeroU32;
+/// #
+/// # use fuse3::raw::prelude::*;
+/// # use fuse3::Result;
+/// # use futures_util::stream::Empty;
+/// #
+/// # struct Fs;
+/// #
+/// # impl Filesystem for Fs {
+/// #     type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+/// #     type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+/// #
+/// #     async fn init(&self, _req: Request) -> Result<ReplyInit> {
+/// #         Ok(ReplyInit {
+/// #             max_write: NonZeroU32::new(128 * 1024).unwrap(),
+/// #         })
+/// #     }
+/// #
+/// #     async fn destroy(&self, _req: Request) {}
+/// #
+/// #     #[cfg(feature = "file-lock")]
+/// #     async fn getlk(
+/// #         &self,
+/// #         _req: Request,
+/// #         _inode: u64,
+/// #         _fh: u64,
+/// #         _lock: fuse3::FileLock,
+/// #     ) -> Result<ReplyLock> {
+/// #         Err(libc::ENOSYS.into())
+/// #     }
+/// #
+/// #     #[cfg(feature = "file-lock")]
+/// #     async fn setlk(
+/// #         &self,
+/// #         _req: Request,
+/// #         _inode: u64,
+/// #         _fh: u64,
+/// #         _lock: fuse3::FileLock,
+/// #         _block: bool,
+/// #     ) -> Result<()> {
+/// #         Err(libc::ENOSYS.into())
+/// #     }
+/// # }
+/// #
+/// use fuse3::MountOptions;
+///
+/// async fn run() -> std::io::Result<()> {
+///     let mount_handle = fuse3::raw::mount(Fs, "/mnt/fs", MountOptions::default()).await?;
+///
+///     mount_handle.await
+/// }
+/// ```
+pub async fn mount<FS, P>(
+    fs: FS,
+    mount_path: P,
+    mount_options: MountOptions,
+) -> IoResult<MountHandle>
+where
+    FS: Filesystem + Send + Sync + 'static,
+    P: AsRef<Path>,
+{
+    Session::new(mount_options).mount(fs, mount_path).await
+}
+
+/// mount the filesystem without root permission, it is a shortcut of
+/// `Session::new(mount_options).mount_with_unprivileged(fs, mount_path)`, await the returned
+/// [`MountHandle`] to wait the filesystem is unmounted.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use std::num::NonZeroU32;
+/// #
+/// # use fuse3::raw::prelude::*;
+/// # use fuse3::Result;
+/// # use futures_util::stream::Empty;
+/// #
+/// # struct Fs;
+/// #
+/// # impl Filesystem for Fs {
+/// #     type DirEntryStream<'a> = Empty<Result<DirectoryEntry>>;
+/// #     type DirEntryPlusStream<'a> = Empty<Result<DirectoryEntryPlus>>;
+/// #
+/// #     async fn init(&self, _req: Request) -> Result<ReplyInit> {
+/// #         Ok(ReplyInit {
+/// #             max_write: NonZeroU32::new(128 * 1024).unwrap(),
+/// #         })
+/// #     }
+/// #
+/// #     async fn destroy(&self, _req: Request) {}
+/// #
+/// #     #[cfg(feature = "file-lock")]
+/// #     async fn getlk(
+/// #         &self,
+/// #         _req: Request,
+/// #         _inode: u64,
+/// #         _fh: u64,
+/// #         _lock: fuse3::FileLock,
+/// #     ) -> Result<ReplyLock> {
+/// #         Err(libc::ENOSYS.into())
+/// #     }
+/// #
+/// #     #[cfg(feature = "file-lock")]
+/// #     async fn setlk(
+/// #         &self,
+/// #         _req: Request,
+/// #         _inode: u64,
+/// #         _fh: u64,
+/// #         _lock: fuse3::FileLock,
+/// #         _block: bool,
+/// #     ) -> Result<()> {
+/// #         Err(libc::ENOSYS.into())
+/// #     }
+/// # }
+/// #
+/// use fuse3::MountOptions;
+///
+/// async fn run() -> std::io::Result<()> {
+///     let mount_handle =
+///         fuse3::raw::mount_with_unprivileged(Fs, "/mnt/fs", MountOptions::default()).await?;
+///
+///     mount_handle.await
+/// }
+/// ```
+#[cfg(any(
+    all(target_os = "linux", feature = "unprivileged"),
+    all(target_os = "freebsd", feature = "unprivileged"),
+    target_os = "macos"
+))]
+pub async fn mount_with_unprivileged<FS, P>(
+    fs: FS,
+    mount_path: P,
+    mount_options: MountOptions,
+) -> IoResult<MountHandle>
+where
+    FS: Filesystem + Send + Sync + 'static,
+    P: AsRef<Path>,
+{
+    Session::new(mount_options)
+        .mount_with_unprivileged(fs, mount_path)
+        .await
+}
 
 /// A Future which returns when a file system is unmounted
 ///
@@ -82,6 +256,156 @@ impl MountHandle {
             .inner_unmount()
             .await
     }
+
+    /// stop reading the new requests, wait the in flight requests done, then unmount the
+    /// filesystem, so the clean shutdown doesn't fail the requests which are being handled, the
+    /// [`unmount`][MountHandle::unmount] drops their replies instead.
+    ///
+    /// # Notes:
+    ///
+    /// if the in flight requests aren't done within `timeout`, like a request which never
+    /// completes, it stops waiting them and unmounts, the replies of them are dropped. The
+    /// requests sent by kernel after draining started are not read, they fail when unmounting,
+    /// such as the flush of a `close(2)`, so the unmount fails with `EBUSY` like
+    /// [`unmount`][MountHandle::unmount] if the files are still opened.
+    pub async fn drain(mut self, timeout: Duration) -> IoResult<()> {
+        self.inner
+            .take()
+            .expect("drain call twice")
+            .inner_drain(timeout)
+            .await
+    }
+
+    /// wait until the `FUSE_INIT` request is handled, then the filesystem is ready to serve the
+    /// requests. It returns an error if the init failed or the session exited before ready.
+    ///
+    /// The mount methods already wait it before returning the handle, so it resolves immediately
+    /// for a mounted handle.
+    pub fn wait_ready(&self) -> impl Future<Output = IoResult<()>> + Send + 'static {
+        let ready = self
+            .inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .ready
+            .clone();
+
+        async move {
+            ready.await.map_err(|_| {
+                IoError::new(
+                    ErrorKind::ConnectionAborted,
+                    "session exited before the filesystem is ready",
+                )
+            })
+        }
+    }
+
+    /// wait the `FUSE_INIT` request handled, if the init failed, unmount the mount path when
+    /// `unmount_on_error` is true and return the init error, so the mount methods fail fast.
+    async fn wait_init(mut self, unmount_on_error: bool) -> IoResult<Self> {
+        if self.wait_ready().await.is_ok() {
+            return Ok(self);
+        }
+
+        let mut inner = self.inner.take().expect("inner should be Some()");
+
+        // the session task exits with the init error
+        #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+        let result = (&mut inner.task).await;
+        #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+        let result = (&mut inner.task).await.unwrap();
+
+        let err = result.err().unwrap_or_else(|| {
+            IoError::new(
+                ErrorKind::ConnectionAborted,
+                "session exited before the filesystem is ready",
+            )
+        });
+
+        error!("init filesystem failed: {}", err);
+
+        if !unmount_on_error {
+            return Err(err);
+        }
+
+        if let Err(unmount_err) = inner.umount().await {
+            error!("unmount after init failed: {}", unmount_err);
+        }
+
+        Err(err)
+    }
+
+    /// get the mount path of the filesystem, it is the path given when mounting, and it is
+    /// unmounted by [`unmount`][MountHandle::unmount] or dropping the handle.
+    pub fn mount_path(&self) -> &Path {
+        &self
+            .inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .mount_path
+    }
+
+    /// get the request counters of the session.
+    pub fn stats(&self) -> SessionStats {
+        self.inner
+            .as_ref()
+            .expect("inner should be Some()")
+            .counters
+            .snapshot()
+    }
+
+    /// duplicate the fd of the fuse connection, such as passing it to a supervisor process, so a
+    /// new session can be attached to the connection by [`Session::attach`] after this process
+    /// crashes. It returns `NotConnected` if the connection is already closed.
+    ///
+    /// # Notes:
+    ///
+    /// when this process exits without unmounting, the returned fd keeps the connection alive,
+    /// the requests are queued in the kernel until a new session is attached. It doesn't prevent
+    /// the mount path being unmounted.
+    pub fn try_clone_fd(&self) -> IoResult<OwnedFd> {
+        let inner = self.inner.as_ref().expect("inner should be Some()");
+
+        inner
+            .fuse_connection
+            .upgrade()
+            .ok_or_else(|| IoError::new(ErrorKind::NotConnected, "fuse connection is closed"))?
+            .as_fd()
+            .try_clone_to_owned()
+    }
+
+    /// abort the fuse connection by the fuse control filesystem, all pending requests will be
+    /// terminated and the dispatch loop will exit, the mount point still need to be unmounted.
+    ///
+    /// This is useful when the filesystem is deadlocked and can't be unmounted normally.
+    ///
+    /// # Notes:
+    ///
+    /// this is Linux only, and requires the fuse control filesystem mounted at
+    /// `/sys/fs/fuse/connections` and the permission to write it.
+    #[cfg(target_os = "linux")]
+    pub fn abort(&self) -> IoResult<()> {
+        let connection_id = self.connection_id()?;
+
+        std::fs::write(
+            format!("/sys/fs/fuse/connections/{connection_id}/abort"),
+            b"1",
+        )
+    }
+
+    /// get the id of the fuse connection, which is the minor number of the mount point `st_dev`,
+    /// the kernel side stats of the connection, like `waiting` and `max_background`, are in
+    /// `/sys/fs/fuse/connections/<id>`, so the logs can be correlated with them.
+    ///
+    /// # Notes:
+    ///
+    /// this is Linux only, the id is found in `/proc/self/mountinfo` instead of calling `stat` on
+    /// the mount point, so it won't block when the filesystem is deadlocked.
+    #[cfg(target_os = "linux")]
+    pub fn connection_id(&self) -> IoResult<u64> {
+        let inner = self.inner.as_ref().expect("inner should be Some()");
+
+        connection_id(&inner.mount_path)
+    }
 }
 
 impl Drop for MountHandle {
@@ -104,11 +428,184 @@ impl Drop for MountHandle {
     }
 }
 
+/// the request counters of a session, it is got by [`MountHandle::stats`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SessionStats {
+    /// the number of the requests which are being handled.
+    pub in_flight: usize,
+    /// the max number of the requests which were handled at the same time.
+    pub peak_in_flight: usize,
+    /// the number of the requests which have been handled.
+    pub total_handled: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    total_handled: AtomicU64,
+    // the dispatch waits the in flight requests before destroying the filesystem when draining
+    draining: AtomicBool,
+    idle: async_notify::Notify,
+}
+
+impl Counters {
+    fn snapshot(&self) -> SessionStats {
+        SessionStats {
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+            total_handled: self.total_handled.load(Ordering::Relaxed),
+        }
+    }
+
+    /// wait until there is no in flight request, it returns early when the draining is stopped.
+    async fn wait_idle(&self) {
+        while self.draining.load(Ordering::Acquire) && self.in_flight.load(Ordering::Acquire) > 0 {
+            self.idle.notified().await;
+        }
+    }
+
+    fn stop_draining(&self) {
+        self.draining.store(false, Ordering::Release);
+        self.idle.notify();
+    }
+}
+
+/// a request which is being handled, it is counted until dropped.
+struct InFlight(Arc<Counters>);
+
+impl InFlight {
+    fn new(counters: Arc<Counters>) -> Self {
+        let in_flight = counters.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        counters
+            .peak_in_flight
+            .fetch_max(in_flight, Ordering::Relaxed);
+
+        Self(counters)
+    }
+}
+
+/// the lookup counts and generations of the inodes replied to kernel, it is enabled by
+/// [`MountOptions::track_lookups`].
+#[derive(Debug, Default)]
+struct LookupTracker(Mutex<HashMap<u64, Lookups>>);
+
+#[derive(Debug)]
+struct Lookups {
+    count: u64,
+    generation: u64,
+}
+
+impl LookupTracker {
+    fn lookup(&self, inode: u64, generation: u64) {
+        // the negative entry and the root inode are never forgotten
+        if inode == 0 || inode == FUSE_ROOT_ID {
+            return;
+        }
+
+        let mut lookups = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let lookups = lookups.entry(inode).or_insert(Lookups {
+            count: 0,
+            generation,
+        });
+
+        if lookups.generation != generation {
+            warn!(
+                inode,
+                generation,
+                previous_generation = lookups.generation,
+                "generation of the inode is changed before it is forgotten"
+            );
+
+            lookups.generation = generation;
+        }
+
+        lookups.count += 1;
+    }
+
+    fn forget(&self, inode: u64, nlookup: u64) {
+        let mut lookups = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = lookups.get(&inode).map_or(0, |lookups| lookups.count);
+
+        if nlookup > count {
+            warn!(
+                inode,
+                nlookup,
+                lookups = count,
+                "forget is more than the lookups of the inode"
+            );
+        }
+
+        if nlookup >= count {
+            lookups.remove(&inode);
+        } else if let Some(lookups) = lookups.get_mut(&inode) {
+            lookups.count -= nlookup;
+        }
+    }
+
+    fn report(&self) {
+        let lookups = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !lookups.is_empty() {
+            let counts = lookups
+                .iter()
+                .map(|(inode, lookups)| (*inode, lookups.count))
+                .collect::<HashMap<_, _>>();
+
+            info!(
+                inodes = lookups.len(),
+                "the inodes are not forgotten before unmounting, inode lookups {:?}", counts
+            );
+        }
+    }
+}
+
+#[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+fn new_semaphore(max: usize) -> Semaphore {
+    Semaphore::new(max)
+}
+
+#[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+fn new_semaphore(max: usize) -> Semaphore {
+    Semaphore::new(max.min(Semaphore::MAX_PERMITS))
+}
+
+/// wait a permit of the request when it is limited, such as by
+/// [`MountOptions::max_concurrent_bulk_ops`], the permit is held until the request is replied.
+async fn acquire_permit(semaphore: Option<Arc<Semaphore>>) -> Option<Permit> {
+    let semaphore = semaphore?;
+
+    #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+    let permit = semaphore.acquire_arc().await;
+    #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+    let permit = semaphore
+        .acquire_owned()
+        .await
+        .expect("request semaphore is never closed");
+
+    Some(permit)
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.total_handled.fetch_add(1, Ordering::Relaxed);
+
+        if self.0.in_flight.fetch_sub(1, Ordering::AcqRel) == 1
+            && self.0.draining.load(Ordering::Acquire)
+        {
+            self.0.idle.notify();
+        }
+    }
+}
+
 #[derive(Debug)]
 struct MountHandleInner {
     task: JoinHandle<IoResult<()>>,
     mount_path: PathBuf,
     destroy_notify: Arc<async_notify::Notify>,
+    ready: Shared<oneshot::Receiver<()>>,
+    counters: Arc<Counters>,
+    fuse_connection: Weak<FuseConnection>,
     #[cfg(any(
         all(target_os = "linux", feature = "unprivileged"),
         target_os = "macos"
@@ -117,29 +614,92 @@ struct MountHandleInner {
 }
 
 impl MountHandleInner {
-    async fn inner_unmount(self) -> IoResult<()> {
+    async fn inner_unmount(mut self) -> IoResult<()> {
         self.destroy_notify.notify();
 
+        // wait destroy done
         #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
-        {
-            // wait destroy done
-            self.task.await?;
+        (&mut self.task).await?;
+        #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+        (&mut self.task).await.unwrap()?;
+
+        self.umount().await
+    }
+
+    async fn inner_drain(mut self, timeout: Duration) -> IoResult<()> {
+        self.counters.draining.store(true, Ordering::Release);
+        self.destroy_notify.notify();
+
+        #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+        let timer = async_io::Timer::after(timeout);
+        #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+        let timer = tokio::time::sleep(timeout);
+
+        // wait the in flight requests done and destroy done
+        let result = match future::select(&mut self.task, pin!(timer)).await {
+            Either::Left((result, _)) => result,
+
+            Either::Right(_) => {
+                warn!(
+                    in_flight = self.counters.in_flight.load(Ordering::Relaxed),
+                    "drain timeout, stop waiting the in flight requests"
+                );
+
+                self.counters.stop_draining();
+
+                (&mut self.task).await
+            }
+        };
+
+        #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+        result?;
+        #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
+        result.unwrap()?;
+
+        self.umount().await
+    }
+
+    /// unmount the mount path, the session task must be finished.
+    async fn umount(self) -> IoResult<()> {
+        MountPoint {
+            path: self.mount_path,
+            #[cfg(any(
+                all(target_os = "linux", feature = "unprivileged"),
+                target_os = "macos"
+            ))]
+            unprivileged: self.unprivileged,
+        }
+        .unmount()
+        .await
+    }
+}
+
+/// the mount path and how to unmount it.
+#[derive(Debug)]
+struct MountPoint {
+    path: PathBuf,
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        target_os = "macos"
+    ))]
+    unprivileged: bool,
+}
 
+impl MountPoint {
+    async fn unmount(self) -> IoResult<()> {
+        #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
+        {
             // TODO: freebsd mount is unprivileged, then unmount is unprivileged too?
             #[cfg(target_os = "freebsd")]
             {
-                task::spawn_blocking(move || {
-                    mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
-                })
-                .await?;
+                task::spawn_blocking(move || mount::unmount(&self.path, MntFlags::MNT_SYNCHRONOUS))
+                    .await?;
             }
 
             #[cfg(target_os = "macos")]
             {
-                task::spawn_blocking(move || {
-                    mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
-                })
-                .await?;
+                task::spawn_blocking(move || mount::unmount(&self.path, MntFlags::MNT_SYNCHRONOUS))
+                    .await?;
             }
 
             #[cfg(target_os = "linux")]
@@ -148,7 +708,7 @@ impl MountHandleInner {
                 if self.unprivileged {
                     let binary_path = find_fusermount3()?;
                     let mut child = Command::new(binary_path)
-                        .args([OsStr::new("-u"), self.mount_path.as_os_str()])
+                        .args([OsStr::new("-u"), self.path.as_os_str()])
                         .spawn()?;
                     if !child.status().await?.success() {
                         return Err(IoError::new(
@@ -160,31 +720,24 @@ impl MountHandleInner {
                     return Ok(());
                 }
 
-                task::spawn_blocking(move || mount::umount(&self.mount_path)).await?;
+                task::spawn_blocking(move || mount::umount(&self.path)).await?;
             }
         }
 
         #[cfg(all(not(feature = "async-io-runtime"), feature = "tokio-runtime"))]
         {
-            // wait destroy done
-            self.task.await.unwrap()?;
-
             // TODO: freebsd mount is unprivileged, then unmount is unprivileged too?
             #[cfg(target_os = "freebsd")]
             {
-                task::spawn_blocking(move || {
-                    mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
-                })
-                .await
-                .unwrap()?;
+                task::spawn_blocking(move || mount::unmount(&self.path, MntFlags::MNT_SYNCHRONOUS))
+                    .await
+                    .unwrap()?;
             }
             #[cfg(target_os = "macos")]
             {
-                task::spawn_blocking(move || {
-                    mount::unmount(&self.mount_path, MntFlags::MNT_SYNCHRONOUS)
-                })
-                .await
-                .unwrap()?;
+                task::spawn_blocking(move || mount::unmount(&self.path, MntFlags::MNT_SYNCHRONOUS))
+                    .await
+                    .unwrap()?;
             }
 
             #[cfg(target_os = "linux")]
@@ -193,7 +746,7 @@ impl MountHandleInner {
                 if self.unprivileged {
                     let binary_path = find_fusermount3()?;
                     let mut child = Command::new(binary_path)
-                        .args([OsStr::new("-u"), self.mount_path.as_os_str()])
+                        .args([OsStr::new("-u"), self.path.as_os_str()])
                         .spawn()?;
                     if !child.wait().await?.success() {
                         return Err(IoError::new(
@@ -205,7 +758,7 @@ impl MountHandleInner {
                     return Ok(());
                 }
 
-                task::spawn_blocking(move || mount::umount(&self.mount_path))
+                task::spawn_blocking(move || mount::umount(&self.path))
                     .await
                     .unwrap()?;
             }
@@ -236,11 +789,28 @@ impl Future for MountHandle {
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 /// fuse filesystem session, inode based.
+///
+/// # Notes:
+///
+/// the session emits the `info` level tracing events named `fuse3.mounted`, `fuse3.initialized`
+/// and `fuse3.destroyed` in order, and the `error` level event `fuse3.error` if it exits with an
+/// error, a tracing layer can hook them for the mount lifecycle, such as notifying the service
+/// manager.
 pub struct Session<FS> {
     fuse_connection: Option<Arc<FuseConnection>>,
     filesystem: Option<Arc<FS>>,
-    response_sender: UnboundedSender<FuseData>,
-    response_receiver: Option<UnboundedReceiver<FuseData>>,
+    response_sender: ResponseSender,
+    response_receiver: Option<ResponseReceiver>,
+    ready_sender: Option<oneshot::Sender<()>>,
+    ready: Shared<oneshot::Receiver<()>>,
+    counters: Arc<Counters>,
+    lookups: Option<Arc<LookupTracker>>,
+    // limit the read, readdir and readdirplus requests which are handled at the same time
+    bulk_ops: Option<Arc<Semaphore>>,
+    // the session is attached to an existing connection, the kernel won't send the `FUSE_INIT`
+    attached: bool,
+    // limit the normal priority requests which are handled at the same time
+    normal_requests: Option<Arc<Semaphore>>,
     mount_options: MountOptions,
 }
 
@@ -267,15 +837,51 @@ impl Debug for ReadResult {
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
 impl<FS> Session<FS> {
+    /// new a fuse filesystem session with a shared filesystem, mount it by
+    /// [`mount_shared`][Session::mount_shared] or
+    /// [`mount_shared_with_unprivileged`][Session::mount_shared_with_unprivileged].
+    ///
+    /// It is used to serve one filesystem at multiple mount paths, each mount path needs its own
+    /// session, create them with the clones of the same `Arc`, so the filesystem can share its
+    /// cache between the mount paths.
+    ///
+    /// # Notes:
+    ///
+    /// [`init`][Filesystem::init] and [`destroy`][Filesystem::destroy] are called for each mount
+    /// path, and the inodes of the mount paths are not distinguished, the filesystem should use
+    /// the same inode for the same file in all mount paths.
+    pub fn with_shared(fs: Arc<FS>, mount_options: MountOptions) -> Self {
+        let mut session = Self::new(mount_options);
+        session.filesystem.replace(fs);
+
+        session
+    }
+
     /// new a fuse filesystem session.
     pub fn new(mount_options: MountOptions) -> Self {
-        let (sender, receiver) = unbounded();
+        let (sender, receiver) = response_channel();
+        let (ready_sender, ready) = oneshot::channel();
 
         Self {
             fuse_connection: None,
             filesystem: None,
             response_sender: sender,
             response_receiver: Some(receiver),
+            ready_sender: Some(ready_sender),
+            ready: ready.shared(),
+            counters: Default::default(),
+            lookups: mount_options.track_lookups.then(Default::default),
+            bulk_ops: mount_options
+                .max_concurrent_bulk_ops
+                .map(|max| Arc::new(new_semaphore(max.get()))),
+            attached: false,
+            normal_requests: mount_options.request_class.as_ref().map(|_| {
+                let max = mount_options
+                    .max_concurrent_normal_requests
+                    .map_or(DEFAULT_MAX_CONCURRENT_NORMAL_REQUESTS, NonZeroUsize::get);
+
+                Arc::new(new_semaphore(max))
+            }),
             mount_options,
         }
     }
@@ -286,6 +892,66 @@ impl<FS> Session<FS> {
     fn get_notify(&self) -> Notify {
         Notify::new(self.response_sender.clone())
     }
+
+    /// get the default permissions of the replied attributes.
+    fn default_mode(&self) -> DefaultMode {
+        DefaultMode {
+            file: self.mount_options.default_file_mode,
+            dir: self.mount_options.default_dir_mode,
+        }
+    }
+
+    /// spawn the request handle task in the current span, which is the `fuse_op` span of the
+    /// handling request, by the [`MountOptions::spawner`] if it is set. The normal priority request
+    /// waits for the normal request budget in the task.
+    #[inline]
+    fn spawn<F>(&self, priority: Priority, fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_with_bulk_ops(priority, None, fut)
+    }
+
+    /// spawn the bulk request handle task like [`spawn`](Self::spawn), the task waits for the
+    /// [`MountOptions::max_concurrent_bulk_ops`] permit before the normal request budget, so the
+    /// waiting bulk requests don't hold the normal request budget.
+    #[inline]
+    fn spawn_bulk<F>(&self, priority: Priority, fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn_with_bulk_ops(priority, self.bulk_ops.clone(), fut)
+    }
+
+    fn spawn_with_bulk_ops<F>(&self, priority: Priority, bulk_ops: Option<Arc<Semaphore>>, fut: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let span = Span::current();
+        let in_flight = InFlight::new(self.counters.clone());
+        let normal_requests = match priority {
+            Priority::Normal => self.normal_requests.clone(),
+            Priority::High => None,
+        };
+        let fut = async move {
+            let bulk_permit = acquire_permit(bulk_ops).await;
+            let permit = acquire_permit(normal_requests).await;
+            let output = fut.await;
+            drop(permit);
+            drop(bulk_permit);
+            drop(in_flight);
+
+            output
+        };
+
+        match &self.mount_options.spawner {
+            None => spawn(span, fut),
+            Some(spawner) => (spawner.0)(Box::pin(fut.instrument(span).map(|_| ()))),
+        }
+    }
 }
 
 #[cfg(any(feature = "async-io-runtime", feature = "tokio-runtime"))]
@@ -312,28 +978,92 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         Ok(())
     }
 
-    /// mount the filesystem without root permission. This function will block
-    /// until the filesystem is unmounted.
+    /// mount the filesystem without root permission.
+    ///
+    /// It returns after the `FUSE_INIT` request is handled, if [`Filesystem::init`] returns an
+    /// error, the mount path is unmounted and the init error is returned. Mounting on a path
+    /// inside an active mount of this process is rejected with `InvalidInput`, it would deadlock.
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        all(target_os = "freebsd", feature = "unprivileged"),
+        target_os = "macos"
+    ))]
+    pub async fn mount_with_unprivileged<P: AsRef<Path>>(
+        mut self,
+        fs: FS,
+        mount_path: P,
+    ) -> IoResult<MountHandle> {
+        self.filesystem.replace(Arc::new(fs));
+
+        self.do_mount_with_unprivileged(mount_path).await
+    }
+
+    /// mount the filesystem with root permission.
+    ///
+    /// It returns after the `FUSE_INIT` request is handled, if [`Filesystem::init`] returns an
+    /// error, the mount path is unmounted and the init error is returned. Mounting on a path
+    /// inside an active mount of this process is rejected with `InvalidInput`, it would deadlock.
+    pub async fn mount<P: AsRef<Path>>(mut self, fs: FS, mount_path: P) -> IoResult<MountHandle> {
+        self.filesystem.replace(Arc::new(fs));
+
+        self.do_mount(mount_path).await
+    }
+
+    /// mount the shared filesystem of the session created by [`Session::with_shared`] without
+    /// root permission, see [`mount_with_unprivileged`][Session::mount_with_unprivileged].
+    #[cfg(any(
+        all(target_os = "linux", feature = "unprivileged"),
+        all(target_os = "freebsd", feature = "unprivileged"),
+        target_os = "macos"
+    ))]
+    pub async fn mount_shared_with_unprivileged<P: AsRef<Path>>(
+        self,
+        mount_path: P,
+    ) -> IoResult<MountHandle> {
+        self.shared_check()?;
+
+        self.do_mount_with_unprivileged(mount_path).await
+    }
+
+    /// mount the shared filesystem of the session created by [`Session::with_shared`] with root
+    /// permission, see [`mount`][Session::mount].
+    pub async fn mount_shared<P: AsRef<Path>>(self, mount_path: P) -> IoResult<MountHandle> {
+        self.shared_check()?;
+
+        self.do_mount(mount_path).await
+    }
+
+    fn shared_check(&self) -> IoResult<()> {
+        if self.filesystem.is_none() {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "session is not created by Session::with_shared",
+            ));
+        }
+
+        Ok(())
+    }
+
     // On FreeBSD, no special interface is required to mount unprivileged.
     // If vfs.usermount=1 and the user has access to the mountpoint, it will
     // just work.
     #[cfg(all(target_os = "freebsd", feature = "unprivileged"))]
-    pub async fn mount_with_unprivileged<P: AsRef<Path>>(
+    async fn do_mount_with_unprivileged<P: AsRef<Path>>(
         self,
-        fs: FS,
         mount_path: P,
     ) -> IoResult<MountHandle> {
-        self.mount(fs, mount_path).await
+        self.do_mount(mount_path).await
     }
 
     #[cfg(target_os = "macos")]
-    pub async fn mount_with_unprivileged<P: AsRef<Path>>(
+    async fn do_mount_with_unprivileged<P: AsRef<Path>>(
         mut self,
-        fs: FS,
         mount_path: P,
     ) -> IoResult<MountHandle> {
         let mount_path = mount_path.as_ref();
 
+        self.mount_options.validate()?;
+        self_mount_check(mount_path)?;
         self.mount_empty_check(mount_path).await?;
 
         let notify = Arc::new(async_notify::Notify::new());
@@ -344,31 +1074,44 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         )
         .await?;
 
-        self.fuse_connection.replace(Arc::new(fuse_connection));
-
-        self.filesystem.replace(Arc::new(fs));
+        let fuse_connection = Arc::new(fuse_connection);
+        register_mount(mount_path, &fuse_connection);
+        let weak_connection = Arc::downgrade(&fuse_connection);
+        self.fuse_connection.replace(fuse_connection);
 
         debug!("mount {:?} success", mount_path);
 
-        Ok(MountHandle {
+        let ready = self.ready.clone();
+        let counters = self.counters.clone();
+        let mount_point = MountPoint {
+            path: mount_path.to_path_buf(),
+            unprivileged: true,
+        };
+
+        MountHandle {
             inner: Some(MountHandleInner {
-                task: task::spawn(self.inner_mount()),
+                task: task::spawn(self.inner_mount(mount_point)),
                 mount_path: mount_path.to_path_buf(),
                 destroy_notify: notify,
+                ready,
+                counters,
+                fuse_connection: weak_connection,
                 unprivileged: true,
             }),
-        })
+        }
+        .wait_init(true)
+        .await
     }
 
-    /// mount the filesystem without root permission.
     #[cfg(all(target_os = "linux", feature = "unprivileged"))]
-    pub async fn mount_with_unprivileged<P: AsRef<Path>>(
+    async fn do_mount_with_unprivileged<P: AsRef<Path>>(
         mut self,
-        fs: FS,
         mount_path: P,
     ) -> IoResult<MountHandle> {
         let mount_path = mount_path.as_ref();
 
+        self.mount_options.validate()?;
+        self_mount_check(mount_path)?;
         self.mount_empty_check(mount_path).await?;
 
         let notify = Arc::new(async_notify::Notify::new());
@@ -379,27 +1122,41 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         )
         .await?;
 
-        self.fuse_connection.replace(Arc::new(fuse_connection));
-
-        self.filesystem.replace(Arc::new(fs));
+        let fuse_connection = Arc::new(fuse_connection);
+        register_mount(mount_path, &fuse_connection);
+        let weak_connection = Arc::downgrade(&fuse_connection);
+        self.fuse_connection.replace(fuse_connection);
 
         debug!("mount {:?} success", mount_path);
 
-        Ok(MountHandle {
+        let ready = self.ready.clone();
+        let counters = self.counters.clone();
+        let mount_point = MountPoint {
+            path: mount_path.to_path_buf(),
+            unprivileged: true,
+        };
+
+        MountHandle {
             inner: Some(MountHandleInner {
-                task: task::spawn(self.inner_mount()),
+                task: task::spawn(self.inner_mount(mount_point)),
                 mount_path: mount_path.to_path_buf(),
                 destroy_notify: notify,
+                ready,
+                counters,
+                fuse_connection: weak_connection,
                 unprivileged: true,
             }),
-        })
+        }
+        .wait_init(true)
+        .await
     }
 
-    /// mount the filesystem with root permission.
     #[cfg(target_os = "linux")]
-    pub async fn mount<P: AsRef<Path>>(mut self, fs: FS, mount_path: P) -> IoResult<MountHandle> {
+    async fn do_mount<P: AsRef<Path>>(mut self, mount_path: P) -> IoResult<MountHandle> {
         let mount_path = mount_path.as_ref();
 
+        self.mount_options.validate()?;
+        self_mount_check(mount_path)?;
         self.mount_empty_check(mount_path).await?;
 
         let notify = Arc::new(async_notify::Notify::new());
@@ -429,28 +1186,144 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             return Err(err.into());
         }
 
-        self.fuse_connection.replace(Arc::new(fuse_connection));
-
-        self.filesystem.replace(Arc::new(fs));
+        let fuse_connection = Arc::new(fuse_connection);
+        register_mount(mount_path, &fuse_connection);
+        let weak_connection = Arc::downgrade(&fuse_connection);
+        self.fuse_connection.replace(fuse_connection);
 
         debug!("mount {:?} success", mount_path);
 
-        Ok(MountHandle {
+        let ready = self.ready.clone();
+        let counters = self.counters.clone();
+        let mount_point = MountPoint {
+            path: mount_path.to_path_buf(),
+            #[cfg(all(target_os = "linux", feature = "unprivileged"))]
+            unprivileged: false,
+        };
+
+        MountHandle {
             inner: Some(MountHandleInner {
-                task: task::spawn(self.inner_mount()),
+                task: task::spawn(self.inner_mount(mount_point)),
                 mount_path: mount_path.to_path_buf(),
                 destroy_notify: notify,
+                ready,
+                counters,
+                fuse_connection: weak_connection,
                 #[cfg(all(target_os = "linux", feature = "unprivileged"))]
                 unprivileged: false,
             }),
-        })
+        }
+        .wait_init(true)
+        .await
+    }
+
+    /// attach the filesystem to the fd of an existing fuse connection which is mounted at
+    /// `mount_path` with root permission, it is used to resume serving after the previous
+    /// session crashed, the fd is got by [`MountHandle::try_clone_fd`] and kept by another
+    /// process, such as a supervisor.
+    ///
+    /// It doesn't mount, and the kernel won't send the `FUSE_INIT` request again, so
+    /// [`Filesystem::init`] is called without replying to the kernel. The returned
+    /// [`MountHandle`] unmounts `mount_path` like a mounted one.
+    ///
+    /// # Notes:
+    ///
+    /// - the kernel keeps the state negotiated with the previous session, the mount options
+    ///   which are used in the `FUSE_INIT`, such as [`MountOptions::write_back`] and
+    ///   [`MountOptions::force_readdir_plus`], are ignored.
+    /// - the `max_write` returned by [`Filesystem::init`] can't be less than the previous one, or
+    ///   the larger write requests can't be read.
+    /// - the requests which were read but not replied by the previous session are lost, the
+    ///   callers are blocked until the connection is aborted.
+    /// - the kernel still uses the inodes and the file handles got from the previous session, it
+    ///   sends `forget` and `release` for them, the filesystem must recognize them.
+    /// - no other process can read the fd at the same time.
+    ///
+    /// [`MountOptions::write_back`]: crate::MountOptions::write_back
+    /// [`MountOptions::force_readdir_plus`]: crate::MountOptions::force_readdir_plus
+    #[cfg(target_os = "linux")]
+    pub async fn attach<P: AsRef<Path>>(
+        mut self,
+        fs: FS,
+        fd: OwnedFd,
+        mount_path: P,
+    ) -> IoResult<MountHandle> {
+        self.filesystem.replace(Arc::new(fs));
+
+        self.do_attach(
+            fd,
+            mount_path.as_ref(),
+            #[cfg(feature = "unprivileged")]
+            false,
+        )
+        .await
+    }
+
+    /// attach the filesystem to the fd of an existing fuse connection which is mounted at
+    /// `mount_path` without root permission, see [`attach`][Session::attach].
+    #[cfg(all(target_os = "linux", feature = "unprivileged"))]
+    pub async fn attach_with_unprivileged<P: AsRef<Path>>(
+        mut self,
+        fs: FS,
+        fd: OwnedFd,
+        mount_path: P,
+    ) -> IoResult<MountHandle> {
+        self.filesystem.replace(Arc::new(fs));
+
+        self.do_attach(fd, mount_path.as_ref(), true).await
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn do_attach(
+        mut self,
+        fd: OwnedFd,
+        mount_path: &Path,
+        #[cfg(feature = "unprivileged")] unprivileged: bool,
+    ) -> IoResult<MountHandle> {
+        self.mount_options.validate()?;
+        self_mount_check(mount_path)?;
+
+        let notify = Arc::new(async_notify::Notify::new());
+        let fuse_connection = Arc::new(FuseConnection::from_fd(fd, notify.clone())?);
+        register_mount(mount_path, &fuse_connection);
+        let weak_connection = Arc::downgrade(&fuse_connection);
+        self.fuse_connection.replace(fuse_connection);
+        self.attached = true;
+
+        debug!("attach {:?} success", mount_path);
+
+        let ready = self.ready.clone();
+        let counters = self.counters.clone();
+        let mount_point = MountPoint {
+            path: mount_path.to_path_buf(),
+            #[cfg(feature = "unprivileged")]
+            unprivileged,
+        };
+
+        // the mount path is served by the previous session, don't unmount it if the init failed,
+        // the caller can attach again
+        MountHandle {
+            inner: Some(MountHandleInner {
+                task: task::spawn(self.inner_mount(mount_point)),
+                mount_path: mount_path.to_path_buf(),
+                destroy_notify: notify,
+                ready,
+                counters,
+                fuse_connection: weak_connection,
+                #[cfg(feature = "unprivileged")]
+                unprivileged,
+            }),
+        }
+        .wait_init(false)
+        .await
     }
 
-    /// mount the filesystem
     #[cfg(target_os = "freebsd")]
-    pub async fn mount<P: AsRef<Path>>(mut self, fs: FS, mount_path: P) -> IoResult<MountHandle> {
+    async fn do_mount<P: AsRef<Path>>(mut self, mount_path: P) -> IoResult<MountHandle> {
         let mount_path = mount_path.as_ref();
 
+        self.mount_options.validate()?;
+        self_mount_check(mount_path)?;
         self.mount_empty_check(mount_path).await?;
 
         let notify = Arc::new(async_notify::Notify::new());
@@ -472,30 +1345,81 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             }
         }
 
-        self.fuse_connection.replace(Arc::new(fuse_connection));
-
-        self.filesystem.replace(Arc::new(fs));
+        let fuse_connection = Arc::new(fuse_connection);
+        register_mount(mount_path, &fuse_connection);
+        let weak_connection = Arc::downgrade(&fuse_connection);
+        self.fuse_connection.replace(fuse_connection);
 
         debug!("mount {:?} success", mount_path);
 
-        Ok(MountHandle {
+        let ready = self.ready.clone();
+        let counters = self.counters.clone();
+        let mount_point = MountPoint {
+            path: mount_path.to_path_buf(),
+        };
+
+        MountHandle {
             inner: Some(MountHandleInner {
-                task: task::spawn(self.inner_mount()),
+                task: task::spawn(self.inner_mount(mount_point)),
                 mount_path: mount_path.to_path_buf(),
                 destroy_notify: notify,
+                ready,
+                counters,
+                fuse_connection: weak_connection,
             }),
-        })
+        }
+        .wait_init(true)
+        .await
     }
 
     #[cfg(target_os = "macos")]
-    pub async fn mount<P: AsRef<Path>>(mut self, fs: FS, mount_path: P) -> IoResult<MountHandle> {
-        self.mount_with_unprivileged(fs, mount_path).await
+    async fn do_mount<P: AsRef<Path>>(self, mount_path: P) -> IoResult<MountHandle> {
+        self.do_mount_with_unprivileged(mount_path).await
+    }
+
+    async fn inner_mount(self, mount_point: MountPoint) -> IoResult<()> {
+        let mount_path = mount_point.path.clone();
+
+        info!(
+            name: "fuse3.mounted",
+            mount_path = %mount_path.display(),
+            "filesystem is mounted"
+        );
+
+        let result = self.serve(mount_point).await;
+
+        if let Err(err) = &result {
+            error!(
+                name: "fuse3.error",
+                mount_path = %mount_path.display(),
+                error = %err,
+                "session exited with error"
+            );
+        }
+
+        result
     }
 
-    async fn inner_mount(mut self) -> IoResult<()> {
-        let fuse_write_connection = self.fuse_connection.as_ref().unwrap().clone();
+    async fn serve(mut self, mount_point: MountPoint) -> IoResult<()> {
+        // the session is consumed by mount, but return an error instead of panic if the invariant
+        // is broken
+        let fuse_write_connection = self
+            .fuse_connection
+            .clone()
+            .ok_or_else(|| IoError::new(ErrorKind::NotConnected, "fuse connection is not set"))?;
+
+        let receiver = self.response_receiver.take().ok_or_else(|| {
+            IoError::new(
+                ErrorKind::AlreadyExists,
+                "session is already mounted, response receiver is taken",
+            )
+        })?;
 
-        let receiver = self.response_receiver.take().unwrap();
+        let shutdown_signal = self
+            .mount_options
+            .shutdown_signal
+            .as_ref()
+            .and_then(|signal| signal.0.lock().unwrap().take());
 
         let dispatch_task = self.dispatch().fuse();
         let mut dispatch_task = pin!(dispatch_task);
@@ -511,47 +1435,128 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let mut reply_task = pin!(reply_task);
 
-        select! {
-            reply_result = reply_task => {
-                reply_result?;
+        let mut shutdown_signal = pin!(async move {
+            match shutdown_signal {
+                None => future::pending().await,
+                Some(signal) => signal.await,
             }
+        }
+        .fuse());
+        let mut mount_point = Some(mount_point);
+        let mut unmount_task = pin!(Fuse::terminated());
+
+        loop {
+            select! {
+                reply_result = reply_task => {
+                    reply_result?;
+
+                    return Ok(());
+                }
+
+                dispatch_result = dispatch_task => {
+                    dispatch_result?;
+
+                    // the dispatch closes the reply channel, write the queued replies before the
+                    // connection is closed, like the replies of the drained requests
+                    reply_task.await?;
+
+                    return Ok(());
+                }
 
-            dispatch_result = dispatch_task => {
-                dispatch_result?;
+                _ = shutdown_signal => {
+                    debug!("receive shutdown signal, unmount the filesystem");
+
+                    // the dispatch exits and calls destroy after the mount path is unmounted, like
+                    // an external umount
+                    if let Some(mount_point) = mount_point.take() {
+                        unmount_task.set(mount_point.unmount().fuse());
+                    }
+                }
+
+                unmount_result = unmount_task => {
+                    unmount_result?;
+                }
             }
         }
-
-        Ok(())
     }
 
     async fn reply_fuse(
         fuse_connection: Arc<FuseConnection>,
-        mut response_receiver: UnboundedReceiver<FuseData>,
+        mut response_receiver: ResponseReceiver,
     ) -> IoResult<()> {
         while let Some(response) = response_receiver.next().await {
-            let (data, extend_data) = match response {
-                Either::Left(data) => (data, None),
-                Either::Right((data, extend_data)) => (data, Some(extend_data)),
-            };
-            if let Err(err) = fuse_connection.write_vectored(data, extend_data).await.1 {
-                if err.kind() == ErrorKind::NotFound {
-                    warn!(
-                        "may reply interrupted fuse request, ignore this error {}",
-                        err
-                    );
+            match response {
+                Response::NotifyBatch(data) => {
+                    let mut data = reply_buffer_into_bytes(data);
 
-                    continue;
+                    while !data.is_empty() {
+                        let len = protocol::decode_out_header_len(&data)
+                            .unwrap_or(data.len())
+                            .clamp(FUSE_OUT_HEADER_SIZE, data.len());
+
+                        Self::write_reply(&fuse_connection, data.split_to(len), None::<Bytes>)
+                            .await?;
+                    }
                 }
 
-                error!("reply fuse failed {}", err);
+                Response::Reply(Either::Left(data)) => {
+                    Self::write_reply(&fuse_connection, data, None::<Bytes>).await?;
+                }
 
-                return Err(err);
+                Response::Reply(Either::Right((data, extend_data))) => {
+                    Self::write_reply(&fuse_connection, data, Some(extend_data)).await?;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// write a reply or a notification, the errors which only drop the reply are ignored.
+    async fn write_reply<T: Deref<Target = [u8]> + Send, U: Deref<Target = [u8]> + Send>(
+        fuse_connection: &FuseConnection,
+        data: T,
+        extend_data: Option<U>,
+    ) -> IoResult<()> {
+        let len = data.len() + extend_data.as_ref().map_or(0, |data| data.len());
+
+        let err = match fuse_connection.write_vectored(data, extend_data).await.1 {
+            Ok(n) if n >= len => return Ok(()),
+
+            // the fuse device takes a reply in one write, a short write can't be continued by
+            // writing the rest, kernel parses the rest as a new reply, so the torn reply
+            // breaks the connection
+            Ok(n) => {
+                error!(written = n, len, "reply fuse is written partially");
+
+                return Err(ErrorKind::WriteZero.into());
+            }
+
+            Err(err) => err,
+        };
+
+        if err.raw_os_error() == Some(libc::ENODEV) {
+            debug!("fuse connection is gone, drop the reply");
+
+            return Ok(());
+        }
+
+        if err.kind() == ErrorKind::NotFound {
+            warn!(
+                "may reply interrupted fuse request, ignore this error {}",
+                err
+            );
+
+            return Ok(());
+        }
+
+        // the connection already retried EINTR and EAGAIN, the other errors can't be
+        // recovered
+        error!("reply fuse failed {}", err);
+
+        Err(err)
+    }
+
     #[instrument(level = "debug", skip(self, fs), ret, err)]
     async fn init_filesystem(
         &mut self,
@@ -586,14 +1591,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         let opcode = match fuse_opcode::try_from(in_header.opcode) {
             Err(err) => {
-                debug!("receive unknown opcode {}", err.0);
+                debug!("receive {}", err);
 
-                reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::ENOSYS.into(), request, &mut self.response_sender).await;
 
-                return Err(IoError::new(
-                    ErrorKind::Other,
-                    format!("receive unknown opcode {}", err.0),
-                ));
+                return Err(IoError::new(ErrorKind::Other, format!("receive {err}")));
             }
 
             Ok(opcode) => opcode,
@@ -617,6 +1619,27 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             .await
     }
 
+    /// init the filesystem of an attached session, the kernel has negotiated with the previous
+    /// session, so nothing is replied.
+    #[instrument(level = "debug", skip(self, fs), ret, err)]
+    async fn attach_filesystem(&mut self, fs: &FS) -> IoResult<NonZeroU32> {
+        let reply = fs
+            .init(Request {
+                unique: 0,
+                uid: 0,
+                gid: 0,
+                pid: 0,
+            })
+            .await?;
+
+        let mut max_write = reply.max_write;
+        if let Some(mount_max_write) = self.mount_options.max_write {
+            max_write = max_write.min(mount_max_write);
+        }
+
+        Ok(max_write)
+    }
+
     #[instrument(level = "debug", skip(self, header_buffer, data_buffer), ret)]
     async fn read_fuse_request(
         &mut self,
@@ -656,6 +1679,14 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 };
             }
 
+            // EOF means the connection is gone, such as unmount, the next read won't get a
+            // request too
+            Ok(0) => {
+                debug!("read from /dev/fuse EOF");
+
+                return ReadResult::Destroy;
+            }
+
             Ok(n) => n,
         };
 
@@ -677,9 +1708,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             };
         }
 
-        let in_header = match get_bincode_config().deserialize::<fuse_in_header>(&header_buffer) {
+        let in_header = match protocol::decode_in_header(&header_buffer) {
             Err(err) => {
-                error!("deserialize fuse_in_header failed {}", err);
+                error!("decode fuse_in_header failed {}", err);
 
                 return ReadResult::Request {
                     in_header: Err(IoError::new(ErrorKind::Other, err)),
@@ -688,7 +1719,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 };
             }
 
-            Ok(in_header) => in_header,
+            Ok((in_header, _)) => in_header,
         };
 
         ReadResult::Request {
@@ -699,10 +1730,44 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     }
 
     async fn dispatch(&mut self) -> IoResult<()> {
-        let fuse_connection = self.fuse_connection.take().unwrap();
-        let fs = self.filesystem.take().expect("filesystem not init");
+        let result = self.dispatch_requests().await;
+
+        // the connection is gone, close the reply channel, so the replies of the in-flight
+        // requests and the notifications fail instead of waiting the connection forever
+        self.response_sender.close_channel();
+
+        result
+    }
+
+    async fn dispatch_requests(&mut self) -> IoResult<()> {
+        let fuse_connection = self
+            .fuse_connection
+            .take()
+            .ok_or_else(|| IoError::new(ErrorKind::NotConnected, "fuse connection is not set"))?;
+        let fs = self
+            .filesystem
+            .take()
+            .ok_or_else(|| IoError::other("filesystem not init"))?;
+
+        let max_write = if self.attached {
+            self.attach_filesystem(&fs).await?
+        } else {
+            self.init_filesystem(&fs, &fuse_connection).await?
+        };
+        let max_write = max_write.get() as usize;
+        let capabilities = fs.capabilities();
+        debug!("filesystem capabilities {:?}", capabilities);
+
+        if let Some(ready_sender) = self.ready_sender.take() {
+            let _ = ready_sender.send(());
+        }
+
+        info!(
+            name: "fuse3.initialized",
+            max_write,
+            "filesystem is initialized"
+        );
 
-        let max_write = self.init_filesystem(&fs, &fuse_connection).await?.get() as usize;
         let buffer_size = (max_write + FUSE_WRITE_IN_SIZE).max(FUSE_MIN_READ_BUFFER_SIZE);
 
         let mut header_buffer = vec![0; FUSE_IN_HEADER_SIZE];
@@ -714,12 +1779,15 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 .await
             {
                 ReadResult::Destroy => {
-                    fs.destroy(Request {
-                        unique: 0,
-                        uid: 0,
-                        gid: 0,
-                        pid: 0,
-                    })
+                    self.destroy_filesystem(
+                        Request {
+                            unique: 0,
+                            uid: 0,
+                            gid: 0,
+                            pid: 0,
+                        },
+                        &fs,
+                    )
                     .await;
 
                     return Ok(());
@@ -745,9 +1813,16 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let opcode = match fuse_opcode::try_from(in_header.opcode) {
                 Err(err) => {
-                    debug!("receive unknown opcode {}", err.0);
+                    debug!("receive {}", err);
+
+                    if let Some(hook) = &self.mount_options.on_unknown_opcode {
+                        let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
+
+                        (hook.0)(err.0, &data_buffer[..data_size]);
+                    }
 
-                    reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
+                    reply_error_in_place(libc::ENOSYS.into(), request, &mut self.response_sender)
+                        .await;
 
                     continue;
                 }
@@ -757,240 +1832,422 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             debug!("receive opcode {}", opcode);
 
-            let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
-            let data_ref = &data_buffer[..data_size];
+            if !is_session_managed(&opcode) {
+                if !capabilities.supports(opcode) {
+                    debug!(
+                        "opcode {} is not supported by filesystem, request unique {}",
+                        opcode, request.unique
+                    );
 
-            match opcode {
-                fuse_opcode::FUSE_INIT => {
-                    warn!("duplicated fuse init request");
+                    reply_error_in_place(libc::ENOSYS.into(), request, &mut self.response_sender)
+                        .await;
 
-                    self.handle_init(request, data_ref, &fuse_connection, &fs)
-                        .await?;
+                    continue;
                 }
 
-                fuse_opcode::FUSE_DESTROY => {
-                    debug!("receive fuse destroy");
-
-                    fs.destroy(request).await;
-
-                    debug!("fuse destroyed");
+                // the kernel rejects the writes of a read only mount, but don't trust it
+                if self.mount_options.read_only && is_write_opcode(&opcode) {
+                    debug!(
+                        "opcode {} is rejected by read only mount, request unique {}",
+                        opcode, request.unique
+                    );
 
-                    return Ok(());
-                }
+                    reply_error_in_place(libc::EROFS.into(), request, &mut self.response_sender)
+                        .await;
 
-                fuse_opcode::FUSE_LOOKUP => {
-                    self.handle_lookup(request, in_header, data_ref, &fs).await;
+                    continue;
                 }
 
-                fuse_opcode::FUSE_FORGET => {
-                    self.handle_forget(request, in_header, data_ref, &fs).await;
-                }
+                if let Some(filter) = &self.mount_options.access_filter {
+                    if let Err(err) = (filter.0)(&request, opcode) {
+                        debug!(
+                            "opcode {} is rejected by access filter, request unique {}",
+                            opcode, request.unique
+                        );
 
-                fuse_opcode::FUSE_GETATTR => {
-                    self.handle_getattr(request, in_header, data_ref, &fs).await;
-                }
+                        reply_error_in_place(err, request, &mut self.response_sender).await;
 
-                fuse_opcode::FUSE_SETATTR => {
-                    self.handle_setattr(request, in_header, data_ref, &fs).await;
+                        continue;
+                    }
                 }
+            }
 
-                fuse_opcode::FUSE_READLINK => {
-                    self.handle_readlink(request, in_header, &fs).await;
-                }
+            let data_size = in_header.len as usize - FUSE_IN_HEADER_SIZE;
 
-                fuse_opcode::FUSE_SYMLINK => {
-                    self.handle_symlink(request, in_header, data_ref, &fs).await;
-                }
+            // a large write takes the request buffer instead of copying the data, the next
+            // request is read into a new buffer. The small writes still copy the data, a whole
+            // buffer kept by the filesystem for a small write wastes too much memory
+            let write_buffer = if opcode == fuse_opcode::FUSE_WRITE
+                && data_size >= buffer_size / ZERO_COPY_WRITE_DIVISOR
+            {
+                let buffer = mem::replace(&mut data_buffer, vec![0; buffer_size]);
+                let buffer = Bytes::from(buffer).slice(..data_size);
+                Some(buffer)
+            } else {
+                None
+            };
+            let data_ref = write_buffer.as_deref().unwrap_or(&data_buffer[..data_size]);
 
-                fuse_opcode::FUSE_MKNOD => {
-                    self.handle_mknod(request, in_header, data_ref, &fs).await;
-                }
+            let span = debug_span!(
+                "fuse_op",
+                unique = in_header.unique,
+                nodeid = in_header.nodeid,
+                opcode = %opcode
+            );
 
-                fuse_opcode::FUSE_MKDIR => {
-                    self.handle_mkdir(request, in_header, data_ref, &fs).await;
-                }
+            if self
+                .handle_request(
+                    opcode,
+                    request,
+                    in_header,
+                    data_ref,
+                    write_buffer.clone(),
+                    &fuse_connection,
+                    &fs,
+                )
+                .instrument(span)
+                .await?
+                .is_break()
+            {
+                return Ok(());
+            }
+        }
+    }
 
-                fuse_opcode::FUSE_UNLINK => {
-                    self.handle_unlink(request, in_header, data_ref, &fs).await;
-                }
+    /// destroy the filesystem when the session is ending, by the `FUSE_DESTROY` request or the
+    /// closed fuse connection.
+    async fn destroy_filesystem(&self, request: Request, fs: &FS) {
+        // the replies of the in flight requests are still written when draining
+        if self.counters.draining.load(Ordering::Acquire) {
+            debug!("wait the in flight requests before destroying the filesystem");
 
-                fuse_opcode::FUSE_RMDIR => {
-                    self.handle_rmdir(request, in_header, data_ref, &fs).await;
-                }
+            self.counters.wait_idle().await;
+        }
 
-                fuse_opcode::FUSE_RENAME => {
-                    self.handle_rename(request, in_header, data_ref, &fs).await;
-                }
+        if let Some(lookups) = &self.lookups {
+            lookups.report();
+        }
 
-                fuse_opcode::FUSE_LINK => {
-                    self.handle_link(request, in_header, data_ref, &fs).await;
-                }
+        fs.destroy(request).await;
 
-                fuse_opcode::FUSE_OPEN => {
-                    self.handle_open(request, in_header, data_ref, &fs).await;
-                }
+        info!(name: "fuse3.destroyed", "filesystem is destroyed");
+    }
 
-                fuse_opcode::FUSE_READ => {
-                    self.handle_read(request, in_header, data_ref, &fs).await;
-                }
+    /// handle the request in the `fuse_op` span of dispatch, the handlers spawn tasks in the same
+    /// span, so all logs of a request have its unique, nodeid and opcode.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_request(
+        &mut self,
+        opcode: fuse_opcode,
+        request: Request,
+        in_header: fuse_in_header,
+        data_ref: &[u8],
+        write_buffer: Option<Bytes>,
+        fuse_connection: &FuseConnection,
+        fs: &Arc<FS>,
+    ) -> IoResult<ControlFlow<()>> {
+        let priority = self
+            .mount_options
+            .request_class
+            .as_ref()
+            .map_or(Priority::Normal, |classifier| (classifier.0)(opcode));
+
+        match opcode {
+            fuse_opcode::FUSE_INIT => {
+                warn!("duplicated fuse init request");
+
+                self.handle_init(request, data_ref, fuse_connection, fs)
+                    .await?;
+            }
 
-                fuse_opcode::FUSE_WRITE => {
-                    self.handle_write(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_DESTROY => {
+                debug!("receive fuse destroy");
 
-                fuse_opcode::FUSE_STATFS => {
-                    self.handle_statfs(request, in_header, &fs).await;
-                }
+                self.destroy_filesystem(request, fs).await;
 
-                fuse_opcode::FUSE_RELEASE => {
-                    self.handle_release(request, in_header, data_ref, &fs).await;
-                }
+                return Ok(ControlFlow::Break(()));
+            }
 
-                fuse_opcode::FUSE_FSYNC => {
-                    self.handle_fsync(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_LOOKUP => {
+                self.handle_lookup(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_SETXATTR => {
-                    self.handle_setxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_FORGET => {
+                self.handle_forget(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_GETXATTR => {
-                    self.handle_getxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_GETATTR => {
+                self.handle_getattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_LISTXATTR => {
-                    self.handle_listxattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_SETATTR => {
+                self.handle_setattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_REMOVEXATTR => {
-                    self.handle_removexattr(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_READLINK => {
+                self.handle_readlink(request, priority, in_header, fs).await;
+            }
 
-                fuse_opcode::FUSE_FLUSH => {
-                    self.handle_flush(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_SYMLINK => {
+                self.handle_symlink(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_OPENDIR => {
-                    self.handle_opendir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_MKNOD => {
+                self.handle_mknod(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_READDIR => {
-                    self.handle_readdir(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_MKDIR => {
+                self.handle_mkdir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_RELEASEDIR => {
-                    self.handle_releasedir(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_UNLINK => {
+                self.handle_unlink(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_FSYNCDIR => {
-                    self.handle_fsyncdir(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_RMDIR => {
+                self.handle_rmdir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                #[cfg(feature = "file-lock")]
-                fuse_opcode::FUSE_GETLK => {
-                    self.handle_getlk(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_RENAME => {
+                self.handle_rename(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                #[cfg(feature = "file-lock")]
-                fuse_opcode::FUSE_SETLK | fuse_opcode::FUSE_SETLKW => {
-                    self.handle_setlk(
-                        request,
-                        in_header,
-                        data_ref,
-                        opcode == fuse_opcode::FUSE_SETLKW,
-                        &fs,
-                    )
+            fuse_opcode::FUSE_LINK => {
+                self.handle_link(request, priority, in_header, data_ref, fs)
                     .await;
-                }
+            }
 
-                fuse_opcode::FUSE_ACCESS => {
-                    self.handle_access(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_OPEN => {
+                self.handle_open(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_CREATE => {
-                    self.handle_create(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_READ => {
+                self.handle_read(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_INTERRUPT => {
-                    self.handle_interrupt(request, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_WRITE => {
+                self.handle_write(request, priority, in_header, data_ref, write_buffer, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_BMAP => {
-                    self.handle_bmap(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_STATFS => {
+                self.handle_statfs(request, priority, in_header, fs).await;
+            }
 
-                /*fuse_opcode::FUSE_IOCTL => {
-                    let mut resp_sender = self.response_sender.clone();
+            fuse_opcode::FUSE_RELEASE => {
+                self.handle_release(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                    let ioctl_in = match get_bincode_config().deserialize::<fuse_ioctl_in>(data) {
-                        Err(err) => {
-                            error!("deserialize fuse_ioctl_in failed {}", err);
+            fuse_opcode::FUSE_FSYNC => {
+                self.handle_fsync(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                             reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+            fuse_opcode::FUSE_SETXATTR => {
+                self.handle_setxattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                            continue;
-                        }
+            fuse_opcode::FUSE_GETXATTR => {
+                self.handle_getxattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                        Ok(ioctl_in) => ioctl_in,
-                    };
+            fuse_opcode::FUSE_LISTXATTR => {
+                self.handle_listxattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                    let ioctl_data = (&data[FUSE_IOCTL_IN_SIZE..]).to_vec();
+            fuse_opcode::FUSE_REMOVEXATTR => {
+                self.handle_removexattr(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                    let fs = fs.clone();
-                }*/
-                fuse_opcode::FUSE_POLL => {
-                    self.handle_poll(request, in_header, data_ref, &fs).await;
-                }
+            fuse_opcode::FUSE_FLUSH => {
+                self.handle_flush(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_NOTIFY_REPLY => {
-                    self.handle_notify_reply(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_OPENDIR => {
+                self.handle_opendir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_BATCH_FORGET => {
-                    self.handle_batch_forget(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_READDIR => {
+                self.handle_readdir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_FALLOCATE => {
-                    self.handle_fallocate(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_RELEASEDIR => {
+                self.handle_releasedir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_READDIRPLUS => {
-                    self.handle_readdirplus(request, in_header, data_ref, &fs)
-                        .await;
-                }
+            fuse_opcode::FUSE_FSYNCDIR => {
+                self.handle_fsyncdir(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_RENAME2 => {
-                    self.handle_rename2(request, in_header, data_ref, &fs).await;
-                }
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_GETLK => {
+                self.handle_getlk(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_LSEEK => {
-                    self.handle_lseek(request, in_header, data_ref, &fs).await;
-                }
+            #[cfg(feature = "file-lock")]
+            fuse_opcode::FUSE_SETLK | fuse_opcode::FUSE_SETLKW => {
+                self.handle_setlk(
+                    request,
+                    priority,
+                    in_header,
+                    data_ref,
+                    opcode == fuse_opcode::FUSE_SETLKW,
+                    fs,
+                )
+                .await;
+            }
+
+            fuse_opcode::FUSE_ACCESS => {
+                self.handle_access(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_CREATE => {
+                self.handle_create(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_INTERRUPT => {
+                self.handle_interrupt(request, priority, data_ref, fs).await;
+            }
+
+            fuse_opcode::FUSE_BMAP => {
+                self.handle_bmap(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                fuse_opcode::FUSE_COPY_FILE_RANGE => {
-                    self.handle_copy_file_range(request, in_header, data_ref, &fs)
+            /*fuse_opcode::FUSE_IOCTL => {
+                let mut resp_sender = self.response_sender.clone();
+
+                let ioctl_in = match protocol::decode_body::<fuse_ioctl_in>(data) {
+                    Err(err) => {
+                        error!("deserialize fuse_ioctl_in failed {}", err);
+
+                        reply_error_in_place(
+                            libc::EINVAL.into(),
+                            request,
+                            &mut self.response_sender,
+                        )
                         .await;
-                }
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_SETVOLNAME => {}
+                        continue;
+                    }
+
+                    Ok(ioctl_in) => ioctl_in,
+                };
+
+                let ioctl_data = (&data[FUSE_IOCTL_IN_SIZE..]).to_vec();
+
+                let fs = fs.clone();
+            }*/
+            fuse_opcode::FUSE_POLL => {
+                self.handle_poll(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_NOTIFY_REPLY => {
+                self.handle_notify_reply(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_BATCH_FORGET => {
+                self.handle_batch_forget(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_FALLOCATE => {
+                self.handle_fallocate(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_READDIRPLUS => {
+                self.handle_readdirplus(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_RENAME2 => {
+                self.handle_rename2(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_LSEEK => {
+                self.handle_lseek(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            fuse_opcode::FUSE_COPY_FILE_RANGE => {
+                self.handle_copy_file_range(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            #[cfg(feature = "dax")]
+            fuse_opcode::FUSE_SETUPMAPPING => {
+                self.handle_setup_mapping(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
+
+            #[cfg(feature = "dax")]
+            fuse_opcode::FUSE_REMOVEMAPPING => {
+                self.handle_remove_mapping(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_GETXTIMES => {}
+            fuse_opcode::FUSE_TMPFILE => {
+                self.handle_tmpfile(request, priority, in_header, data_ref, fs)
+                    .await;
+            }
 
-                #[cfg(target_os = "macos")]
-                fuse_opcode::FUSE_EXCHANGE => {} // fuse_opcode::CUSE_INIT => {}
+            #[cfg(target_os = "linux")]
+            fuse_opcode::FUSE_STATX => {
+                self.handle_statx(request, priority, in_header, data_ref, fs)
+                    .await;
             }
+
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_SETVOLNAME => {}
+
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_GETXTIMES => {}
+
+            #[cfg(target_os = "macos")]
+            fuse_opcode::FUSE_EXCHANGE => {} // fuse_opcode::CUSE_INIT => {}
         }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// get the root attr and return its kind when it isn't a directory, the filesystem which
+    /// can't get the root attr, like it doesn't implement `getattr`, isn't checked.
+    async fn check_root(request: Request, fs: &FS) -> Option<FileType> {
+        let reply = fs
+            .getattr(request, FUSE_ROOT_ID, None, GetattrFlags::default())
+            .await
+            .ok()?;
+
+        (reply.attr.kind != FileType::Directory).then_some(reply.attr.kind)
     }
 
     #[instrument(skip(self, data, fs))]
@@ -1001,22 +2258,20 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
         fuse_connection: &FuseConnection,
         fs: &FS,
     ) -> IoResult<NonZeroU32> {
-        let init_in = match get_bincode_config().deserialize::<fuse_init_in>(data) {
+        let init_in = match protocol::decode_body::<fuse_init_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_init_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                let init_out_header = fuse_out_header {
-                    len: FUSE_OUT_HEADER_SIZE as u32,
-                    error: libc::EINVAL,
-                    unique: request.unique,
-                };
-
-                let init_out_header_data = get_bincode_config()
-                    .serialize(&init_out_header)
-                    .expect("won't happened");
+                let mut init_out_header_data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+                protocol::encode_out_header(
+                    &mut init_out_header_data,
+                    request.unique,
+                    Errno::from(libc::EINVAL).into(),
+                    0,
+                );
 
                 if let Err(err) = fuse_connection
                     .write_vectored::<_, Vec<u8>>(init_out_header_data, None)
@@ -1101,10 +2356,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_SPLICE_READ;
         }
 
-        // posix lock used, maybe we don't need bsd lock
-        /*if init_in.flags&FUSE_FLOCK_LOCKS>0 {
+        #[cfg(feature = "file-lock")]
+        if init_in.flags & FUSE_FLOCK_LOCKS > 0 && self.mount_options.flock {
+            debug!("enable FUSE_FLOCK_LOCKS");
+
             reply_flags |= FUSE_FLOCK_LOCKS;
-        }*/
+        }
 
         /*if init_in.flags & FUSE_HAS_IOCTL_DIR > 0 {
             debug!("enable FUSE_HAS_IOCTL_DIR");
@@ -1136,7 +2393,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_ASYNC_DIO;
         }
 
-        if init_in.flags & FUSE_WRITEBACK_CACHE > 0 && self.mount_options.write_back {
+        // the read only mount has nothing to write back
+        if init_in.flags & FUSE_WRITEBACK_CACHE > 0
+            && self.mount_options.write_back
+            && !self.mount_options.read_only
+        {
             debug!("enable FUSE_WRITEBACK_CACHE");
 
             reply_flags |= FUSE_WRITEBACK_CACHE;
@@ -1219,18 +2480,37 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             reply_flags |= FUSE_XTIMES;
         }
 
+        #[cfg(target_os = "linux")]
+        let mut reply_flags2 = 0;
+
+        // the flags2 follows the fuse_init_in when kernel sets FUSE_INIT_EXT
+        #[cfg(target_os = "linux")]
+        let flags2 = if init_in.flags & FUSE_INIT_EXT > 0 {
+            data.get(FUSE_INIT_IN_SIZE..)
+                .and_then(|data| protocol::decode_body::<u32>(data).ok())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        #[cfg(target_os = "linux")]
+        if flags2 & FUSE_SECURITY_CTX > 0 && self.mount_options.security_context {
+            debug!("enable FUSE_SECURITY_CTX");
+
+            reply_flags |= FUSE_INIT_EXT;
+            reply_flags2 |= FUSE_SECURITY_CTX;
+        }
+
         // TODO: pass init_in to init, so the file system will know which flags are in use.
         let reply = match fs.init(request).await {
             Err(err) => {
-                let init_out_header = fuse_out_header {
-                    len: FUSE_OUT_HEADER_SIZE as u32,
-                    error: err.into(),
-                    unique: request.unique,
-                };
-
-                let init_out_header_data = get_bincode_config()
-                    .serialize(&init_out_header)
-                    .expect("won't happened");
+                let mut init_out_header_data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+                protocol::encode_out_header(
+                    &mut init_out_header_data,
+                    request.unique,
+                    err.into(),
+                    0,
+                );
 
                 if let Err(err) = fuse_connection
                     .write_vectored::<_, Vec<u8>>(init_out_header_data, None)
@@ -1246,36 +2526,81 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(reply) => reply,
         };
 
+        // kernel always takes the inode 1 as the root, a root which isn't a directory makes the
+        // mount unusable, fail the mount early with a clear error instead
+        if let Some(kind) = Self::check_root(request, fs).await {
+            error!(
+                "the root inode {} is {:?} which isn't a directory, request unique {}",
+                FUSE_ROOT_ID, kind, request.unique
+            );
+
+            let mut init_out_header_data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+            protocol::encode_out_header(
+                &mut init_out_header_data,
+                request.unique,
+                Errno::from(libc::ENOTDIR).into(),
+                0,
+            );
+
+            if let Err(err) = fuse_connection
+                .write_vectored::<_, Vec<u8>>(init_out_header_data, None)
+                .await
+                .1
+            {
+                error!("write error init out data to /dev/fuse failed {}", err);
+            }
+
+            // the init has succeeded, the filesystem is destroyed like a normal unmount
+            fs.destroy(request).await;
+
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("the root inode {FUSE_ROOT_ID} is {kind:?}, not a directory"),
+            ));
+        }
+
+        // kernel doesn't tell us the max write size it can accept, but it can't send a write
+        // request which is larger than the max pages of a request
+        let kernel_max_write = if init_in.flags & FUSE_MAX_PAGES > 0 {
+            u32::MAX
+        } else {
+            FUSE_DEFAULT_MAX_PAGES_PER_REQ.saturating_mul(page_size())
+        };
+
+        let mut max_write = reply.max_write.get().min(kernel_max_write);
+        if let Some(mount_max_write) = self.mount_options.max_write {
+            max_write = max_write.min(mount_max_write.get());
+        }
+        let max_write = NonZeroU32::new(max_write).expect("max_write can't be 0");
+
+        debug!(max_write, "negotiated max_write");
+
+        let (max_background, congestion_threshold) = self.mount_options.background_limits();
+
         let init_out = fuse_init_out {
             major: FUSE_KERNEL_VERSION,
             minor: FUSE_KERNEL_MINOR_VERSION,
             max_readahead: init_in.max_readahead,
             flags: reply_flags,
-            max_background: DEFAULT_MAX_BACKGROUND,
-            congestion_threshold: DEFAULT_CONGESTION_THRESHOLD,
-            max_write: reply.max_write.get(),
+            max_background,
+            congestion_threshold,
+            max_write: max_write.get(),
             time_gran: DEFAULT_TIME_GRAN,
             max_pages: DEFAULT_MAX_PAGES,
             map_alignment: DEFAULT_MAP_ALIGNMENT,
-            unused: [0; 8],
+            #[cfg(target_os = "linux")]
+            flags2: reply_flags2,
+            #[cfg(not(target_os = "linux"))]
+            flags2: 0,
+            unused: [0; 7],
         };
 
         debug!("fuse init out {:?}", init_out);
 
-        let out_header = fuse_out_header {
-            len: (FUSE_OUT_HEADER_SIZE + FUSE_INIT_OUT_SIZE) as u32,
-            error: 0,
-            unique: request.unique,
-        };
-
-        let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_INIT_OUT_SIZE);
+        let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_INIT_OUT_SIZE);
 
-        get_bincode_config()
-            .serialize_into(&mut data, &out_header)
-            .expect("won't happened");
-        get_bincode_config()
-            .serialize_into(&mut data, &init_out)
-            .expect("won't happened");
+        protocol::encode_out_header(&mut data, request.unique, 0, FUSE_INIT_OUT_SIZE);
+        protocol::encode_struct(&mut data, &init_out);
 
         if let Err(err) = fuse_connection
             .write_vectored::<_, Vec<u8>>(data, None)
@@ -1289,76 +2614,81 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
         debug!("fuse init done");
 
-        Ok(reply.max_write)
+        Ok(max_write)
     }
 
     #[instrument(skip(self, data, fs))]
     async fn handle_lookup(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let name = match get_first_null_position(data) {
-            None => {
-                error!("lookup body has no null, request unique {}", request.unique);
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        let name = match protocol::decode_name_body(data) {
+            Err(err) => {
+                reply_decode_error("lookup", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(name) => name,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_lookup"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "lookup unique {} name {:?} in parent {}",
                 request.unique, name, in_header.nodeid
             );
 
-            let data = match fs.lookup(request, in_header.nodeid, &name).await {
+            let result = if name == ".." {
+                fs.lookup_parent(request, in_header.nodeid).await
+            } else {
+                fs.lookup(request, in_header.nodeid, &name).await
+            };
+
+            let data = match result {
                 Err(err) => {
-                    let out_header = fuse_out_header {
-                        len: FUSE_OUT_HEADER_SIZE as u32,
-                        error: err.into(),
-                        unique: request.unique,
-                    };
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    get_bincode_config()
-                        .serialize(&out_header)
-                        .expect("won't happened")
+                    return;
                 }
 
-                Ok(entry) => {
+                Ok(mut entry) => {
+                    default_mode.apply(&mut entry.attr);
+
                     let entry_out: fuse_entry_out = entry.into();
 
-                    debug!("lookup response {:?}", entry_out);
+                    if let Some(lookups) = &lookups {
+                        lookups.lookup(entry_out.nodeid, entry_out.generation);
+                    }
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    if entry_out.nodeid == 0 {
+                        debug!(
+                            "lookup unique {} name {:?} is negative",
+                            request.unique, name
+                        );
+                    }
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
+                    debug!("lookup response {:?}", entry_out);
+
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &entry_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ENTRY_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &entry_out);
 
                     data
                 }
             };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -1367,11 +2697,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_forget(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let forget_in = match get_bincode_config().deserialize::<fuse_forget_in>(data) {
+        let forget_in = match protocol::decode_body::<fuse_forget_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_forget_in failed {}, request unique {}",
@@ -1385,9 +2716,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(forget_in) => forget_in,
         };
 
+        if let Some(lookups) = &self.lookups {
+            lookups.forget(in_header.nodeid, forget_in.nlookup);
+        }
+
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_forget"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "forget unique {} inode {} nlookup {}",
                 request.unique, in_header.nodeid, forget_in.nlookup
@@ -1402,18 +2737,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_getattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let getattr_in = match get_bincode_config().deserialize::<fuse_getattr_in>(data) {
+        let getattr_in = match protocol::decode_body::<fuse_getattr_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_forget_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -1421,38 +2757,33 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(getattr_in) => getattr_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
 
-        spawn(debug_span!("fuse_getattr"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "getattr unique {} inode {}",
                 request.unique, in_header.nodeid
             );
 
-            let fh = if getattr_in.getattr_flags & FUSE_GETATTR_FH > 0 {
+            let flags = GetattrFlags::from(getattr_in.getattr_flags);
+            let fh = if flags.has_fh() {
                 Some(getattr_in.fh)
             } else {
                 None
             };
 
-            let data = match fs
-                .getattr(request, in_header.nodeid, fh, getattr_in.getattr_flags)
-                .await
-            {
+            let data = match fs.getattr(request, in_header.nodeid, fh, flags).await {
                 Err(err) => {
-                    let out_header = fuse_out_header {
-                        len: FUSE_OUT_HEADER_SIZE as u32,
-                        error: err.into(),
-                        unique: request.unique,
-                    };
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    get_bincode_config()
-                        .serialize(&out_header)
-                        .expect("won't happened")
+                    return;
                 }
 
-                Ok(attr) => {
+                Ok(mut attr) => {
+                    default_mode.apply(&mut attr.attr);
+
                     let attr_out = fuse_attr_out {
                         attr_valid: attr.ttl.as_secs(),
                         attr_valid_nsec: attr.ttl.subsec_nanos(),
@@ -1460,26 +2791,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                         attr: attr.attr.into(),
                     };
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
-
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &attr_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ATTR_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &attr_out);
 
                     data
                 }
             };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -1487,18 +2809,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_setattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let setattr_in = match get_bincode_config().deserialize::<fuse_setattr_in>(data) {
+        let setattr_in = match protocol::decode_body::<fuse_setattr_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_setattr_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -1506,10 +2829,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(setattr_in) => setattr_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
 
-        spawn(debug_span!("fuse_setattr"), async move {
+        self.spawn(priority, async move {
             let set_attr = SetAttr::from(&setattr_in);
 
             let fh = if setattr_in.valid & FATTR_FH > 0 {
@@ -1525,49 +2849,42 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let data = match fs.setattr(request, in_header.nodeid, fh, set_attr).await {
                 Err(err) => {
-                    let out_header = fuse_out_header {
-                        len: FUSE_OUT_HEADER_SIZE as u32,
-                        error: err.into(),
-                        unique: request.unique,
-                    };
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    get_bincode_config()
-                        .serialize(&out_header)
-                        .expect("won't happened")
+                    return;
                 }
 
-                Ok(attr) => {
-                    let attr_out: fuse_attr_out = attr.into();
+                Ok(mut attr) => {
+                    default_mode.apply(&mut attr.attr);
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    let attr_out: fuse_attr_out = attr.into();
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ATTR_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &attr_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ATTR_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &attr_out);
 
                     data
                 }
             };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
     #[instrument(skip(self, fs))]
-    async fn handle_readlink(&mut self, request: Request, in_header: fuse_in_header, fs: &Arc<FS>) {
-        let mut resp_sender = self.response_sender.clone();
+    async fn handle_readlink(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        fs: &Arc<FS>,
+    ) {
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_readlink"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "readlink unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -1575,37 +2892,23 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let data = match fs.readlink(request, in_header.nodeid).await {
                 Err(err) => {
-                    let out_header = fuse_out_header {
-                        len: FUSE_OUT_HEADER_SIZE as u32,
-                        error: err.into(),
-                        unique: request.unique,
-                    };
+                    let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE);
 
-                    Either::Left(
-                        get_bincode_config()
-                            .serialize(&out_header)
-                            .expect("won't happened"),
-                    )
+                    protocol::encode_out_header(&mut data, request.unique, err.into(), 0);
+
+                    Either::Left(data)
                 }
 
                 Ok(data) => {
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + data.data.len()) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    let mut data_buf = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE);
 
-                    let mut data_buf = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
-
-                    get_bincode_config()
-                        .serialize_into(&mut data_buf, &out_header)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data_buf, request.unique, 0, data.data.len());
 
                     Either::Right((data_buf, data.data))
                 }
             };
 
-            let _ = resp_sender.send(data).await;
+            send_reply(data, request, resp_sender).await;
         });
     }
 
@@ -1613,87 +2916,78 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_symlink(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let (name, first_null_index) = match get_first_null_position(data) {
-            None => {
-                error!("symlink has no null, request unique {}", request.unique);
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Some(index) => (OsString::from_vec(data[..index].to_vec()), index),
-        };
-
-        data = &data[first_null_index + 1..];
-
-        let link_name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "symlink has no second null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        let SymlinkIn {
+            name,
+            link_name,
+            security_context,
+        } = match protocol::decode_symlink(&in_header, data) {
+            Err(err) => {
+                reply_decode_error("symlink", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(symlink) => symlink,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_symlink"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "symlink unique {} parent {} name {:?} link {:?}",
                 request.unique, in_header.nodeid, name, link_name
             );
 
             let data = match fs
-                .symlink(request, in_header.nodeid, &name, &link_name)
+                .symlink(
+                    request,
+                    in_header.nodeid,
+                    &name,
+                    &link_name,
+                    security_context,
+                )
                 .await
             {
                 Err(err) => {
-                    let out_header = fuse_out_header {
-                        len: FUSE_OUT_HEADER_SIZE as u32,
-                        error: err.into(),
-                        unique: request.unique,
-                    };
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(entry) if entry.attr.ino == 0 => {
+                    reply_invalid_entry("symlink", request, resp_sender).await;
 
-                    get_bincode_config()
-                        .serialize(&out_header)
-                        .expect("won't happened")
+                    return;
                 }
 
-                Ok(entry) => {
+                Ok(mut entry) => {
+                    default_mode.apply(&mut entry.attr);
+
                     let entry_out: fuse_entry_out = entry.into();
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    if let Some(lookups) = &lookups {
+                        lookups.lookup(entry_out.nodeid, entry_out.generation);
+                    }
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &entry_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ENTRY_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &entry_out);
 
                     data
                 }
             };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -1701,46 +2995,31 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_mknod(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let mknod_in = match get_bincode_config().deserialize::<fuse_mknod_in>(data) {
+        let MknodIn {
+            mknod_in,
+            name,
+            security_context,
+        } = match protocol::decode_mknod(&in_header, data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_mknod_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Ok(mknod_in) => mknod_in,
-        };
-
-        data = &data[FUSE_MKNOD_IN_SIZE..];
-
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_mknod_in body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("mknod", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(mknod) => mknod,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_mknod"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "mknod unique {} parent {} name {:?} {:?}",
                 request.unique, in_header.nodeid, name, mknod_in
@@ -1753,6 +3032,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &name,
                     mknod_in.mode,
                     mknod_in.rdev,
+                    security_context,
                 )
                 .await
             {
@@ -1760,25 +3040,26 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     reply_error_in_place(err, request, resp_sender).await;
                 }
 
-                Ok(entry) => {
+                Ok(entry) if entry.attr.ino == 0 => {
+                    reply_invalid_entry("mknod", request, resp_sender).await;
+                }
+
+                Ok(mut entry) => {
+                    default_mode.apply(&mut entry.attr);
+
                     let entry_out: fuse_entry_out = entry.into();
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    if let Some(lookups) = &lookups {
+                        lookups.lookup(entry_out.nodeid, entry_out.generation);
+                    }
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &entry_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ENTRY_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &entry_out);
 
-                    let _ = resp_sender.send(Either::Left(data)).await;
+                    send_reply(Either::Left(data), request, resp_sender).await;
                 }
             }
         });
@@ -1788,50 +3069,35 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_mkdir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let mkdir_in = match get_bincode_config().deserialize::<fuse_mkdir_in>(data) {
+        let MkdirIn {
+            mkdir_in,
+            name,
+            security_context,
+        } = match protocol::decode_mkdir(&in_header, data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_mknod_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("mkdir", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(mkdir_in) => mkdir_in,
+            Ok(mkdir) => mkdir,
         };
 
-        data = &data[FUSE_MKDIR_IN_SIZE..];
-
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "deserialize fuse_mknod_in doesn't have null unique {}",
-                    request.unique
-                );
+        let resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
-        };
-
-        let mut resp_sender = self.response_sender.clone();
-        let fs = fs.clone();
-
-        spawn(debug_span!("fuse_mkdir"), async move {
-            debug!(
-                "mkdir unique {} parent {} name {:?} {:?}",
-                request.unique, in_header.nodeid, name, mkdir_in
-            );
+        self.spawn(priority, async move {
+            debug!(
+                "mkdir unique {} parent {} name {:?} {:?}",
+                request.unique, in_header.nodeid, name, mkdir_in
+            );
 
             match fs
                 .mkdir(
@@ -1840,6 +3106,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &name,
                     mkdir_in.mode,
                     mkdir_in.umask,
+                    security_context,
                 )
                 .await
             {
@@ -1847,25 +3114,26 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     reply_error_in_place(err, request, resp_sender).await;
                 }
 
-                Ok(entry) => {
+                Ok(entry) if entry.attr.ino == 0 => {
+                    reply_invalid_entry("mkdir", request, resp_sender).await;
+                }
+
+                Ok(mut entry) => {
+                    default_mode.apply(&mut entry.attr);
+
                     let entry_out: fuse_entry_out = entry.into();
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    if let Some(lookups) = &lookups {
+                        lookups.lookup(entry_out.nodeid, entry_out.generation);
+                    }
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &entry_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ENTRY_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &entry_out);
 
-                    let _ = resp_sender.send(Either::Left(data)).await;
+                    send_reply(Either::Left(data), request, resp_sender).await;
                 }
             }
         });
@@ -1875,51 +3143,34 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_unlink(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "unlink body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        let name = match protocol::decode_name_body(data) {
+            Err(err) => {
+                reply_decode_error("unlink", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(name) => name,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_unlink"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "unlink unique {} parent {} name {:?}",
                 request.unique, in_header.nodeid, name
             );
 
-            let resp_value = if let Err(err) = fs.unlink(request, in_header.nodeid, &name).await {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match fs.unlink(request, in_header.nodeid, &name).await {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -1927,51 +3178,34 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_rmdir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "rmdir body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        let name = match protocol::decode_name_body(data) {
+            Err(err) => {
+                reply_decode_error("rmdir", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(name) => name,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rmdir"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "rmdir unique {} parent {} name {:?}",
                 request.unique, in_header.nodeid, name
             );
 
-            let resp_value = if let Err(err) = fs.rmdir(request, in_header.nodeid, &name).await {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match fs.rmdir(request, in_header.nodeid, &name).await {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -1979,94 +3213,42 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_rename(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let rename_in = match get_bincode_config().deserialize::<fuse_rename_in>(data) {
+        let RenameIn {
+            new_dir,
+            name,
+            new_name,
+            ..
+        } = match protocol::decode_rename(data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_rename_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Ok(rename_in) => rename_in,
-        };
-
-        data = &data[FUSE_RENAME_IN_SIZE..];
-
-        let (name, first_null_index) = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_rename_in body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Some(index) => (OsString::from_vec(data[..index].to_vec()), index),
-        };
-
-        data = &data[first_null_index + 1..];
-
-        let new_name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_rename_in body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("rename", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(rename) => rename,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rename"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "rename unique {} parent {} name {:?} new parent {} new name {:?}",
-                request.unique, in_header.nodeid, name, rename_in.newdir, new_name
+                request.unique, in_header.nodeid, name, new_dir, new_name
             );
 
-            let resp_value = if let Err(err) = fs
-                .rename(
-                    request,
-                    in_header.nodeid,
-                    &name,
-                    rename_in.newdir,
-                    &new_name,
-                )
+            match fs
+                .rename(request, in_header.nodeid, &name, new_dir, &new_name)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2074,46 +3256,27 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_link(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let link_in = match get_bincode_config().deserialize::<fuse_link_in>(data) {
+        let LinkIn { link_in, name } = match protocol::decode_link(data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_link_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Ok(link_in) => link_in,
-        };
-
-        data = &data[FUSE_LINK_IN_SIZE..];
-
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_link_in body doesn't have null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("link", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(link) => link,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_link"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "link unique {} inode {} new parent {} new name {:?}",
                 request.unique, link_in.oldnodeid, in_header.nodeid, name
@@ -2127,25 +3290,26 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     reply_error_in_place(err, request, resp_sender).await;
                 }
 
-                Ok(entry) => {
+                Ok(entry) if entry.attr.ino == 0 => {
+                    reply_invalid_entry("link", request, resp_sender).await;
+                }
+
+                Ok(mut entry) => {
+                    default_mode.apply(&mut entry.attr);
+
                     let entry_out: fuse_entry_out = entry.into();
 
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
+                    if let Some(lookups) = &lookups {
+                        lookups.lookup(entry_out.nodeid, entry_out.generation);
+                    }
 
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
+                    let mut data =
+                        ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE);
 
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &entry_out)
-                        .expect("won't happened");
+                    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_ENTRY_OUT_SIZE);
+                    protocol::encode_struct(&mut data, &entry_out);
 
-                    let _ = resp_sender.send(Either::Left(data)).await;
+                    send_reply(Either::Left(data), request, resp_sender).await;
                 }
             }
         });
@@ -2155,18 +3319,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_open(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let open_in = match get_bincode_config().deserialize::<fuse_open_in>(data) {
+        let open_in = match protocol::decode_body::<fuse_open_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_open_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2174,10 +3339,22 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(open_in) => open_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        if self.mount_options.read_only && is_write_open(open_in.flags) {
+            debug!(
+                "open flags {:#x} is rejected by read only mount, request unique {}",
+                open_in.flags, request.unique
+            );
+
+            reply_error_in_place(libc::EROFS.into(), request, &mut self.response_sender).await;
+
+            return;
+        }
+
+        let keep_cache = self.mount_options.keep_cache;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_open"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "open unique {} inode {} flags {}",
                 request.unique, in_header.nodeid, open_in.flags
@@ -2193,24 +3370,15 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(opened) => opened,
             };
 
-            let open_out: fuse_open_out = opened.into();
+            let mut open_out: fuse_open_out = opened.into();
+            open_out.open_flags = keep_cache_flags(open_out.open_flags, keep_cache);
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &open_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_OPEN_OUT_SIZE);
+            protocol::encode_struct(&mut data, &open_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -2218,18 +3386,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_read(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let read_in = match get_bincode_config().deserialize::<fuse_read_in>(data) {
+        let read_in = match protocol::decode_body::<fuse_read_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_read_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2237,10 +3406,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(read_in) => read_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_read"), async move {
+        self.spawn_bulk(priority, async move {
             debug!(
                 "read unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, read_in
@@ -2266,78 +3435,67 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             };
 
             if reply_data.len() > read_in.size as _ {
+                debug!(
+                    "read reply data len {} is larger than request size {}, truncate it",
+                    reply_data.len(),
+                    read_in.size
+                );
+
                 reply_data.truncate(read_in.size as _);
             }
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + reply_data.len()) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data_buf = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+            let mut data_buf = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data_buf, &out_header)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data_buf, request.unique, 0, reply_data.len());
 
-            let _ = resp_sender
-                .send(Either::Right((data_buf, reply_data)))
-                .await;
+            send_reply(Either::Right((data_buf, reply_data)), request, resp_sender).await;
         });
     }
 
-    #[instrument(skip(self, data, fs))]
+    #[instrument(skip(self, data, write_buffer, fs))]
     async fn handle_write(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
+        write_buffer: Option<Bytes>,
         fs: &Arc<FS>,
     ) {
-        let write_in = match get_bincode_config().deserialize::<fuse_write_in>(data) {
+        let WriteIn { write_in, data } = match protocol::decode_write(data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_write_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("write", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(write_in) => write_in,
+            Ok(write) => write,
         };
 
-        data = &data[FUSE_WRITE_IN_SIZE..];
-
-        if write_in.size as usize != data.len() {
-            error!("fuse_write_in body len is invalid");
-
-            reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-            return;
-        }
-
-        let data = data.to_vec();
+        // the large write data is a part of its own request buffer, the small write data is
+        // copied once here because the request buffer is reused by the next request
+        let data = match write_buffer {
+            Some(write_buffer) => write_buffer.slice_ref(data),
+            None => Bytes::copy_from_slice(data),
+        };
+        let data_len = data.len();
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_write"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "write unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, write_in
             );
 
             let reply_write = match fs
-                .write(
+                .write_bytes(
                     request,
                     in_header.nodeid,
                     write_in.fh,
                     write_in.offset,
-                    &data,
+                    data,
                     write_in.write_flags,
                     write_in.flags,
                 )
@@ -2352,33 +3510,36 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(reply_write) => reply_write,
             };
 
-            let write_out: fuse_write_out = reply_write.into();
+            if reply_write.written as usize > data_len {
+                warn!(
+                    "write reply written {} is greater than the data length {}, request unique {}",
+                    reply_write.written, data_len, request.unique
+                );
+            }
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
+            let write_out: fuse_write_out = reply_write.into();
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &write_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_WRITE_OUT_SIZE);
+            protocol::encode_struct(&mut data, &write_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
     #[instrument(skip(self, fs))]
-    async fn handle_statfs(&mut self, request: Request, in_header: fuse_in_header, fs: &Arc<FS>) {
-        let mut resp_sender = self.response_sender.clone();
+    async fn handle_statfs(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        fs: &Arc<FS>,
+    ) {
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_statfs"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "statfs unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2394,24 +3555,21 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(fs_stat) => fs_stat,
             };
 
-            let statfs_out: fuse_statfs_out = fs_stat.into();
+            if fs_stat.bsize == 0 {
+                warn!(
+                    "statfs reply bsize is 0, the block size is unknown, request unique {}",
+                    request.unique
+                );
+            }
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
+            let statfs_out: fuse_statfs_out = fs_stat.into();
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &statfs_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_STATFS_OUT_SIZE);
+            protocol::encode_struct(&mut data, &statfs_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -2419,18 +3577,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_release(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let release_in = match get_bincode_config().deserialize::<fuse_release_in>(data) {
+        let release_in = match protocol::decode_body::<fuse_release_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_release_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2438,49 +3597,36 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(release_in) => release_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_release"), async move {
-            let flush = release_in.release_flags & FUSE_RELEASE_FLUSH > 0;
+        self.spawn(priority, async move {
+            let release_flags = ReleaseFlags::from(release_in.release_flags);
 
             debug!(
-                "release unique {} inode {} fh {} flags {} lock_owner {} flush {}",
+                "release unique {} inode {} fh {} flags {} lock_owner {} release_flags {:?}",
                 request.unique,
                 in_header.nodeid,
                 release_in.fh,
                 release_in.flags,
                 release_in.lock_owner,
-                flush
+                release_flags
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .release(
                     request,
                     in_header.nodeid,
                     release_in.fh,
                     release_in.flags,
                     release_in.lock_owner,
-                    flush,
+                    release_flags,
                 )
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2488,18 +3634,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_fsync(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let fsync_in = match get_bincode_config().deserialize::<fuse_fsync_in>(data) {
+        let fsync_in = match protocol::decode_body::<fuse_fsync_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_fsync_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2507,10 +3654,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(fsync_in) => fsync_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fsync"), async move {
+        self.spawn(priority, async move {
             let data_sync = fsync_in.fsync_flags & 1 > 0;
 
             debug!(
@@ -2518,26 +3665,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 request.unique, in_header.nodeid, fsync_in.fh, data_sync
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .fsync(request, in_header.nodeid, fsync_in.fh, data_sync)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2545,67 +3679,49 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_setxattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let setxattr_in = match get_bincode_config().deserialize::<fuse_setxattr_in>(data) {
+        let SetxattrIn {
+            setxattr_in,
+            name,
+            value,
+        } = match protocol::decode_setxattr(data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_setxattr_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("setxattr", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(setxattr_in) => setxattr_in,
+            Ok(setxattr) => setxattr,
         };
 
-        data = &data[FUSE_SETXATTR_IN_SIZE..];
-
-        let (name, first_null_index) = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_setxattr_in body has no null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Some(index) => (OsString::from_vec(data[..index].to_vec()), index),
-        };
-
-        data = &data[first_null_index + 1..];
-
-        // setxattr "size" field specifies size of only "Value" part of data
-        if setxattr_in.size as usize != data.len() {
-            error!(
-                "fuse_setxattr_in value field data length is not right, request unique {} setxattr_in.size={} data.len={}", request.unique, setxattr_in.size, data.len());
+        if !self.mount_options.is_xattr_allowed(&name) {
+            debug!(
+                "xattr {:?} namespace is not allowed, request unique {}",
+                name, request.unique
+            );
 
-            reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+            reply_error_in_place(libc::EOPNOTSUPP.into(), request, &mut self.response_sender).await;
 
             return;
         }
 
-        let data = data.to_vec();
+        let data = value.to_vec();
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_setxattr"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "setxattr unique {} inode {}",
                 request.unique, in_header.nodeid
             );
 
             // TODO handle os X argument
-            let resp_value = if let Err(err) = fs
+            match fs
                 .setxattr(
                     request,
                     in_header.nodeid,
@@ -2616,22 +3732,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 )
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2639,43 +3742,36 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_getxattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let getxattr_in = match get_bincode_config().deserialize::<fuse_getxattr_in>(data) {
+        let GetxattrIn { getxattr_in, name } = match protocol::decode_getxattr(data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_getxattr_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("getxattr", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(getxattr_in) => getxattr_in,
+            Ok(getxattr) => getxattr,
         };
 
-        data = &data[FUSE_GETXATTR_IN_SIZE..];
-
-        let name = match get_first_null_position(data) {
-            None => {
-                error!("fuse_getxattr_in body has no null {}", request.unique);
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        if !self.mount_options.is_xattr_allowed(&name) {
+            debug!(
+                "xattr {:?} namespace is not allowed, request unique {}",
+                name, request.unique
+            );
 
-                return;
-            }
+            reply_error_in_place(libc::EOPNOTSUPP.into(), request, &mut self.response_sender).await;
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
-        };
+            return;
+        }
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_getxattr"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "getxattr unique {} inode {}",
                 request.unique, in_header.nodeid
@@ -2694,48 +3790,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(xattr) => xattr,
             };
 
-            let data = match xattr {
-                ReplyXAttr::Size(size) => {
-                    let getxattr_out = fuse_getxattr_out { size, _padding: 0 };
-
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE) as u32,
-                        error: libc::ERANGE,
-                        unique: request.unique,
-                    };
-
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
-
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &getxattr_out)
-                        .expect("won't happened");
+            let data = match encode_xattr_reply(xattr, getxattr_in.size, request) {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    Either::Left(data)
+                    return;
                 }
 
-                ReplyXAttr::Data(xattr_data) => {
-                    // TODO check is right way or not
-                    // TODO should we check data length or not
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + xattr_data.len()) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
-
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
-
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-
-                    Either::Right((data, xattr_data))
-                }
+                Ok(data) => data,
             };
 
-            let _ = resp_sender.send(data).await;
+            send_reply(data, request, resp_sender).await;
         });
     }
 
@@ -2743,18 +3808,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_listxattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let listxattr_in = match get_bincode_config().deserialize::<fuse_getxattr_in>(data) {
+        let listxattr_in = match protocol::decode_body::<fuse_getxattr_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_getxattr_in in listxattr failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2762,10 +3828,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(listxattr_in) => listxattr_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_listxattr"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "listxattr unique {} inode {} size {}",
                 request.unique, in_header.nodeid, listxattr_in.size
@@ -2784,48 +3850,17 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(xattr) => xattr,
             };
 
-            let data = match xattr {
-                ReplyXAttr::Size(size) => {
-                    let getxattr_out = fuse_getxattr_out { size, _padding: 0 };
-
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
-
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATFS_OUT_SIZE);
-
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-                    get_bincode_config()
-                        .serialize_into(&mut data, &getxattr_out)
-                        .expect("won't happened");
+            let data = match encode_xattr_reply(xattr, listxattr_in.size, request) {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
 
-                    Either::Left(data)
+                    return;
                 }
 
-                ReplyXAttr::Data(xattr_data) => {
-                    // TODO check is right way or not
-                    // TODO should we check data length or not
-                    let out_header = fuse_out_header {
-                        len: (FUSE_OUT_HEADER_SIZE + xattr_data.len()) as u32,
-                        error: 0,
-                        unique: request.unique,
-                    };
-
-                    let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
-
-                    get_bincode_config()
-                        .serialize_into(&mut data, &out_header)
-                        .expect("won't happened");
-
-                    Either::Right((data, xattr_data))
-                }
+                Ok(data) => data,
             };
 
-            let _ = resp_sender.send(data).await;
+            send_reply(data, request, resp_sender).await;
         });
     }
 
@@ -2833,52 +3868,45 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_removexattr(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse removexattr body has no null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+        let name = match protocol::decode_name_body(data) {
+            Err(err) => {
+                reply_decode_error("removexattr", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(name) => name,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        if !self.mount_options.is_xattr_allowed(&name) {
+            debug!(
+                "xattr {:?} namespace is not allowed, request unique {}",
+                name, request.unique
+            );
+
+            reply_error_in_place(libc::EOPNOTSUPP.into(), request, &mut self.response_sender).await;
+
+            return;
+        }
+
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_removexattr"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "removexattr unique {} inode {}",
                 request.unique, in_header.nodeid
             );
 
-            let resp_value =
-                if let Err(err) = fs.removexattr(request, in_header.nodeid, &name).await {
-                    err.into()
-                } else {
-                    0
-                };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match fs.removexattr(request, in_header.nodeid, &name).await {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2886,18 +3914,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_flush(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let flush_in = match get_bincode_config().deserialize::<fuse_flush_in>(data) {
+        let flush_in = match protocol::decode_body::<fuse_flush_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_flush_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2905,35 +3934,22 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(flush_in) => flush_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_flush"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "flush unique {} inode {} fh {} lock_owner {}",
                 request.unique, in_header.nodeid, flush_in.fh, flush_in.lock_owner
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .flush(request, in_header.nodeid, flush_in.fh, flush_in.lock_owner)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -2941,18 +3957,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_opendir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let open_in = match get_bincode_config().deserialize::<fuse_open_in>(data) {
+        let open_in = match protocol::decode_body::<fuse_open_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_open_in in opendir failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -2960,10 +3977,11 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(open_in) => open_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let keep_cache = self.mount_options.keep_cache;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_opendir"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "opendir unique {} inode {} flags {}",
                 request.unique, in_header.nodeid, open_in.flags
@@ -2979,24 +3997,15 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 Ok(reply_open) => reply_open,
             };
 
-            let open_out: fuse_open_out = reply_open.into();
-
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
+            let mut open_out: fuse_open_out = reply_open.into();
+            open_out.open_flags = keep_cache_flags(open_out.open_flags, keep_cache);
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &open_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_OPEN_OUT_SIZE);
+            protocol::encode_struct(&mut data, &open_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -3004,24 +4013,25 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_readdir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
         if self.mount_options.force_readdir_plus {
-            reply_error_in_place(libc::ENOSYS.into(), request, &self.response_sender).await;
+            reply_error_in_place(libc::ENOSYS.into(), request, &mut self.response_sender).await;
 
             return;
         }
 
-        let read_in = match get_bincode_config().deserialize::<fuse_read_in>(data) {
+        let read_in = match protocol::decode_body::<fuse_read_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_read_in in readdir failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3029,33 +4039,86 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(read_in) => read_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let synthesize_dot_entries = self.mount_options.synthesize_dot_entries;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_readdir"), async move {
+        self.spawn_bulk(priority, async move {
             debug!(
                 "readdir unique {} inode {} fh {} offset {}",
                 request.unique, in_header.nodeid, read_in.fh, read_in.offset
             );
 
-            let reply_readdir = match fs
+            let (parent, entries) = match fs
                 .readdir(request, in_header.nodeid, read_in.fh, read_in.offset as i64)
                 .await
             {
+                // the filesystem only implements readdirplus, adapt it by discarding the attrs
+                Err(err) if err == libc::ENOSYS.into() => {
+                    if !READDIR_FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+                        warn!(
+                            "readdir is not implemented, fallback to readdirplus, the lookup \
+                             references taken by readdirplus are never forgotten by kernel"
+                        );
+                    }
+
+                    debug!(
+                        "readdir is not implemented, fallback to readdirplus, request unique {}",
+                        request.unique
+                    );
+
+                    match fs
+                        .readdirplus(request, in_header.nodeid, read_in.fh, read_in.offset, 0)
+                        .await
+                    {
+                        Err(err) => {
+                            reply_error_in_place(err, request, resp_sender).await;
+
+                            return;
+                        }
+
+                        Ok(reply_readdirplus) => (
+                            reply_readdirplus.parent,
+                            reply_readdirplus
+                                .entries
+                                .map(|entry| {
+                                    entry.map(|entry| DirectoryEntry {
+                                        inode: entry.inode,
+                                        kind: entry.kind,
+                                        name: entry.name,
+                                        offset: entry.offset,
+                                    })
+                                })
+                                .right_stream(),
+                        ),
+                    }
+                }
+
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
                     return;
                 }
 
-                Ok(reply_readdir) => reply_readdir,
+                Ok(reply_readdir) => (reply_readdir.parent, reply_readdir.entries.left_stream()),
+            };
+
+            let dot_entries = match parent {
+                Some(parent) if synthesize_dot_entries => {
+                    dot_entries(in_header.nodeid, parent, read_in.offset)
+                }
+
+                _ => vec![],
             };
 
             let max_size = read_in.size as usize;
 
-            let mut entry_data = Vec::with_capacity(max_size);
+            // the header is backfilled after the entries are encoded, so the reply is sent in one
+            // buffer
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + max_size);
+            data.resize(FUSE_OUT_HEADER_SIZE, 0);
 
-            let entries = reply_readdir.entries;
+            let entries = stream::iter(dot_entries.into_iter().map(Ok)).chain(entries);
             let mut entries = pin!(entries);
 
             while let Some(entry) = entries.next().await {
@@ -3073,47 +4136,28 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
                 let dir_entry_size = FUSE_DIRENT_SIZE + name.len();
 
-                let padding_size = get_padding_size(dir_entry_size);
-
-                if entry_data.len() + dir_entry_size > max_size {
+                if data.len() - FUSE_OUT_HEADER_SIZE + dir_entry_size > max_size {
                     break;
                 }
 
-                let dir_entry = fuse_dirent {
-                    ino: entry.inode,
-                    off: entry.offset as u64,
-                    namelen: name.len() as u32,
-                    // learn from fuse-rs and golang bazil.org fuse DirentType
-                    r#type: mode_from_kind_and_perm(entry.kind, 0) >> 12,
-                };
-
-                get_bincode_config()
-                    .serialize_into(&mut entry_data, &dir_entry)
-                    .expect("won't happened");
-
-                entry_data.extend_from_slice(name.as_bytes());
-
-                // padding
-                entry_data.resize(entry_data.len() + padding_size, 0);
+                protocol::encode_dirent(
+                    &mut data,
+                    entry.inode,
+                    entry.offset as u64,
+                    entry.kind,
+                    name,
+                );
             }
 
-            // TODO find a way to avoid multi allocate
-
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + entry_data.len()) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
-
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
+            let body_len = data.len() - FUSE_OUT_HEADER_SIZE;
+            protocol::encode_out_header(
+                &mut &mut data[..FUSE_OUT_HEADER_SIZE],
+                request.unique,
+                0,
+                body_len,
+            );
 
-            let _ = resp_sender
-                .send(Either::Right((data, entry_data.into())))
-                .await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -3121,18 +4165,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_releasedir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let release_in = match get_bincode_config().deserialize::<fuse_release_in>(data) {
+        let release_in = match protocol::decode_body::<fuse_release_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_release_in in releasedir failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3140,35 +4185,22 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(release_in) => release_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_releasedir"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "releasedir unique {} inode {} fh {} flags {}",
                 request.unique, in_header.nodeid, release_in.fh, release_in.flags
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .releasedir(request, in_header.nodeid, release_in.fh, release_in.flags)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3176,18 +4208,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_fsyncdir(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let fsync_in = match get_bincode_config().deserialize::<fuse_fsync_in>(data) {
+        let fsync_in = match protocol::decode_body::<fuse_fsync_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_fsync_in in fsyncdir failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3195,10 +4228,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(fsync_in) => fsync_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fsyncdir"), async move {
+        self.spawn(priority, async move {
             let data_sync = fsync_in.fsync_flags & 1 > 0;
 
             debug!(
@@ -3206,26 +4239,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 request.unique, in_header.nodeid, fsync_in.fh, data_sync
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .fsyncdir(request, in_header.nodeid, fsync_in.fh, data_sync)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3234,18 +4254,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_getlk(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let getlk_in = match get_bincode_config().deserialize::<fuse_lk_in>(data) {
+        let getlk_in = match protocol::decode_body::<fuse_lk_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_lk_in in getlk failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3253,28 +4274,27 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(getlk_in) => getlk_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let Some(lock) = FileLock::from_raw(&getlk_in.lk, getlk_in.owner) else {
+            error!(
+                "getlk lock type {} is invalid, request unique {}",
+                getlk_in.lk.r#type, request.unique
+            );
+
+            reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
+
+            return;
+        };
+
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_getlk"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "getlk unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, getlk_in
             );
 
-            let reply_lock = match fs
-                .getlk(
-                    request,
-                    in_header.nodeid,
-                    getlk_in.fh,
-                    getlk_in.owner,
-                    getlk_in.lk.start,
-                    getlk_in.lk.end,
-                    getlk_in.lk.r#type,
-                    getlk_in.lk.pid,
-                )
-                .await
-            {
+            let reply_lock = match fs.getlk(request, in_header.nodeid, getlk_in.fh, lock).await {
                 Err(err) => {
                     reply_error_in_place(err, request, resp_sender).await;
 
@@ -3286,22 +4306,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let getlk_out: fuse_lk_out = reply_lock.into();
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_LK_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_LK_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_LK_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &getlk_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_LK_OUT_SIZE);
+            protocol::encode_struct(&mut data, &getlk_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -3310,12 +4320,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_setlk(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         block: bool,
         fs: &Arc<FS>,
     ) {
-        let setlk_in = match get_bincode_config().deserialize::<fuse_lk_in>(data) {
+        let setlk_in = match protocol::decode_body::<fuse_lk_in>(data) {
             Err(err) => {
                 let opcode = if block {
                     fuse_opcode::FUSE_SETLKW
@@ -3328,7 +4339,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     opcode, err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3336,45 +4347,93 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(setlk_in) => setlk_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        if setlk_in.lk_flags & FUSE_LK_FLOCK > 0 {
+            self.handle_flock(request, priority, in_header, setlk_in, block, fs);
+
+            return;
+        }
+
+        let Some(lock) = FileLock::from_raw(&setlk_in.lk, setlk_in.owner) else {
+            error!(
+                "setlk lock type {} is invalid, request unique {}",
+                setlk_in.lk.r#type, request.unique
+            );
+
+            reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
+
+            return;
+        };
+
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_setlk"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "setlk unique {} inode {} block {} {:?}",
                 request.unique, in_header.nodeid, block, setlk_in
             );
 
-            let resp = if let Err(err) = fs
-                .setlk(
-                    request,
-                    in_header.nodeid,
-                    setlk_in.fh,
-                    setlk_in.owner,
-                    setlk_in.lk.start,
-                    setlk_in.lk.end,
-                    setlk_in.lk.r#type,
-                    setlk_in.lk.pid,
-                    block,
-                )
+            match fs
+                .setlk(request, in_header.nodeid, setlk_in.fh, lock, block)
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
+        });
+    }
+
+    #[cfg(feature = "file-lock")]
+    fn handle_flock(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        flock_in: fuse_lk_in,
+        block: bool,
+        fs: &Arc<FS>,
+    ) {
+        let resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        self.spawn(priority, async move {
+            debug!(
+                "flock unique {} inode {} block {} {:?}",
+                request.unique, in_header.nodeid, block, flock_in
+            );
 
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp,
-                unique: request.unique,
+            // the kernel sends the flock operation as the posix lock type
+            let operation = match flock_in.lk.r#type as i32 {
+                libc::F_RDLCK => Ok(libc::LOCK_SH as u32),
+                libc::F_WRLCK => Ok(libc::LOCK_EX as u32),
+                libc::F_UNLCK => Ok(libc::LOCK_UN as u32),
+                _ => Err(Errno::from(libc::EINVAL)),
             };
 
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("can't serialize into vec");
+            let operation = match operation {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(operation) => operation,
+            };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match fs
+                .flock(
+                    request,
+                    in_header.nodeid,
+                    flock_in.fh,
+                    flock_in.owner,
+                    operation,
+                    block,
+                )
+                .await
+            {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3382,18 +4441,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_access(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let access_in = match get_bincode_config().deserialize::<fuse_access_in>(data) {
+        let access_in = match protocol::decode_body::<fuse_access_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_access_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3401,35 +4461,23 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(access_in) => access_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_access"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "access unique {} inode {} mask {}",
                 request.unique, in_header.nodeid, access_in.mask
             );
 
-            let resp_value =
-                if let Err(err) = fs.access(request, in_header.nodeid, access_in.mask).await {
-                    err.into()
-                } else {
-                    0
-                };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            debug!("access response {}", resp_value);
+            let result = fs.access(request, in_header.nodeid, access_in.mask).await;
 
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
+            debug!("access response {:?}", result);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match result {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3437,46 +4485,32 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_create(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let create_in = match get_bincode_config().deserialize::<fuse_create_in>(data) {
+        let CreateIn {
+            create_in,
+            name,
+            security_context,
+        } = match protocol::decode_create(&in_header, data) {
             Err(err) => {
-                error!(
-                    "deserialize fuse_create_in failed {}, request unique {}",
-                    err, request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_decode_error("create", err, request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(create_in) => create_in,
-        };
-
-        data = &data[FUSE_CREATE_IN_SIZE..];
-
-        let name = match get_first_null_position(data) {
-            None => {
-                error!(
-                    "fuse_create_in body has no null, request unique {}",
-                    request.unique
-                );
-
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
-
-                return;
-            }
-
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(create) => create,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let keep_cache = self.mount_options.keep_cache;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_create"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "create unique {} parent {} name {:?} mode {} flags {}",
                 request.unique, in_header.nodeid, name, create_in.mode, create_in.flags
@@ -3489,6 +4523,7 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     &name,
                     create_in.mode,
                     create_in.flags,
+                    security_context,
                 )
                 .await
             {
@@ -3498,44 +4533,59 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     return;
                 }
 
-                Ok(created) => created,
-            };
+                Ok(created) if created.attr.ino == 0 => {
+                    reply_invalid_entry("create", request, resp_sender).await;
+
+                    return;
+                }
 
-            let (entry_out, open_out): (fuse_entry_out, fuse_open_out) = created.into();
+                Ok(mut created) => {
+                    default_mode.apply(&mut created.attr);
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
+                    created
+                }
             };
 
-            let mut data =
-                Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE);
+            let (entry_out, mut open_out): (fuse_entry_out, fuse_open_out) = created.into();
+
+            if let Some(lookups) = &lookups {
+                lookups.lookup(entry_out.nodeid, entry_out.generation);
+            }
+            open_out.open_flags = keep_cache_flags(open_out.open_flags, keep_cache);
+
+            let mut data = ReplyBuffer::with_capacity(
+                FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE,
+            );
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &entry_out)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &open_out)
-                .expect("won't happened");
+            protocol::encode_out_header(
+                &mut data,
+                request.unique,
+                0,
+                FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE,
+            );
+            protocol::encode_struct(&mut data, &entry_out);
+            protocol::encode_struct(&mut data, &open_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
     #[instrument(skip(self, data, fs))]
-    async fn handle_interrupt(&mut self, request: Request, data: &[u8], fs: &Arc<FS>) {
-        let interrupt_in = match get_bincode_config().deserialize::<fuse_interrupt_in>(data) {
+    async fn handle_interrupt(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let interrupt_in = match protocol::decode_body::<fuse_interrupt_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_interrupt_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3543,32 +4593,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(interrupt_in) => interrupt_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_interrupt"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "interrupt_in unique {} interrupt unique {}",
                 request.unique, interrupt_in.unique
             );
 
-            let resp_value = if let Err(err) = fs.interrupt(request, interrupt_in.unique).await {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+            match fs.interrupt(request, interrupt_in.unique).await {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3576,18 +4613,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_bmap(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let bmap_in = match get_bincode_config().deserialize::<fuse_bmap_in>(data) {
+        let bmap_in = match protocol::decode_body::<fuse_bmap_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_bmap_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3595,10 +4633,10 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(bmap_in) => bmap_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_bmap"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "bmap unique {} inode {} block size {} idx {}",
                 request.unique, in_header.nodeid, bmap_in.blocksize, bmap_in.block
@@ -3619,22 +4657,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let bmap_out: fuse_bmap_out = reply_bmap.into();
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_BMAP_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &bmap_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_BMAP_OUT_SIZE);
+            protocol::encode_struct(&mut data, &bmap_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -3642,18 +4670,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_poll(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let poll_in = match get_bincode_config().deserialize::<fuse_poll_in>(data) {
+        let poll_in = match protocol::decode_body::<fuse_poll_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_poll_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3661,12 +4690,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(poll_in) => poll_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
         let notify = self.get_notify();
 
-        spawn(debug_span!("fuse_poll"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "poll unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, poll_in
@@ -3701,22 +4730,12 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let poll_out: fuse_poll_out = reply_poll.into();
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_POLL_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
-            };
-
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_POLL_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_POLL_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &poll_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_POLL_OUT_SIZE);
+            protocol::encode_struct(&mut data, &poll_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
@@ -3724,44 +4743,35 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_notify_reply(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
         let resp_sender = self.response_sender.clone();
 
-        let notify_retrieve_in =
-            match get_bincode_config().deserialize::<fuse_notify_retrieve_in>(data) {
-                Err(err) => {
-                    error!(
-                        "deserialize fuse_notify_retrieve_in failed {}, request unique {}",
-                        err, request.unique
-                    );
-
-                    // TODO need to reply or not?
-                    return;
-                }
-
-                Ok(notify_retrieve_in) => notify_retrieve_in,
-            };
-
-        data = &data[FUSE_NOTIFY_RETRIEVE_IN_SIZE..];
+        let NotifyReplyIn {
+            notify_retrieve_in,
+            data,
+        } = match protocol::decode_notify_reply(data) {
+            Err(err) => {
+                error!(
+                    "decode notify reply failed {}, request unique {}",
+                    err, request.unique
+                );
 
-        if data.len() < notify_retrieve_in.size as usize {
-            error!(
-                "fuse_notify_retrieve unique {} data size is not right",
-                request.unique
-            );
+                // TODO need to reply or not?
+                return;
+            }
 
-            // TODO need to reply or not?
-            return;
-        }
+            Ok(notify_reply) => notify_reply,
+        };
 
-        let data = data[..notify_retrieve_in.size as usize].to_vec();
+        let data = data.to_vec();
 
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_notify_reply"), async move {
+        self.spawn(priority, async move {
             if let Err(err) = fs
                 .notify_reply(
                     request,
@@ -3780,14 +4790,15 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_batch_forget(
         &mut self,
         request: Request,
+        priority: Priority,
         _in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let batch_forget_in = match get_bincode_config().deserialize::<fuse_batch_forget_in>(data) {
+        let forgets = match protocol::decode_batch_forget(data) {
             Err(err) => {
                 error!(
-                    "deserialize fuse_batch_forget_in failed {}, request unique {}",
+                    "decode batch_forget request failed {}, request unique {}",
                     err, request.unique
                 );
 
@@ -3795,43 +4806,18 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 return;
             }
 
-            Ok(batch_forget_in) => batch_forget_in,
+            Ok(forgets) => forgets,
         };
 
-        let mut forgets = vec![];
-
-        data = &data[FUSE_BATCH_FORGET_IN_SIZE..];
-
-        // TODO if has less data, should I return error?
-        while data.len() >= FUSE_FORGET_ONE_SIZE {
-            match get_bincode_config().deserialize::<fuse_forget_one>(data) {
-                Err(err) => {
-                    error!("deserialize fuse_batch_forget_in body fuse_forget_one failed {}, request unique {}", err, request.unique);
-
-                    // no need to reply
-                    return;
-                }
-
-                Ok(forget_one) => {
-                    data = &data[FUSE_FORGET_ONE_SIZE..];
-
-                    forgets.push(forget_one);
-                }
+        if let Some(lookups) = &self.lookups {
+            for forget_one in &forgets {
+                lookups.forget(forget_one.nodeid, forget_one.nlookup);
             }
         }
 
-        if forgets.len() != batch_forget_in.count as usize {
-            error!(
-                "fuse_forget_one count != fuse_batch_forget_in.count, request unique {}",
-                request.unique
-            );
-
-            return;
-        }
-
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_batch_forget"), async move {
+        self.spawn(priority, async move {
             let inodes = forgets
                 .into_iter()
                 .map(|forget_one| forget_one.nodeid)
@@ -3847,18 +4833,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_fallocate(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let fallocate_in = match get_bincode_config().deserialize::<fuse_fallocate_in>(data) {
+        let fallocate_in = match protocol::decode_body::<fuse_fallocate_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_fallocate_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3866,16 +4853,16 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(fallocate_in) => fallocate_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_fallocate"), async move {
+        self.spawn(priority, async move {
             debug!(
                 "fallocate unique {} inode {} {:?}",
                 request.unique, in_header.nodeid, fallocate_in
             );
 
-            let resp_value = if let Err(err) = fs
+            match fs
                 .fallocate(
                     request,
                     in_header.nodeid,
@@ -3886,22 +4873,9 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                 )
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
-
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
-
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
-
-            let _ = resp_sender.send(Either::Left(data)).await;
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
@@ -3909,18 +4883,19 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
     async fn handle_readdirplus(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let readdirplus_in = match get_bincode_config().deserialize::<fuse_read_in>(data) {
+        let readdirplus_in = match protocol::decode_body::<fuse_read_in>(data) {
             Err(err) => {
                 error!(
                     "deserialize fuse_read_in in readdirplus failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
@@ -3928,10 +4903,13 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
             Ok(readdirplus_in) => readdirplus_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let synthesize_dot_entries = self.mount_options.synthesize_dot_entries;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_readdirplus"), async move {
+        self.spawn_bulk(priority, async move {
             debug!(
                 "readdirplus unique {} parent {} {:?}",
                 request.unique, in_header.nodeid, readdirplus_in
@@ -3958,7 +4936,38 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
             let max_size = readdirplus_in.size as usize;
 
-            let mut entry_data = Vec::with_capacity(max_size);
+            // the header is backfilled after the entries are encoded, so the reply is sent in one
+            // buffer
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + max_size);
+            data.resize(FUSE_OUT_HEADER_SIZE, 0);
+
+            let dot_entries = match directory_plus.parent {
+                Some(parent) if synthesize_dot_entries => {
+                    dot_entries(in_header.nodeid, parent, readdirplus_in.offset)
+                }
+
+                _ => vec![],
+            };
+
+            for entry in dot_entries {
+                let name = &entry.name;
+
+                let dir_entry_size = FUSE_DIRENTPLUS_SIZE + name.len();
+
+                if data.len() - FUSE_OUT_HEADER_SIZE + dir_entry_size > max_size {
+                    break;
+                }
+
+                // the kernel never looks up "." and "..", the empty entry_out won't be used
+                protocol::encode_direntplus(
+                    &mut data,
+                    fuse_entry_out::default(),
+                    entry.inode,
+                    entry.offset as u64,
+                    entry.kind,
+                    name,
+                );
+            }
 
             let entries = directory_plus.entries;
             let mut entries = pin!(entries);
@@ -3978,204 +4987,383 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
 
                 let dir_entry_size = FUSE_DIRENTPLUS_SIZE + name.len();
 
-                let padding_size = get_padding_size(dir_entry_size);
-
-                if entry_data.len() + dir_entry_size > max_size {
+                if data.len() - FUSE_OUT_HEADER_SIZE + dir_entry_size > max_size {
                     break;
                 }
 
-                let attr = entry.attr;
-
-                let dir_entry = fuse_direntplus {
-                    entry_out: fuse_entry_out {
-                        nodeid: attr.ino,
-                        generation: entry.generation,
-                        entry_valid: entry.entry_ttl.as_secs(),
-                        attr_valid: entry.attr_ttl.as_secs(),
-                        entry_valid_nsec: entry.entry_ttl.subsec_nanos(),
-                        attr_valid_nsec: entry.attr_ttl.subsec_nanos(),
-                        attr: attr.into(),
-                    },
-                    dirent: fuse_dirent {
-                        ino: entry.inode,
-                        off: entry.offset as u64,
-                        namelen: name.len() as u32,
-                        // learn from fuse-rs and golang bazil.org fuse DirentType
-                        r#type: mode_from_kind_and_perm(entry.kind, 0) >> 12,
-                    },
-                };
+                let mut attr = entry.attr;
+                default_mode.apply(&mut attr);
 
-                get_bincode_config()
-                    .serialize_into(&mut entry_data, &dir_entry)
-                    .expect("won't happened");
+                // the kernel doesn't count the lookups of "." and ".."
+                if let Some(lookups) = &lookups {
+                    if name != "." && name != ".." {
+                        lookups.lookup(attr.ino, entry.generation);
+                    }
+                }
 
-                entry_data.extend_from_slice(name.as_bytes());
+                let entry_out = fuse_entry_out {
+                    nodeid: attr.ino,
+                    generation: entry.generation,
+                    entry_valid: entry.entry_ttl.as_secs(),
+                    attr_valid: entry.attr_ttl.as_secs(),
+                    entry_valid_nsec: entry.entry_ttl.subsec_nanos(),
+                    attr_valid_nsec: entry.attr_ttl.subsec_nanos(),
+                    attr: attr.into(),
+                };
 
-                // padding
-                entry_data.resize(entry_data.len() + padding_size, 0);
+                protocol::encode_direntplus(
+                    &mut data,
+                    entry_out,
+                    entry.inode,
+                    entry.offset as u64,
+                    entry.kind,
+                    name,
+                );
             }
 
-            // TODO find a way to avoid multi allocate
+            let body_len = data.len() - FUSE_OUT_HEADER_SIZE;
+            protocol::encode_out_header(
+                &mut &mut data[..FUSE_OUT_HEADER_SIZE],
+                request.unique,
+                0,
+                body_len,
+            );
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + entry_data.len()) as u32,
-                error: 0,
-                unique: request.unique,
-            };
+            send_reply(Either::Left(data), request, resp_sender).await;
+        });
+    }
+
+    #[instrument(skip(self, data, fs))]
+    async fn handle_rename2(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let RenameIn {
+            new_dir,
+            name,
+            new_name,
+            flags,
+        } = match protocol::decode_rename2(data) {
+            Err(err) => {
+                reply_decode_error("rename2", err, request, &mut self.response_sender).await;
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE);
+                return;
+            }
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
+            Ok(rename) => rename,
+        };
 
-            let _ = resp_sender
-                .send(Either::Right((data, entry_data.into())))
-                .await;
+        let resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        self.spawn(priority, async move {
+            debug!(
+                "rename2 unique {} parent {} name {:?} new parent {} new name {:?} flags {}",
+                request.unique, in_header.nodeid, name, new_dir, new_name, flags
+            );
+
+            match fs
+                .rename2(request, in_header.nodeid, &name, new_dir, &new_name, flags)
+                .await
+            {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
     #[instrument(skip(self, data, fs))]
-    async fn handle_rename2(
+    async fn handle_lseek(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
-        mut data: &[u8],
+        data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let rename2_in = match get_bincode_config().deserialize::<fuse_rename2_in>(data) {
+        let resp_sender = self.response_sender.clone();
+
+        let lseek_in = match protocol::decode_body::<fuse_lseek_in>(data) {
             Err(err) => {
                 error!(
-                    "deserialize fuse_rename2_in failed {}, request unique {}",
+                    "deserialize fuse_lseek_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(rename2_in) => rename2_in,
+            Ok(lseek_in) => lseek_in,
         };
 
-        data = &data[FUSE_RENAME2_IN_SIZE..];
+        let fs = fs.clone();
+
+        self.spawn(priority, async move {
+            debug!(
+                "lseek unique {} inode {} {:?}",
+                request.unique, in_header.nodeid, lseek_in
+            );
+
+            let reply_lseek = match fs
+                .lseek(
+                    request,
+                    in_header.nodeid,
+                    lseek_in.fh,
+                    lseek_in.offset,
+                    lseek_in.whence,
+                )
+                .await
+            {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(reply_lseek) => reply_lseek,
+            };
+
+            let lseek_out: fuse_lseek_out = reply_lseek.into();
+
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
+
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_LSEEK_OUT_SIZE);
+            protocol::encode_struct(&mut data, &lseek_out);
+
+            send_reply(Either::Left(data), request, resp_sender).await;
+        });
+    }
 
-        let (old_name, index) = match get_first_null_position(data) {
-            None => {
+    #[instrument(skip(self, data, fs))]
+    async fn handle_copy_file_range(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let resp_sender = self.response_sender.clone();
+
+        let copy_file_range_in = match protocol::decode_body::<fuse_copy_file_range_in>(data) {
+            Err(err) => {
                 error!(
-                    "fuse_rename2_in body doesn't have null, request unique {}",
-                    request.unique
+                    "deserialize fuse_copy_file_range_in failed {}, request unique {}",
+                    err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => (OsString::from_vec(data[..index].to_vec()), index),
+            Ok(copy_file_range_in) => copy_file_range_in,
         };
 
-        data = &data[index + 1..];
+        let fs = fs.clone();
 
-        let new_name = match get_first_null_position(data) {
-            None => {
+        self.spawn(priority, async move {
+            debug!(
+                "reply_copy_file_range unique {} inode {} {:?}",
+                request.unique, in_header.nodeid, copy_file_range_in
+            );
+
+            let reply_copy_file_range = match fs
+                .copy_file_range(
+                    request,
+                    in_header.nodeid,
+                    copy_file_range_in.fh_in,
+                    copy_file_range_in.off_in,
+                    copy_file_range_in.nodeid_out,
+                    copy_file_range_in.fh_out,
+                    copy_file_range_in.off_out,
+                    copy_file_range_in.len,
+                    copy_file_range_in.flags,
+                )
+                .await
+            {
+                Err(err) => {
+                    reply_error_in_place(err, request, resp_sender).await;
+
+                    return;
+                }
+
+                Ok(reply_copy_file_range) => reply_copy_file_range,
+            };
+
+            let write_out: fuse_write_out = reply_copy_file_range.into();
+
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE);
+
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_WRITE_OUT_SIZE);
+            protocol::encode_struct(&mut data, &write_out);
+
+            send_reply(Either::Left(data), request, resp_sender).await;
+        });
+    }
+
+    #[cfg(feature = "dax")]
+    #[instrument(skip(self, data, fs))]
+    async fn handle_setup_mapping(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let setup_mapping_in = match protocol::decode_body::<fuse_setupmapping_in>(data) {
+            Err(err) => {
                 error!(
-                    "fuse_rename2_in body doesn't have second null, request unique {}",
-                    request.unique
+                    "deserialize fuse_setupmapping_in failed {}, request unique {}",
+                    err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Some(index) => OsString::from_vec(data[..index].to_vec()),
+            Ok(setup_mapping_in) => setup_mapping_in,
         };
 
-        let mut resp_sender = self.response_sender.clone();
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
 
-        spawn(debug_span!("fuse_rename2"), async move {
+        self.spawn(priority, async move {
             debug!(
-                "rename2 unique {} parent {} name {:?} new parent {} new name {:?} flags {}",
-                request.unique,
-                in_header.nodeid,
-                old_name,
-                rename2_in.newdir,
-                new_name,
-                rename2_in.flags
+                "setup_mapping unique {} inode {} {:?}",
+                request.unique, in_header.nodeid, setup_mapping_in
             );
 
-            let resp_value = if let Err(err) = fs
-                .rename2(
+            match fs
+                .setup_mapping(
                     request,
                     in_header.nodeid,
-                    &old_name,
-                    rename2_in.newdir,
-                    &new_name,
-                    rename2_in.flags,
+                    setup_mapping_in.fh,
+                    setup_mapping_in.foffset,
+                    setup_mapping_in.len,
+                    setup_mapping_in.flags,
+                    setup_mapping_in.moffset,
                 )
                 .await
             {
-                err.into()
-            } else {
-                0
-            };
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
+        });
+    }
 
-            let out_header = fuse_out_header {
-                len: FUSE_OUT_HEADER_SIZE as u32,
-                error: resp_value,
-                unique: request.unique,
-            };
+    #[cfg(feature = "dax")]
+    #[instrument(skip(self, data, fs))]
+    async fn handle_remove_mapping(
+        &mut self,
+        request: Request,
+        priority: Priority,
+        in_header: fuse_in_header,
+        data: &[u8],
+        fs: &Arc<FS>,
+    ) {
+        let mappings = match protocol::decode_remove_mapping(data) {
+            Err(err) => {
+                error!(
+                    "decode removemapping failed {}, request unique {}",
+                    err, request.unique
+                );
+
+                reply_error_in_place(err.errno(), request, &mut self.response_sender).await;
+
+                return;
+            }
 
-            let data = get_bincode_config()
-                .serialize(&out_header)
-                .expect("won't happened");
+            Ok(mappings) => mappings,
+        };
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+        let resp_sender = self.response_sender.clone();
+        let fs = fs.clone();
+
+        self.spawn(priority, async move {
+            debug!(
+                "remove_mapping unique {} inode {} {:?}",
+                request.unique, in_header.nodeid, mappings
+            );
+
+            match fs
+                .remove_mapping(request, in_header.nodeid, &mappings)
+                .await
+            {
+                Err(err) => reply_error_in_place(err, request, resp_sender).await,
+                Ok(()) => reply_ok_in_place(request, resp_sender).await,
+            }
         });
     }
 
     #[instrument(skip(self, data, fs))]
-    async fn handle_lseek(
+    async fn handle_tmpfile(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let mut resp_sender = self.response_sender.clone();
+        let security_context = match protocol::decode_security_context(&in_header, data) {
+            Err(err) => {
+                error!(
+                    "tmpfile security context is invalid {}, request unique {}",
+                    err, request.unique
+                );
+
+                reply_error_in_place(err.errno(), request, &mut self.response_sender).await;
+
+                return;
+            }
+
+            Ok(security_context) => security_context,
+        };
 
-        let lseek_in = match get_bincode_config().deserialize::<fuse_lseek_in>(data) {
+        // the body has a name after fuse_create_in like create, but the tmpfile has no name,
+        // ignore it
+        let create_in = match protocol::decode_body::<fuse_create_in>(data) {
             Err(err) => {
                 error!(
-                    "deserialize fuse_lseek_in failed {}, request unique {}",
+                    "deserialize fuse_create_in failed {}, request unique {}",
                     err, request.unique
                 );
 
-                reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
                 return;
             }
 
-            Ok(lseek_in) => lseek_in,
+            Ok(create_in) => create_in,
         };
 
+        let keep_cache = self.mount_options.keep_cache;
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
+        let lookups = self.lookups.clone();
 
-        spawn(debug_span!("fuse_lseek"), async move {
+        self.spawn(priority, async move {
             debug!(
-                "lseek unique {} inode {} {:?}",
-                request.unique, in_header.nodeid, lseek_in
+                "tmpfile unique {} parent {} mode {} flags {}",
+                request.unique, in_header.nodeid, create_in.mode, create_in.flags
             );
 
-            let reply_lseek = match fs
-                .lseek(
+            let created = match fs
+                .tmpfile(
                     request,
                     in_header.nodeid,
-                    lseek_in.fh,
-                    lseek_in.offset,
-                    lseek_in.whence,
+                    create_in.mode,
+                    create_in.flags,
+                    security_context,
                 )
                 .await
             {
@@ -4185,75 +5373,93 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     return;
                 }
 
-                Ok(reply_lseek) => reply_lseek,
-            };
+                Ok(created) if created.attr.ino == 0 => {
+                    reply_invalid_entry("tmpfile", request, resp_sender).await;
 
-            let lseek_out: fuse_lseek_out = reply_lseek.into();
+                    return;
+                }
+
+                Ok(mut created) => {
+                    default_mode.apply(&mut created.attr);
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_LSEEK_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
+                    created
+                }
             };
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_OPEN_OUT_SIZE);
+            let (entry_out, mut open_out): (fuse_entry_out, fuse_open_out) = created.into();
+
+            if let Some(lookups) = &lookups {
+                lookups.lookup(entry_out.nodeid, entry_out.generation);
+            }
+            open_out.open_flags = keep_cache_flags(open_out.open_flags, keep_cache);
+
+            let mut data = ReplyBuffer::with_capacity(
+                FUSE_OUT_HEADER_SIZE + FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE,
+            );
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &lseek_out)
-                .expect("won't happened");
+            protocol::encode_out_header(
+                &mut data,
+                request.unique,
+                0,
+                FUSE_ENTRY_OUT_SIZE + FUSE_OPEN_OUT_SIZE,
+            );
+            protocol::encode_struct(&mut data, &entry_out);
+            protocol::encode_struct(&mut data, &open_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 
+    #[cfg(target_os = "linux")]
     #[instrument(skip(self, data, fs))]
-    async fn handle_copy_file_range(
+    async fn handle_statx(
         &mut self,
         request: Request,
+        priority: Priority,
         in_header: fuse_in_header,
         data: &[u8],
         fs: &Arc<FS>,
     ) {
-        let mut resp_sender = self.response_sender.clone();
-
-        let copy_file_range_in =
-            match get_bincode_config().deserialize::<fuse_copy_file_range_in>(data) {
-                Err(err) => {
-                    error!(
-                        "deserialize fuse_copy_file_range_in failed {}, request unique {}",
-                        err, request.unique
-                    );
+        let statx_in = match protocol::decode_body::<fuse_statx_in>(data) {
+            Err(err) => {
+                error!(
+                    "deserialize fuse_statx_in failed {}, request unique {}",
+                    err, request.unique
+                );
 
-                    reply_error_in_place(libc::EINVAL.into(), request, &self.response_sender).await;
+                reply_error_in_place(libc::EINVAL.into(), request, &mut self.response_sender).await;
 
-                    return;
-                }
+                return;
+            }
 
-                Ok(copy_file_range_in) => copy_file_range_in,
-            };
+            Ok(statx_in) => statx_in,
+        };
 
+        let resp_sender = self.response_sender.clone();
         let fs = fs.clone();
+        let default_mode = self.default_mode();
 
-        spawn(debug_span!("fuse_copy_file_range"), async move {
+        self.spawn(priority, async move {
             debug!(
-                "reply_copy_file_range unique {} inode {} {:?}",
-                request.unique, in_header.nodeid, copy_file_range_in
+                "statx unique {} inode {} mask {:#x}",
+                request.unique, in_header.nodeid, statx_in.sx_mask
             );
 
-            let reply_copy_file_range = match fs
-                .copy_file_range(
+            let flags = GetattrFlags::from(statx_in.getattr_flags);
+            let fh = if flags.has_fh() {
+                Some(statx_in.fh)
+            } else {
+                None
+            };
+
+            let statx_out: fuse_statx_out = match fs
+                .statx(
                     request,
                     in_header.nodeid,
-                    copy_file_range_in.fh_in,
-                    copy_file_range_in.off_in,
-                    copy_file_range_in.nodeid_out,
-                    copy_file_range_in.fh_out,
-                    copy_file_range_in.off_out,
-                    copy_file_range_in.len,
-                    copy_file_range_in.flags,
+                    fh,
+                    flags,
+                    statx_in.sx_flags,
+                    statx_in.sx_mask,
                 )
                 .await
             {
@@ -4263,46 +5469,396 @@ impl<FS: Filesystem + Send + Sync + 'static> Session<FS> {
                     return;
                 }
 
-                Ok(reply_copy_file_range) => reply_copy_file_range,
-            };
-
-            let write_out: fuse_write_out = reply_copy_file_range.into();
+                Ok(mut statx) => {
+                    default_mode.apply(&mut statx.attr);
 
-            let out_header = fuse_out_header {
-                len: (FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE) as u32,
-                error: 0,
-                unique: request.unique,
+                    statx.into()
+                }
             };
 
-            let mut data = Vec::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_WRITE_OUT_SIZE);
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_STATX_OUT_SIZE);
 
-            get_bincode_config()
-                .serialize_into(&mut data, &out_header)
-                .expect("won't happened");
-            get_bincode_config()
-                .serialize_into(&mut data, &write_out)
-                .expect("won't happened");
+            protocol::encode_out_header(&mut data, request.unique, 0, FUSE_STATX_OUT_SIZE);
+            protocol::encode_struct(&mut data, &statx_out);
 
-            let _ = resp_sender.send(Either::Left(data)).await;
+            send_reply(Either::Left(data), request, resp_sender).await;
         });
     }
 }
 
+/// the requests which have no reply or are managed by fuse3 itself, they are never rejected by
+/// the capabilities or the access filter. The release and releasedir errors are ignored by the
+/// kernel, if they are rejected, the file handles are never freed by the filesystem.
+fn is_session_managed(opcode: &fuse_opcode) -> bool {
+    matches!(
+        opcode,
+        fuse_opcode::FUSE_INIT
+            | fuse_opcode::FUSE_DESTROY
+            | fuse_opcode::FUSE_FORGET
+            | fuse_opcode::FUSE_BATCH_FORGET
+            | fuse_opcode::FUSE_INTERRUPT
+            | fuse_opcode::FUSE_NOTIFY_REPLY
+            | fuse_opcode::FUSE_RELEASE
+            | fuse_opcode::FUSE_RELEASEDIR
+    )
+}
+
+/// the requests which modify the filesystem, they are rejected with `EROFS` when
+/// [`MountOptions::read_only`][crate::MountOptions::read_only] is enabled.
+fn is_write_opcode(opcode: &fuse_opcode) -> bool {
+    matches!(
+        opcode,
+        fuse_opcode::FUSE_SETATTR
+            | fuse_opcode::FUSE_SYMLINK
+            | fuse_opcode::FUSE_MKNOD
+            | fuse_opcode::FUSE_MKDIR
+            | fuse_opcode::FUSE_UNLINK
+            | fuse_opcode::FUSE_RMDIR
+            | fuse_opcode::FUSE_RENAME
+            | fuse_opcode::FUSE_LINK
+            | fuse_opcode::FUSE_WRITE
+            | fuse_opcode::FUSE_SETXATTR
+            | fuse_opcode::FUSE_REMOVEXATTR
+            | fuse_opcode::FUSE_CREATE
+            | fuse_opcode::FUSE_FALLOCATE
+            | fuse_opcode::FUSE_RENAME2
+            | fuse_opcode::FUSE_COPY_FILE_RANGE
+            | fuse_opcode::FUSE_TMPFILE
+    )
+}
+
+/// check if the open `flags` may modify the file, such as `O_WRONLY` or `O_TRUNC`.
+fn is_write_open(flags: u32) -> bool {
+    let flags = flags as i32;
+
+    flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC > 0
+}
+
+/// add the `FOPEN_KEEP_CACHE` when [`MountOptions::keep_cache`] is enabled, unless the reply
+/// has `FOPEN_DIRECT_IO` which bypasses the page cache.
+fn keep_cache_flags(open_flags: u32, keep_cache: bool) -> u32 {
+    if keep_cache && open_flags & FOPEN_DIRECT_IO == 0 {
+        open_flags | FOPEN_KEEP_CACHE
+    } else {
+        open_flags
+    }
+}
+
+/// the permissions which replace the permission 0 of the replied attributes, they are set by
+/// [`MountOptions::default_file_mode`] and [`MountOptions::default_dir_mode`].
+#[derive(Debug, Copy, Clone)]
+struct DefaultMode {
+    file: Option<u16>,
+    dir: Option<u16>,
+}
+
+impl DefaultMode {
+    fn apply(self, attr: &mut FileAttr) {
+        if attr.perm != 0 {
+            return;
+        }
+
+        let perm = if attr.kind == FileType::Directory {
+            self.dir
+        } else {
+            self.file
+        };
+
+        if let Some(perm) = perm {
+            attr.perm = perm;
+        }
+    }
+}
+
+/// the "." and ".." entries synthesized by
+/// [`MountOptions::synthesize_dot_entries`][crate::MountOptions::synthesize_dot_entries], they use
+/// the reserved offsets 1 and 2, only the entries after `offset` are returned.
+fn dot_entries(inode: u64, parent: u64, offset: u64) -> Vec<DirectoryEntry> {
+    [(inode, "."), (parent, "..")]
+        .into_iter()
+        .zip(1..)
+        // a huge offset on 32-bit targets means all dot entries are read
+        .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+        .map(|((inode, name), offset)| DirectoryEntry {
+            inode,
+            kind: FileType::Directory,
+            name: OsString::from(name),
+            offset,
+        })
+        .collect()
+}
+
+/// reply a request which has no reply body with success.
+async fn reply_ok_in_place<S>(request: Request, sender: S)
+where
+    S: Sink<Response>,
+{
+    reply_header_in_place(0, request, sender).await
+}
+
 async fn reply_error_in_place<S>(err: Errno, request: Request, sender: S)
 where
-    S: Sink<Either<Vec<u8>, (Vec<u8>, Bytes)>>,
+    S: Sink<Response>,
+{
+    reply_header_in_place(err.into(), request, sender).await
+}
+
+/// reply a request which can't be decoded, such as its name is not a valid path component, with
+/// the errno of the decode error.
+async fn reply_decode_error<S>(op: &str, err: DecodeError, request: Request, sender: S)
+where
+    S: Sink<Response>,
+{
+    error!(
+        "decode {} request failed {}, request unique {}",
+        op, err, request.unique
+    );
+
+    reply_error_in_place(err.errno(), request, sender).await
+}
+
+/// the inode 0 is a negative entry, the request which creates an entry can't reply it, kernel
+/// would fail the request with `EIO`, so log the filesystem bug and reply `EIO` directly.
+async fn reply_invalid_entry<S>(op: &str, request: Request, sender: S)
+where
+    S: Sink<Response>,
+{
+    error!(
+        "{} unique {} replied inode 0 for the new entry, it is invalid",
+        op, request.unique
+    );
+
+    reply_error_in_place(libc::EIO.into(), request, sender).await
+}
+
+/// encode the getxattr or listxattr reply for the request `size`.
+///
+/// When `size` is 0, the kernel asks the value size and only accepts a `fuse_getxattr_out`,
+/// otherwise it only accepts the value no larger than `size`, the larger value would make the
+/// device write fail with `EINVAL`, it is replied `ERANGE` instead.
+fn encode_xattr_reply(xattr: ReplyXAttr, size: u32, request: Request) -> Result<FuseData, Errno> {
+    let xattr_size = match xattr {
+        ReplyXAttr::Size(xattr_size) => xattr_size,
+
+        ReplyXAttr::Data(xattr_data) if size > 0 => {
+            if xattr_data.len() > size as usize {
+                warn!(
+                    "xattr reply data len {} is larger than request size {}, reply ERANGE, \
+                     request unique {}",
+                    xattr_data.len(),
+                    size,
+                    request.unique
+                );
+
+                return Err(libc::ERANGE.into());
+            }
+
+            let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE);
+
+            protocol::encode_out_header(&mut data, request.unique, 0, xattr_data.len());
+
+            return Ok(Either::Right((data, xattr_data)));
+        }
+
+        ReplyXAttr::Data(xattr_data) => xattr_data.len() as u32,
+    };
+
+    // the value doesn't fit the request buffer
+    if size > 0 {
+        return Err(libc::ERANGE.into());
+    }
+
+    let getxattr_out = fuse_getxattr_out {
+        size: xattr_size,
+        _padding: 0,
+    };
+
+    let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE);
+
+    protocol::encode_out_header(&mut data, request.unique, 0, FUSE_GETXATTR_OUT_SIZE);
+    protocol::encode_struct(&mut data, &getxattr_out);
+
+    Ok(Either::Left(data))
+}
+
+/// reply a `fuse_out_header` only, the header len is always [`FUSE_OUT_HEADER_SIZE`].
+async fn reply_header_in_place<S>(error: i32, request: Request, sender: S)
+where
+    S: Sink<Response>,
 {
-    let out_header = fuse_out_header {
-        len: FUSE_OUT_HEADER_SIZE as u32,
-        error: err.into(),
-        unique: request.unique,
+    let mut data = ReplyBuffer::with_capacity(FUSE_OUT_HEADER_SIZE);
+
+    protocol::encode_out_header(&mut data, request.unique, error, 0);
+
+    send_reply(Either::Left(data), request, sender).await;
+}
+
+/// send the reply to the reply task, if the fuse connection is gone, the reply is dropped.
+async fn send_reply<S>(data: FuseData, request: Request, sender: S)
+where
+    S: Sink<Response>,
+{
+    if pin!(sender).send(Response::Reply(data)).await.is_err() {
+        debug!(
+            "fuse connection is closed, drop the reply of request unique {}",
+            request.unique
+        );
+    }
+}
+
+/// the readdir fallback to readdirplus is warned once in this process.
+static READDIR_FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// the active mounts of this process, the mount is inactive when its connection is dropped.
+static ACTIVE_MOUNTS: Mutex<Vec<(PathBuf, Weak<FuseConnection>)>> = Mutex::new(Vec::new());
+
+/// make the path absolute and resolve `.` and `..` lexically, it doesn't access the filesystem,
+/// so it won't send request to a fuse3 filesystem of this process.
+fn lexical_absolute_path(path: &Path) -> IoResult<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
     };
 
-    let data = get_bincode_config()
-        .serialize(&out_header)
-        .expect("won't happened");
+    let mut absolute_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute_path.pop();
+            }
+            component => absolute_path.push(component),
+        }
+    }
 
-    let _ = pin!(sender).send(Either::Left(data)).await;
+    Ok(absolute_path)
+}
+
+/// reject the mount path which is inside an active mount of this process, the mount needs to
+/// access the mount path, the request is handled by the same process, it may deadlock.
+///
+/// # Notes:
+///
+/// the mount path is compared lexically, the symlinks which point into an active mount are not
+/// detected.
+fn self_mount_check(mount_path: &Path) -> IoResult<()> {
+    let mount_path = lexical_absolute_path(mount_path)?;
+
+    let mut active_mounts = ACTIVE_MOUNTS.lock().unwrap_or_else(PoisonError::into_inner);
+    active_mounts.retain(|(_, connection)| connection.strong_count() > 0);
+
+    if let Some((active_mount_path, _)) = active_mounts
+        .iter()
+        .find(|(active_mount_path, _)| mount_path.starts_with(active_mount_path))
+    {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "mount path {} is inside the active mount {} of this process",
+                mount_path.display(),
+                active_mount_path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// record the active mount, it is removed after the fuse connection is dropped.
+fn register_mount(mount_path: &Path, fuse_connection: &Arc<FuseConnection>) {
+    // the path is already resolved by the self mount check, the mount is done so don't fail here
+    let Ok(mount_path) = lexical_absolute_path(mount_path) else {
+        return;
+    };
+
+    ACTIVE_MOUNTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push((mount_path, Arc::downgrade(fuse_connection)));
+}
+
+/// get the fuse connection id of the mount point, it is the minor number of the mount point
+/// device.
+///
+/// The id is found in `/proc/self/mountinfo` instead of calling `stat` on the mount point, so a
+/// deadlocked filesystem won't block it.
+#[cfg(target_os = "linux")]
+fn connection_id(mount_path: &Path) -> IoResult<u64> {
+    // don't canonicalize the mount point itself, it will send request to the filesystem
+    let mount_path = match (mount_path.parent(), mount_path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+
+            std::fs::canonicalize(parent)?.join(name)
+        }
+
+        _ => std::fs::canonicalize(mount_path)?,
+    };
+
+    // the paths are not UTF-8 if they are not UTF-8 on the host, they are parsed as bytes
+    let mount_info = std::fs::read("/proc/self/mountinfo")?;
+
+    // format: 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+    // the last one is the top mount when mount many times on the same path
+    mount_info
+        .split(|c| *c == b'\n')
+        .rev()
+        .find_map(|line| {
+            let separator = line.windows(3).position(|sep| sep == b" - ")?;
+            let mut fields = line[..separator].split(|c| *c == b' ');
+            let dev = fields.nth(2)?;
+            let path = fields.nth(1)?;
+
+            let fs_type = line[separator + 3..].split(|c| *c == b' ').next()?;
+            if fs_type != b"fuse" && !fs_type.starts_with(b"fuse.") {
+                return None;
+            }
+
+            if Path::new(&unescape_mount_info_path(path)) != mount_path {
+                return None;
+            }
+
+            let minor = dev.split(|c| *c == b':').nth(1)?;
+
+            std::str::from_utf8(minor).ok()?.parse::<u64>().ok()
+        })
+        .ok_or_else(|| {
+            IoError::new(
+                ErrorKind::NotFound,
+                format!("fuse mount point {} not found", mount_path.display()),
+            )
+        })
+}
+
+/// unescape the octal escaped path in `/proc/self/mountinfo`, like `\040` for space.
+#[cfg(target_os = "linux")]
+fn unescape_mount_info_path(path: &[u8]) -> OsString {
+    let mut unescaped = Vec::with_capacity(path.len());
+    let mut i = 0;
+
+    while i < path.len() {
+        if path[i] == b'\\' && i + 3 < path.len() {
+            if let Some(c) = std::str::from_utf8(&path[i + 1..i + 4])
+                .ok()
+                .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+            {
+                unescaped.push(c);
+                i += 4;
+
+                continue;
+            }
+        }
+
+        unescaped.push(path[i]);
+        i += 1;
+    }
+
+    OsString::from_vec(unescaped)
 }
 
 #[inline]
@@ -4317,3 +5873,95 @@ where
     #[cfg(all(not(feature = "tokio-runtime"), feature = "async-io-runtime"))]
     task::spawn(fut.instrument(span)).detach()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> Request {
+        Request {
+            unique: 7,
+            ..Default::default()
+        }
+    }
+
+    /// the `fuse_getxattr_out` size of the size probe reply.
+    fn probe_size(data: &FuseData) -> u32 {
+        let Either::Left(data) = data else {
+            panic!("size probe reply has extend data");
+        };
+
+        assert_eq!(
+            protocol::decode_out_header_len(data),
+            Some(FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE)
+        );
+        assert_eq!(data.len(), FUSE_OUT_HEADER_SIZE + FUSE_GETXATTR_OUT_SIZE);
+
+        u32::from_le_bytes(data[FUSE_OUT_HEADER_SIZE..][..4].try_into().unwrap())
+    }
+
+    #[test]
+    fn xattr_size_probe() {
+        let reply = encode_xattr_reply(ReplyXAttr::Size(10), 0, request()).unwrap();
+        assert_eq!(probe_size(&reply), 10);
+
+        // the data reply of the size probe only replies the data size
+        let reply =
+            encode_xattr_reply(ReplyXAttr::Data(Bytes::from_static(b"value")), 0, request())
+                .unwrap();
+        assert_eq!(probe_size(&reply), 5);
+    }
+
+    #[test]
+    fn xattr_data() {
+        let reply =
+            encode_xattr_reply(ReplyXAttr::Data(Bytes::from_static(b"value")), 5, request())
+                .unwrap();
+
+        let Either::Right((header, data)) = reply else {
+            panic!("data reply has no extend data");
+        };
+        assert_eq!(data, "value");
+        assert_eq!(
+            protocol::decode_out_header_len(&header),
+            Some(FUSE_OUT_HEADER_SIZE + 5)
+        );
+    }
+
+    #[test]
+    fn xattr_erange() {
+        let erange = Errno::from(libc::ERANGE);
+
+        // the size reply can't answer a request with a buffer
+        assert_eq!(
+            encode_xattr_reply(ReplyXAttr::Size(10), 20, request()).unwrap_err(),
+            erange
+        );
+
+        // the data doesn't fit the request buffer
+        assert_eq!(
+            encode_xattr_reply(ReplyXAttr::Data(Bytes::from_static(b"value")), 4, request())
+                .unwrap_err(),
+            erange
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unescape_mount_info() {
+        let unescape = |path: &[u8]| unescape_mount_info_path(path).into_vec();
+
+        assert_eq!(
+            unescape(b"/mnt/My\\040Cloud\\011Drive"),
+            b"/mnt/My Cloud\tDrive"
+        );
+        assert_eq!(unescape(b"/a\\134b"), b"/a\\b");
+
+        // the non UTF-8 path is kept as bytes
+        assert_eq!(unescape(b"/mnt/\xff\\040\xfe"), b"/mnt/\xff \xfe");
+
+        // the incomplete and invalid escapes are kept
+        assert_eq!(unescape(b"/a\\04"), b"/a\\04");
+        assert_eq!(unescape(b"/a\\999"), b"/a\\999");
+    }
+}
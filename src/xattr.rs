@@ -0,0 +1,51 @@
+//! extended attribute helpers.
+//!
+//! The xattr requests pass the raw attribute names, such as `user.foo`, the part before the first
+//! `.` is the namespace of the name, it can be got by [`namespace`].
+//!
+//! # Notes:
+//!
+//! many filesystems only support the `user.` namespace, they should reply `EOPNOTSUPP` for the
+//! other namespaces. But when the SELinux or other security modules are active, the
+//! `security.` namespace should still be allowed, or the labels can't be set. The permission of
+//! the `trusted.` namespace is checked by the kernel, a process without `CAP_SYS_ADMIN` gets
+//! `EPERM` when setting it, the filesystem won't see the request.
+//!
+//! [`MountOptions::xattr_namespaces`][crate::MountOptions::xattr_namespaces] can reject the
+//! unsupported namespaces before the requests reach the filesystem.
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// the namespace of an extended attribute name.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum XattrNamespace {
+    /// the `user.` namespace, the user defined attributes.
+    User,
+    /// the `security.` namespace, used by the security modules such as SELinux.
+    Security,
+    /// the `trusted.` namespace, only the privileged process can access it.
+    Trusted,
+    /// the `system.` namespace, such as the POSIX ACLs.
+    System,
+    /// the unknown namespace or the name has no namespace, such as the macOS attributes.
+    Other,
+}
+
+/// get the namespace of an extended attribute name.
+pub fn namespace(name: &OsStr) -> XattrNamespace {
+    let name = name.as_bytes();
+
+    let prefix = match name.iter().position(|&c| c == b'.') {
+        None => return XattrNamespace::Other,
+        Some(index) => &name[..index],
+    };
+
+    match prefix {
+        b"user" => XattrNamespace::User,
+        b"security" => XattrNamespace::Security,
+        b"trusted" => XattrNamespace::Trusted,
+        b"system" => XattrNamespace::System,
+        _ => XattrNamespace::Other,
+    }
+}